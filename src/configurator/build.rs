@@ -1,16 +1,28 @@
-use crate::configurator::parser::Configuration;
+use async_sqlite::Pool;
+use serde::Serialize;
 
-pub fn build_plan(configuration: Configuration) -> Plan {
-    let mut plan = Plan { year_plans: vec![] };
-    let config = &configuration;
+use crate::{
+    configurator::{
+        id_utils::{self, IdError},
+        parser::{Configuration, Event, Form, Year},
+    },
+    db::{events::Events, forms::Forms, years::Years},
+};
 
-    let mut empty_scores = serde_json::json!({});
+/// Lay out the years and events a config generates. Year, gender and event ids are canonicalized
+/// up front, so every id, year id, gender id and filter key in the plan is the canonical one.
+pub fn build_plan(configuration: Configuration) -> Result<Plan, IdError> {
+    let config = &configuration.canonicalized()?;
+    let mut plan = Plan {
+        year_plans: vec![],
+        forms: config.forms.clone(),
+    };
 
-    for form in config.forms.iter() {
-        empty_scores[form.id.clone()] = 0.into();
+    for warning in config.warnings() {
+        log::warn!("{}", warning);
     }
 
-    let empty_scores = empty_scores.to_string();
+    let empty_scores = config.empty_scores();
 
     for year in config.years.iter() {
         let year_id = year.id.clone();
@@ -23,32 +35,144 @@ pub fn build_plan(configuration: Configuration) -> Plan {
         };
 
         for event in config.events.iter() {
-            if !configuration.is_event_applicable_to_year(event, &year.clone().id) {
-                continue;
-            }
-            for gender in config.genders.iter() {
-                if configuration.is_event_applicable_to_gender(event, gender) {
-                    year_plan.events.push(EventPlan {
-                        id: format!("{}-{}-{}", year_plan.clone().id, gender, event.clone().id),
-                        name: event.clone().name,
-                        gender_id: gender.clone(),
-                        filter_key: event.clone().id,
-                        scores: empty_scores.clone(),
-                    })
-                }
+            let event = &config.effective_event(event);
+            for expanded in expand_canonical(event, std::slice::from_ref(year), &config.genders)? {
+                year_plan.events.push(EventPlan {
+                    id: expanded.id,
+                    name: event.clone().name,
+                    gender_id: expanded.gender_id,
+                    filter_key: event.clone().id,
+                    scores: empty_scores.clone(),
+                    tags: event.tags.clone(),
+                })
             }
         }
         plan.year_plans.push(year_plan);
     }
-    plan
+    Ok(plan)
 }
 
-#[derive(Debug)]
+/// Every (year, gender) combination an event applies to, in year then gender order, with
+/// canonical ids
+pub fn expand_event(
+    event: &Event,
+    years: &[Year],
+    genders: &[String],
+) -> Result<Vec<ExpandedEvent>, IdError> {
+    let years = years
+        .iter()
+        .map(|year| {
+            Ok(Year {
+                id: id_utils::canonicalize(&year.id)?,
+                name: year.name.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>, IdError>>()?;
+    expand_canonical(
+        &event.canonicalized()?,
+        &years,
+        &id_utils::canonicalize_all(genders)?,
+    )
+}
+
+/// `expand_event` for ids that are already canonical
+fn expand_canonical(
+    event: &Event,
+    years: &[Year],
+    genders: &[String],
+) -> Result<Vec<ExpandedEvent>, IdError> {
+    let mut expanded = vec![];
+    for year in years {
+        if !event.applicable_years.applies_to(&year.id) {
+            continue;
+        }
+        for gender in genders {
+            if event.applicable_genders.applies_to(gender) {
+                expanded.push(ExpandedEvent {
+                    id: event_id(&year.id, gender, &event.id)?,
+                    year_id: year.id.clone(),
+                    gender_id: gender.clone(),
+                });
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+/// `{year}-{gender}-{event}` from canonicalized parts
+pub fn event_id(year_id: &str, gender_id: &str, event_id: &str) -> Result<String, IdError> {
+    Ok([
+        id_utils::canonicalize(year_id)?,
+        id_utils::canonicalize(gender_id)?,
+        id_utils::canonicalize(event_id)?,
+    ]
+    .join("-"))
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ExpandedEvent {
+    pub id: String,
+    pub year_id: String,
+    pub gender_id: String,
+}
+
+#[derive(Debug, PartialEq)]
 pub struct Plan {
     pub year_plans: Vec<YearPlan>,
+    pub forms: Vec<Form>,
 }
 
-#[derive(Debug, Clone)]
+impl Plan {
+    /// The structure that is live in the database, laid out as `build_plan` lays it out so the
+    /// two can be compared. Events keep the ids, genders and filter keys they were stored with
+    /// rather than having them split back out of the id, so manual events and ids that don't
+    /// follow `{year}-{gender}-{event}` come through as they are. Events whose year is missing
+    /// are left out.
+    pub async fn from_db(pool: &Pool) -> Result<Plan, async_sqlite::Error> {
+        let forms = Forms::all(pool)
+            .await?
+            .into_iter()
+            .map(|form| Form {
+                id: form.id,
+                name: form.name,
+                colour: form.colour,
+            })
+            .collect();
+        let mut year_plans: Vec<YearPlan> = Years::all(pool)
+            .await?
+            .into_iter()
+            .map(|year| YearPlan {
+                id: year.id,
+                name: year.name,
+                events: vec![],
+            })
+            .collect();
+
+        for event in Events::all(pool).await? {
+            let Some(year_plan) = year_plans.iter_mut().find(|year| year.id == event.year_id)
+            else {
+                log::warn!(
+                    "Leaving event {} out of the plan as year {} doesn't exist",
+                    event.id,
+                    event.year_id
+                );
+                continue;
+            };
+            year_plan.events.push(EventPlan {
+                id: event.id,
+                name: event.name,
+                gender_id: event.gender_id,
+                filter_key: event.filter_key,
+                scores: event.scores,
+                tags: event.tags,
+            });
+        }
+
+        Ok(Plan { year_plans, forms })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 
 pub struct YearPlan {
     pub id: String,
@@ -56,7 +180,7 @@ pub struct YearPlan {
     pub events: Vec<EventPlan>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 
 pub struct EventPlan {
     pub id: String,
@@ -64,12 +188,19 @@ pub struct EventPlan {
     pub gender_id: String,
     pub filter_key: String,
     pub scores: String,
+    pub tags: Vec<String>,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::configurator::parser::{ApplicabilityRules, Event, Form, Score, Year};
+    use crate::{
+        configurator::{
+            parser::{ApplicabilityRules, ConfigWarning, Event, Form, Score, Year},
+            run::run,
+        },
+        test_harness,
+    };
 
     #[test]
     fn test_build_plan_empty_config() {
@@ -80,9 +211,10 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
-        let plan = build_plan(config);
+        let plan = build_plan(config).unwrap();
         assert_eq!(plan.year_plans.len(), 0);
     }
 
@@ -98,9 +230,10 @@ mod tests {
             }],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
-        let plan = build_plan(config);
+        let plan = build_plan(config).unwrap();
         assert_eq!(plan.year_plans.len(), 1);
         assert_eq!(plan.year_plans[0].id, "year7");
         assert_eq!(plan.year_plans[0].events.len(), 0);
@@ -130,10 +263,12 @@ mod tests {
                 name: "Event 1".to_string(),
                 applicable_years: ApplicabilityRules::All,
                 applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
             }],
+            ..Default::default()
         };
 
-        let plan = build_plan(config);
+        let plan = build_plan(config).unwrap();
         assert_eq!(plan.year_plans.len(), 1);
         assert_eq!(plan.year_plans[0].events.len(), 1);
         assert_eq!(plan.year_plans[0].events[0].name, "Event 1");
@@ -156,10 +291,12 @@ mod tests {
                 name: "Event 1".to_string(),
                 applicable_years: ApplicabilityRules::All,
                 applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
             }],
+            ..Default::default()
         };
 
-        let plan = build_plan(config);
+        let plan = build_plan(config).unwrap();
         assert_eq!(plan.year_plans[0].events.len(), 2);
         assert_eq!(plan.year_plans[0].events[0].gender_id, "boys");
         assert_eq!(plan.year_plans[0].events[1].gender_id, "girls");
@@ -189,10 +326,12 @@ mod tests {
                     ids: vec!["year7".to_string()],
                 },
                 applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
             }],
+            ..Default::default()
         };
 
-        let plan = build_plan(config);
+        let plan = build_plan(config).unwrap();
         assert_eq!(plan.year_plans.len(), 2);
         assert_eq!(plan.year_plans[0].events.len(), 1);
         assert_eq!(plan.year_plans[1].events.len(), 0);
@@ -216,10 +355,12 @@ mod tests {
                 applicable_genders: ApplicabilityRules::Include {
                     ids: vec!["boys".to_string()],
                 },
+                tags: vec![],
             }],
+            ..Default::default()
         };
 
-        let plan = build_plan(config);
+        let plan = build_plan(config).unwrap();
         assert_eq!(plan.year_plans[0].events.len(), 1);
         assert_eq!(plan.year_plans[0].events[0].gender_id, "boys");
     }
@@ -240,10 +381,12 @@ mod tests {
                 name: "Event 1".to_string(),
                 applicable_years: ApplicabilityRules::All,
                 applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
             }],
+            ..Default::default()
         };
 
-        let plan = build_plan(config);
+        let plan = build_plan(config).unwrap();
         assert_eq!(plan.year_plans[0].events[0].id, "year7-mixed-event1");
     }
 
@@ -274,13 +417,369 @@ mod tests {
                 name: "Event 1".to_string(),
                 applicable_years: ApplicabilityRules::All,
                 applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
             }],
+            ..Default::default()
         };
 
-        let plan = build_plan(config);
+        let plan = build_plan(config).unwrap();
         let scores = &plan.year_plans[0].events[0].scores;
         assert!(scores.contains("form1"));
         assert!(scores.contains("form2"));
         assert!(scores.contains(":0"));
     }
+
+    #[test]
+    fn test_expected_event_count_matches_plan() {
+        let config = Configuration {
+            version: "1.0.0".to_string(),
+            genders: vec!["boys".to_string(), "girls".to_string(), "mixed".to_string()],
+            scores: vec![],
+            years: ["year7", "year8", "year9"]
+                .iter()
+                .map(|id| Year {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                })
+                .collect(),
+            forms: vec![],
+            events: vec![
+                Event {
+                    id: "sprint".to_string(),
+                    name: "Sprint".to_string(),
+                    applicable_years: ApplicabilityRules::All,
+                    applicable_genders: ApplicabilityRules::Exclude {
+                        ids: vec!["mixed".to_string()],
+                    },
+                    tags: vec![],
+                },
+                Event {
+                    id: "relay".to_string(),
+                    name: "Relay".to_string(),
+                    applicable_years: ApplicabilityRules::Exclude {
+                        ids: vec!["year7".to_string()],
+                    },
+                    applicable_genders: ApplicabilityRules::Include {
+                        ids: vec!["mixed".to_string()],
+                    },
+                    tags: vec![],
+                },
+                Event {
+                    id: "disabled".to_string(),
+                    name: "Disabled".to_string(),
+                    applicable_years: ApplicabilityRules::None,
+                    applicable_genders: ApplicabilityRules::All,
+                    tags: vec![],
+                },
+            ],
+            ..Default::default()
+        };
+
+        let expected = config.expected_event_count();
+        let plan = build_plan(config).unwrap();
+        let actual: usize = plan.year_plans.iter().map(|y| y.events.len()).sum();
+
+        assert_eq!(expected, 8);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_build_plan_auto_include_mixed() {
+        let mut config = Configuration {
+            genders: vec!["boys".to_string(), "girls".to_string(), "mixed".to_string()],
+            years: vec![Year {
+                id: "year7".to_string(),
+                name: "Year 7".to_string(),
+            }],
+            events: vec![
+                Event {
+                    id: "relay".to_string(),
+                    name: "Relay".to_string(),
+                    applicable_years: ApplicabilityRules::All,
+                    applicable_genders: ApplicabilityRules::Include {
+                        ids: vec!["boys".to_string(), "girls".to_string()],
+                    },
+                    tags: vec![],
+                },
+                Event {
+                    id: "sprint".to_string(),
+                    name: "Sprint".to_string(),
+                    applicable_years: ApplicabilityRules::All,
+                    applicable_genders: ApplicabilityRules::Exclude {
+                        ids: vec!["mixed".to_string()],
+                    },
+                    tags: vec![],
+                },
+            ],
+            ..Default::default()
+        };
+        let ids = |config: &Configuration| -> Vec<String> {
+            build_plan(config.clone()).unwrap().year_plans[0]
+                .events
+                .iter()
+                .map(|event| event.id.clone())
+                .collect()
+        };
+        assert_eq!(ids(&config).len(), 4);
+
+        config.auto_include_mixed = true;
+        assert_eq!(
+            ids(&config),
+            vec![
+                "year7-boys-relay",
+                "year7-girls-relay",
+                "year7-mixed-relay",
+                "year7-boys-sprint",
+                "year7-girls-sprint",
+                "year7-mixed-sprint",
+            ]
+        );
+        assert_eq!(config.expected_event_count(), 6);
+    }
+
+    #[test]
+    fn test_build_plan_no_genders() {
+        let config = Configuration {
+            version: "1.0.0".to_string(),
+            genders: vec![],
+            years: vec![Year {
+                id: "year7".to_string(),
+                name: "Year 7".to_string(),
+            }],
+            events: vec![Event {
+                id: "100m".to_string(),
+                name: "100m".to_string(),
+                applicable_years: ApplicabilityRules::All,
+                applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(config.warnings(), vec![ConfigWarning::NoGenders]);
+        let plan = build_plan(config).unwrap();
+        assert_eq!(plan.year_plans.len(), 1);
+        assert!(plan.year_plans[0].events.is_empty());
+    }
+
+    #[test]
+    fn test_event_with_no_applicable_genders_warns() {
+        let config = Configuration {
+            genders: vec!["boys".to_string(), "girls".to_string()],
+            events: vec![
+                Event {
+                    id: "relay".to_string(),
+                    name: "Relay".to_string(),
+                    applicable_years: ApplicabilityRules::All,
+                    applicable_genders: ApplicabilityRules::Include {
+                        ids: vec!["mixed".to_string()],
+                    },
+                    tags: vec![],
+                },
+                Event {
+                    id: "100m".to_string(),
+                    name: "100m".to_string(),
+                    applicable_years: ApplicabilityRules::All,
+                    applicable_genders: ApplicabilityRules::Exclude {
+                        ids: vec!["girls".to_string()],
+                    },
+                    tags: vec![],
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.warnings(),
+            vec![ConfigWarning::NoApplicableGenders {
+                event_id: "relay".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_expand_event_include_exclude() {
+        let years = vec![
+            Year {
+                id: "year7".to_string(),
+                name: "Year 7".to_string(),
+            },
+            Year {
+                id: "year8".to_string(),
+                name: "Year 8".to_string(),
+            },
+            Year {
+                id: "year9".to_string(),
+                name: "Year 9".to_string(),
+            },
+        ];
+        let genders = vec!["boys".to_string(), "girls".to_string(), "mixed".to_string()];
+        let event = Event {
+            id: "relay".to_string(),
+            name: "Relay".to_string(),
+            applicable_years: ApplicabilityRules::Exclude {
+                ids: vec!["year8".to_string()],
+            },
+            applicable_genders: ApplicabilityRules::Include {
+                ids: vec!["boys".to_string(), "girls".to_string()],
+            },
+            tags: vec![],
+        };
+
+        let ids: Vec<String> = expand_event(&event, &years, &genders)
+            .unwrap()
+            .into_iter()
+            .map(|expanded| expanded.id)
+            .collect();
+
+        assert_eq!(
+            ids,
+            vec![
+                "year7-boys-relay",
+                "year7-girls-relay",
+                "year9-boys-relay",
+                "year9-girls-relay",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_event_canonicalizes_ids() {
+        let years = vec![Year {
+            id: " Year7".to_string(),
+            name: "Year 7".to_string(),
+        }];
+        let genders = vec!["Boys".to_string()];
+        let event = Event {
+            id: "Relay ".to_string(),
+            name: "Relay".to_string(),
+            applicable_years: ApplicabilityRules::All,
+            applicable_genders: ApplicabilityRules::All,
+            tags: vec![],
+        };
+
+        let expanded = expand_event(&event, &years, &genders).unwrap();
+        assert_eq!(expanded[0].id, "year7-boys-relay");
+        assert_eq!(expanded[0].year_id, "year7");
+        assert_eq!(expanded[0].gender_id, "boys");
+    }
+
+    #[test]
+    fn test_event_id_rejects_bad_ids() {
+        assert_eq!(
+            event_id(" Y7", "Boys", "Relay"),
+            Ok("y7-boys-relay".to_string())
+        );
+        assert_eq!(
+            event_id("y7", "boys", "sack race"),
+            Err(IdError::Whitespace("sack race".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_build_plan_canonicalizes_ids() {
+        let config = Configuration {
+            genders: vec!["Boys".to_string()],
+            years: vec![Year {
+                id: " Y7".to_string(),
+                name: "Year 7".to_string(),
+            }],
+            events: vec![Event {
+                id: "Relay".to_string(),
+                name: "Relay".to_string(),
+                applicable_years: ApplicabilityRules::Include {
+                    ids: vec!["Y7".to_string()],
+                },
+                applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
+            }],
+            ..Default::default()
+        };
+
+        let plan = build_plan(config).unwrap();
+        assert_eq!(plan.year_plans[0].id, "y7");
+        let event = &plan.year_plans[0].events[0];
+        assert_eq!(event.id, "y7-boys-relay");
+        assert_eq!(event.gender_id, "boys");
+        assert_eq!(event.filter_key, "relay");
+
+        let bad = Configuration {
+            years: vec![Year {
+                id: "Year 7".to_string(),
+                name: "Year 7".to_string(),
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            build_plan(bad),
+            Err(IdError::Whitespace("Year 7".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plan_from_db() {
+        let pool = test_harness::setup_db("plan_from_db").await;
+        let config = Configuration {
+            genders: vec!["boys".to_string(), "girls".to_string()],
+            years: ["year7", "year8"]
+                .iter()
+                .map(|id| Year {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                })
+                .collect(),
+            forms: vec![Form {
+                id: "form1".to_string(),
+                name: "Form 1".to_string(),
+                colour: "#ff0000".to_string(),
+            }],
+            events: vec![
+                Event {
+                    id: "100m".to_string(),
+                    name: "100m".to_string(),
+                    applicable_years: ApplicabilityRules::All,
+                    applicable_genders: ApplicabilityRules::All,
+                    tags: vec!["track".to_string()],
+                },
+                Event {
+                    id: "relay".to_string(),
+                    name: "Relay".to_string(),
+                    applicable_years: ApplicabilityRules::Include {
+                        ids: vec!["year8".to_string()],
+                    },
+                    applicable_genders: ApplicabilityRules::Include {
+                        ids: vec!["girls".to_string()],
+                    },
+                    tags: vec![],
+                },
+            ],
+            ..Default::default()
+        };
+
+        run(build_plan(config.clone()).unwrap(), &pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            Plan::from_db(&pool).await.unwrap(),
+            build_plan(config).unwrap()
+        );
+
+        // An id that doesn't split into year, gender and event is kept as it is
+        Events::new(
+            "egg and spoon".to_string(),
+            "Egg and Spoon".to_string(),
+            "year7".to_string(),
+            "mixed".to_string(),
+            "egg".to_string(),
+            "{}".to_string(),
+        )
+        .insert(&pool)
+        .await
+        .unwrap();
+        let plan = Plan::from_db(&pool).await.unwrap();
+        let egg = plan.year_plans[0].events.last().unwrap();
+        assert_eq!(egg.id, "egg and spoon");
+        assert_eq!(egg.gender_id, "mixed");
+        assert_eq!(egg.filter_key, "egg");
+    }
 }
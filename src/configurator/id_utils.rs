@@ -0,0 +1,85 @@
+/// Why an id can't be used to build event ids
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdError {
+    Empty,
+    Whitespace(String),
+    InvalidChar { id: String, ch: char },
+}
+
+impl std::fmt::Display for IdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            IdError::Empty => write!(f, "id is empty"),
+            IdError::Whitespace(id) => write!(f, "id '{}' contains whitespace", id),
+            IdError::InvalidChar { id, ch } => {
+                write!(f, "id '{}' contains invalid character '{}'", id, ch)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IdError {}
+
+/// Trim and lowercase an id, rejecting anything other than letters, digits, `-` and `_` so
+/// ids like `Year 7` can't silently produce a different key to `year7`
+pub fn canonicalize(raw: &str) -> Result<String, IdError> {
+    let id = raw.trim();
+    if id.is_empty() {
+        return Err(IdError::Empty);
+    }
+    if id.chars().any(char::is_whitespace) {
+        return Err(IdError::Whitespace(id.to_string()));
+    }
+    if let Some(ch) = id
+        .chars()
+        .find(|ch| !(ch.is_ascii_alphanumeric() || *ch == '-' || *ch == '_'))
+    {
+        return Err(IdError::InvalidChar {
+            id: id.to_string(),
+            ch,
+        });
+    }
+    Ok(id.to_ascii_lowercase())
+}
+
+/// `canonicalize` for a list of ids, failing on the first that doesn't canonicalize
+pub fn canonicalize_all(raw: &[String]) -> Result<Vec<String>, IdError> {
+    raw.iter().map(|id| canonicalize(id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize_valid() {
+        assert_eq!(canonicalize("y7"), Ok("y7".to_string()));
+        assert_eq!(canonicalize("100m_Relay"), Ok("100m_relay".to_string()));
+        assert_eq!(canonicalize("year-7"), Ok("year-7".to_string()));
+    }
+
+    #[test]
+    fn test_canonicalize_trims() {
+        assert_eq!(canonicalize("  Year7\n"), Ok("year7".to_string()));
+    }
+
+    #[test]
+    fn test_canonicalize_rejects_spaces() {
+        assert_eq!(
+            canonicalize("Year 7"),
+            Err(IdError::Whitespace("Year 7".to_string()))
+        );
+        assert_eq!(canonicalize("   "), Err(IdError::Empty));
+    }
+
+    #[test]
+    fn test_canonicalize_rejects_invalid_chars() {
+        assert_eq!(
+            canonicalize("y7/boys"),
+            Err(IdError::InvalidChar {
+                id: "y7/boys".to_string(),
+                ch: '/',
+            })
+        );
+    }
+}
@@ -1,3 +1,5 @@
 pub mod build;
+pub mod id_utils;
 pub mod parser;
 pub mod run;
+pub mod shared;
@@ -1,7 +1,11 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::configurator::id_utils::{self, IdError};
+
 /// Main configuration structure containing all years, forms, and events
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Configuration {
     /// Config Version
     pub version: String,
@@ -15,6 +19,187 @@ pub struct Configuration {
     pub forms: Vec<Form>,
     /// All available events with their applicability rules
     pub events: Vec<Event>,
+    /// Orientation of the scoreboard table
+    #[serde(default)]
+    pub scoreboard_layout: ScoreboardLayout,
+    /// Identifier for this school in exported results
+    #[serde(default)]
+    pub school_id: String,
+    /// Order genders are displayed in, genders not listed follow in `genders` order
+    #[serde(default)]
+    pub gender_order: Vec<String>,
+    /// Upper bounds on the size of the config
+    #[serde(default)]
+    pub limits: ConfigLimits,
+    /// How totals are written on the scoreboard
+    #[serde(default)]
+    pub number_format: NumberFormat,
+    /// Archive scored events before a new version of the config removes them
+    #[serde(default)]
+    pub auto_archive_on_reload: bool,
+    /// Lowest score accepted for a form, unbounded if unset
+    #[serde(default)]
+    pub min_score: Option<i64>,
+    /// Highest score accepted for a form, unbounded if unset
+    #[serde(default)]
+    pub max_score: Option<i64>,
+    /// Sponsor message or notice shown above the scoreboard and on the home page
+    #[serde(default)]
+    pub announcement: Option<String>,
+    /// Gender id to the factor its events' points are scaled by in the totals, 1.0 if unlisted.
+    /// Stored scores are left as entered.
+    #[serde(default)]
+    pub gender_multipliers: BTreeMap<String, f64>,
+    /// Also run an event as "mixed" whenever it applies to any other gender
+    #[serde(default)]
+    pub auto_include_mixed: bool,
+}
+
+/// How numbers are displayed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NumberFormat {
+    /// 12345
+    #[default]
+    Plain,
+    /// 12,345
+    Grouped,
+}
+
+impl NumberFormat {
+    /// Whole numbers are shown without a decimal point, see `utils::format_points`
+    pub fn format(&self, value: &f64) -> String {
+        crate::utils::format_points(*value, *self == NumberFormat::Grouped)
+    }
+}
+
+/// Upper bounds that stop an oversized config from generating an enormous plan
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ConfigLimits {
+    pub max_forms: usize,
+    pub max_years: usize,
+    pub max_events: usize,
+    /// Maximum number of year/gender/event combinations `build_plan` may generate
+    pub max_generated_events: usize,
+}
+
+impl Default for ConfigLimits {
+    fn default() -> Self {
+        Self {
+            max_forms: 100,
+            max_years: 100,
+            max_events: 500,
+            max_generated_events: 10_000,
+        }
+    }
+}
+
+/// A problem with the configuration that should be fixed in the YAML
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// `field` has more entries than its configured limit
+    TooMany {
+        field: &'static str,
+        count: usize,
+        max: usize,
+    },
+    /// More than one entry in `field` ("years", "genders", "forms" or "events") has this id, once
+    /// canonicalized where the field is
+    DuplicateId { field: &'static str, id: String },
+    /// An event's `applicable_genders` lists a gender that isn't in `genders`
+    UnknownGender { event_id: String, gender_id: String },
+    /// `scores` should have exactly one entry with `default: true`
+    DefaultScores { count: usize },
+    /// A year, gender or event id that can't be used to build event ids
+    InvalidId { field: &'static str, error: IdError },
+    /// A `gender_multipliers` entry that is negative or not a number
+    InvalidMultiplier { gender_id: String, multiplier: f64 },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::TooMany { field, count, max } => {
+                write!(f, "{} has {} entries, the limit is {}", field, count, max)
+            }
+            ConfigError::DuplicateId { field, id } => {
+                write!(f, "{} has more than one entry with id \"{}\"", field, id)
+            }
+            ConfigError::UnknownGender {
+                event_id,
+                gender_id,
+            } => write!(
+                f,
+                "event \"{}\" applicable_genders refers to \"{}\", which isn't in genders",
+                event_id, gender_id
+            ),
+            ConfigError::DefaultScores { count } => write!(
+                f,
+                "scores should have exactly one default: true entry, found {}",
+                count
+            ),
+            ConfigError::InvalidId { field, error } => write!(f, "{}: {}", field, error),
+            ConfigError::InvalidMultiplier {
+                gender_id,
+                multiplier,
+            } => write!(
+                f,
+                "gender_multipliers \"{}\" is {}, it should be a number of at least 0",
+                gender_id, multiplier
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Something in the configuration that is allowed but probably a mistake
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigWarning {
+    /// `genders` is empty, so no events will be generated at all
+    NoGenders,
+    /// An event's `applicable_genders` rules out every configured gender
+    NoApplicableGenders { event_id: String },
+}
+
+impl std::fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigWarning::NoGenders => {
+                write!(f, "genders is empty, so no events will be created")
+            }
+            ConfigWarning::NoApplicableGenders { event_id } => write!(
+                f,
+                "event \"{}\" applicable_genders matches none of the genders, so it won't be created",
+                event_id
+            ),
+        }
+    }
+}
+
+/// Every problem `Configuration::validate` found, so `from_yaml_file` can report them together
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigErrors(pub Vec<ConfigError>);
+
+impl std::fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let errors: Vec<String> = self.0.iter().map(|error| error.to_string()).collect();
+        write!(f, "{}", errors.join("; "))
+    }
+}
+
+impl std::error::Error for ConfigErrors {}
+
+/// Which axis of the scoreboard the forms run along
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreboardLayout {
+    /// One row per year, one column per form
+    #[default]
+    FormsAsColumns,
+    /// One row per form, one column per year
+    YearsAsColumns,
 }
 
 /// Represents a school year
@@ -27,7 +212,7 @@ pub struct Year {
 }
 
 /// Represents a form/class level
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Form {
     /// Unique identifier (e.g., "year7", "year8", "reception")
     pub id: String,
@@ -48,6 +233,9 @@ pub struct Event {
     pub applicable_years: ApplicabilityRules,
     /// Rules for which gender this event applies to
     pub applicable_genders: ApplicabilityRules,
+    /// Categories for filtering, e.g. "track", "field", "fun"
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -75,44 +263,411 @@ pub enum ApplicabilityRules {
     Exclude { ids: Vec<String> },
 }
 
+impl Event {
+    /// The event with its id and applicability rules canonicalized
+    pub fn canonicalized(&self) -> Result<Self, IdError> {
+        Ok(Event {
+            id: id_utils::canonicalize(&self.id)?,
+            applicable_years: self.applicable_years.canonicalized()?,
+            applicable_genders: self.applicable_genders.canonicalized()?,
+            ..self.clone()
+        })
+    }
+}
+
+impl ApplicabilityRules {
+    /// Check if these rules allow the given year or gender id
+    pub fn applies_to(&self, id: &str) -> bool {
+        match self {
+            ApplicabilityRules::All => true,
+            ApplicabilityRules::None => false,
+            ApplicabilityRules::Include { ids } => ids.iter().any(|i| i == id),
+            ApplicabilityRules::Exclude { ids } => !ids.iter().any(|i| i == id),
+        }
+    }
+
+    /// The same rules with every id canonicalized
+    pub fn canonicalized(&self) -> Result<Self, IdError> {
+        Ok(match self {
+            ApplicabilityRules::Include { ids } => ApplicabilityRules::Include {
+                ids: id_utils::canonicalize_all(ids)?,
+            },
+            ApplicabilityRules::Exclude { ids } => ApplicabilityRules::Exclude {
+                ids: id_utils::canonicalize_all(ids)?,
+            },
+            rules => rules.clone(),
+        })
+    }
+
+    /// The same rules, also applying to `id`
+    pub fn including(&self, id: &str) -> Self {
+        match self {
+            ApplicabilityRules::Include { ids } if !ids.iter().any(|i| i == id) => {
+                let mut ids = ids.clone();
+                ids.push(id.to_string());
+                ApplicabilityRules::Include { ids }
+            }
+            ApplicabilityRules::Exclude { ids } => ApplicabilityRules::Exclude {
+                ids: ids.iter().filter(|i| *i != id).cloned().collect(),
+            },
+            ApplicabilityRules::None => ApplicabilityRules::Include {
+                ids: vec![id.to_string()],
+            },
+            rules => rules.clone(),
+        }
+    }
+}
+
+/// Gender added to events by `auto_include_mixed`
+pub const MIXED_GENDER: &str = "mixed";
+
+/// Config file loaded when `CONFIG_PATH` isn't set
+pub const DEFAULT_CONFIG_PATH: &str = "./config.yaml";
+
 impl Configuration {
-    /// Load configuration from YAML file
+    /// Path of the config file, from `CONFIG_PATH` or `DEFAULT_CONFIG_PATH`
+    pub fn path_from_env() -> String {
+        std::env::var("CONFIG_PATH").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string())
+    }
+
+    /// Load configuration from YAML file, failing with `ConfigErrors` if it doesn't validate
     pub fn from_yaml_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let content = std::fs::read_to_string(path)?;
         let config: Configuration = serde_yml::from_str(&content)?;
+        config.validate().map_err(ConfigErrors)?;
+        Ok(config.canonicalized()?)
+    }
+
+    /// The config with every year, gender and event id canonicalized, including the ids in
+    /// applicability rules, gender order and gender multipliers, so they match the ids events
+    /// are stored under
+    pub fn canonicalized(&self) -> Result<Self, IdError> {
+        let mut config = self.clone();
+        config.genders = id_utils::canonicalize_all(&self.genders)?;
+        config.gender_order = id_utils::canonicalize_all(&self.gender_order)?;
+        config.gender_multipliers = self
+            .gender_multipliers
+            .iter()
+            .map(|(gender, multiplier)| Ok((id_utils::canonicalize(gender)?, *multiplier)))
+            .collect::<Result<_, IdError>>()?;
+        for year in config.years.iter_mut() {
+            year.id = id_utils::canonicalize(&year.id)?;
+        }
+        for event in config.events.iter_mut() {
+            *event = event.canonicalized()?;
+        }
         Ok(config)
     }
 
+    /// Check the configuration for problems, returning every one found
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        for (field, count, max) in [
+            ("forms", self.forms.len(), self.limits.max_forms),
+            ("years", self.years.len(), self.limits.max_years),
+            ("events", self.events.len(), self.limits.max_events),
+            (
+                "generated events",
+                self.expected_event_count(),
+                self.limits.max_generated_events,
+            ),
+        ] {
+            if count > max {
+                errors.push(ConfigError::TooMany { field, count, max });
+            }
+        }
+
+        for (field, ids) in [
+            ("years", self.years.iter().map(|year| &year.id).collect()),
+            ("genders", self.genders.iter().collect()),
+            (
+                "events",
+                self.events.iter().map(|event| &event.id).collect(),
+            ),
+        ] as [(&'static str, Vec<&String>); 3]
+        {
+            for id in ids {
+                if let Err(error) = id_utils::canonicalize(id) {
+                    errors.push(ConfigError::InvalidId { field, error });
+                }
+            }
+        }
+
+        // Year, gender and event ids are canonicalized before events are built, so ids that only
+        // differ in case or spacing collide. Ids that don't canonicalize were reported above.
+        let canonical = |ids: Vec<&String>| -> Vec<(String, String)> {
+            ids.into_iter()
+                .filter_map(|id| Some((id_utils::canonicalize(id).ok()?, id.clone())))
+                .collect()
+        };
+        for (field, ids) in [
+            (
+                "years",
+                canonical(self.years.iter().map(|year| &year.id).collect()),
+            ),
+            ("genders", canonical(self.genders.iter().collect())),
+            (
+                "forms",
+                self.forms
+                    .iter()
+                    .map(|form| (form.id.clone(), form.id.clone()))
+                    .collect(),
+            ),
+            (
+                "events",
+                canonical(self.events.iter().map(|event| &event.id).collect()),
+            ),
+        ] {
+            let mut seen = std::collections::HashSet::new();
+            let mut reported = std::collections::HashSet::new();
+            for (key, id) in ids {
+                if !seen.insert(key.clone()) && reported.insert(key) {
+                    errors.push(ConfigError::DuplicateId { field, id });
+                }
+            }
+        }
+
+        let genders: Vec<String> = self
+            .genders
+            .iter()
+            .filter_map(|gender| id_utils::canonicalize(gender).ok())
+            .collect();
+        for event in self.events.iter() {
+            if let ApplicabilityRules::Include { ids } | ApplicabilityRules::Exclude { ids } =
+                &event.applicable_genders
+            {
+                for gender_id in ids.iter().filter(|id| {
+                    id_utils::canonicalize(id).map_or(true, |id| !genders.contains(&id))
+                }) {
+                    errors.push(ConfigError::UnknownGender {
+                        event_id: event.id.clone(),
+                        gender_id: gender_id.clone(),
+                    });
+                }
+            }
+        }
+
+        for (gender_id, multiplier) in self.gender_multipliers.iter() {
+            if !multiplier.is_finite() || *multiplier < 0.0 {
+                errors.push(ConfigError::InvalidMultiplier {
+                    gender_id: gender_id.clone(),
+                    multiplier: *multiplier,
+                });
+            }
+        }
+
+        let defaults = self.scores.iter().filter(|score| score.default).count();
+        if defaults != 1 {
+            errors.push(ConfigError::DefaultScores { count: defaults });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Number of events `build_plan` would generate, without building them. Applicability is
+    /// matched on canonical ids, as `build_plan` does
+    pub fn expected_event_count(&self) -> usize {
+        let canonical = self.canonicalized();
+        let config = canonical.as_ref().unwrap_or(self);
+        config
+            .events
+            .iter()
+            .map(|event| {
+                let years = config
+                    .years
+                    .iter()
+                    .filter(|year| config.is_event_applicable_to_year(event, &year.id))
+                    .count();
+                let genders = config
+                    .genders
+                    .iter()
+                    .filter(|gender| config.is_event_applicable_to_gender(event, gender))
+                    .count();
+                years.saturating_mul(genders)
+            })
+            .fold(0, usize::saturating_add)
+    }
+
     /// Check if an event applies to a specific year
     pub fn is_event_applicable_to_year(&self, event: &Event, year_id: &str) -> bool {
-        match &event.applicable_years {
-            ApplicabilityRules::All => true,
-            ApplicabilityRules::None => false,
-            ApplicabilityRules::Include { ids } => ids.contains(&year_id.to_string()),
-            ApplicabilityRules::Exclude { ids } => !ids.contains(&year_id.to_string()),
-        }
+        event.applicable_years.applies_to(year_id)
     }
 
     /// Check if an event applies to a specific gender
     pub fn is_event_applicable_to_gender(&self, event: &Event, gender_id: &str) -> bool {
-        match &event.applicable_genders {
-            ApplicabilityRules::All => true,
-            ApplicabilityRules::None => false,
-            ApplicabilityRules::Include { ids } => ids.contains(&gender_id.to_string()),
-            ApplicabilityRules::Exclude { ids } => !ids.contains(&gender_id.to_string()),
+        self.effective_event(event)
+            .applicable_genders
+            .applies_to(gender_id)
+    }
+
+    /// The event as `build_plan` expands it. With `auto_include_mixed` set, an event that applies
+    /// to any configured gender other than "mixed" applies to "mixed" as well.
+    pub fn effective_event(&self, event: &Event) -> Event {
+        let mut event = event.clone();
+        if self.auto_include_mixed
+            && self
+                .genders
+                .iter()
+                .any(|gender| gender != MIXED_GENDER && event.applicable_genders.applies_to(gender))
+        {
+            event.applicable_genders = event.applicable_genders.including(MIXED_GENDER);
         }
+        event
+    }
+
+    /// Display position of a gender, following `gender_order` then `genders`
+    pub fn gender_rank(&self, gender_id: &str) -> usize {
+        match self.gender_order.iter().position(|id| id == gender_id) {
+            Some(position) => position,
+            None => {
+                self.gender_order.len()
+                    + self
+                        .genders
+                        .iter()
+                        .position(|id| id == gender_id)
+                        .unwrap_or(self.genders.len())
+            }
+        }
+    }
+
+    /// Configured genders in display order
+    pub fn ordered_genders(&self) -> Vec<String> {
+        let mut genders = self.genders.clone();
+        genders.sort_by_key(|gender| self.gender_rank(gender));
+        genders
+    }
+
+    /// The announcement to show, `None` when unset or blank
+    pub fn banner(&self) -> Option<String> {
+        self.announcement
+            .as_deref()
+            .map(str::trim)
+            .filter(|announcement| !announcement.is_empty())
+            .map(str::to_string)
+    }
+
+    /// Scores JSON with every configured form on zero, used for new and reset events
+    pub fn empty_scores(&self) -> String {
+        let mut empty_scores = serde_json::json!({});
+        for form in self.forms.iter() {
+            empty_scores[form.id.clone()] = 0.into();
+        }
+        empty_scores.to_string()
+    }
+
+    /// Forms in a scores object whose score is outside `min_score`/`max_score`, with a message
+    /// for each. Blank or non-numeric scores aren't checked.
+    pub fn score_range_errors(&self, scores: &serde_json::Value) -> BTreeMap<String, String> {
+        let mut errors = BTreeMap::new();
+        let Some(scores) = scores.as_object() else {
+            return errors;
+        };
+        for (form_id, score) in scores {
+            let score = match score {
+                serde_json::Value::String(score) => score.trim().parse::<f64>().ok(),
+                serde_json::Value::Number(score) => score.as_f64(),
+                _ => None,
+            };
+            let Some(score) = score.filter(|score| score.is_finite()) else {
+                continue;
+            };
+            let shown = crate::utils::format_points(score, false);
+            if let Some(min) = self.min_score.filter(|min| score < *min as f64) {
+                errors.insert(
+                    form_id.clone(),
+                    format!("{} is below the minimum score of {}", shown, min),
+                );
+            } else if let Some(max) = self.max_score.filter(|max| score > *max as f64) {
+                errors.insert(
+                    form_id.clone(),
+                    format!("{} is above the maximum score of {}", shown, max),
+                );
+            }
+        }
+        errors
+    }
+
+    /// Likely mistakes that still make a valid config, like an empty `genders` list
+    pub fn warnings(&self) -> Vec<ConfigWarning> {
+        if self.genders.is_empty() {
+            return vec![ConfigWarning::NoGenders];
+        }
+
+        self.events
+            .iter()
+            .filter(|event| {
+                !self
+                    .genders
+                    .iter()
+                    .any(|gender| event.applicable_genders.applies_to(gender))
+            })
+            .map(|event| ConfigWarning::NoApplicableGenders {
+                event_id: event.id.clone(),
+            })
+            .collect()
     }
 
     /// Get Schema Version
     pub fn get_version(&self) -> String {
         self.version.clone()
     }
+
+    /// Hash of the parsed config, so edits to the file can be spotted without a version bump.
+    /// Formatting and comments in the YAML don't affect it. Only comparable within one build.
+    pub fn checksum(&self) -> String {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        serde_json::to_string(self)
+            .expect("config should serialize")
+            .hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_score_range_errors() {
+        let config = Configuration {
+            min_score: Some(0),
+            max_score: Some(10),
+            ..Default::default()
+        };
+        let errors = config.score_range_errors(&serde_json::json!({
+            "a": "10", "b": "100", "c": -1, "d": "", "e": "x", "f": "10.5"
+        }));
+        assert_eq!(
+            errors,
+            BTreeMap::from([
+                (
+                    "b".to_string(),
+                    "100 is above the maximum score of 10".to_string()
+                ),
+                (
+                    "c".to_string(),
+                    "-1 is below the minimum score of 0".to_string()
+                ),
+                (
+                    "f".to_string(),
+                    "10.5 is above the maximum score of 10".to_string()
+                ),
+            ])
+        );
+
+        // Unbounded by default
+        assert!(Configuration::default()
+            .score_range_errors(&serde_json::json!({"a": "100"}))
+            .is_empty());
+    }
+
     #[test]
     fn test_applicability_rules_all() {
         let rules = ApplicabilityRules::All;
@@ -156,6 +711,7 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
         let event = Event {
@@ -163,6 +719,7 @@ mod tests {
             name: "Test".to_string(),
             applicable_years: ApplicabilityRules::All,
             applicable_genders: ApplicabilityRules::All,
+            tags: vec![],
         };
 
         assert!(config.is_event_applicable_to_year(&event, "year7"));
@@ -178,6 +735,7 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
         let event = Event {
@@ -185,6 +743,7 @@ mod tests {
             name: "Test".to_string(),
             applicable_years: ApplicabilityRules::None,
             applicable_genders: ApplicabilityRules::All,
+            tags: vec![],
         };
 
         assert!(!config.is_event_applicable_to_year(&event, "year7"));
@@ -199,6 +758,7 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
         let event = Event {
@@ -208,6 +768,7 @@ mod tests {
                 ids: vec!["year7".to_string(), "year8".to_string()],
             },
             applicable_genders: ApplicabilityRules::All,
+            tags: vec![],
         };
 
         assert!(config.is_event_applicable_to_year(&event, "year7"));
@@ -224,6 +785,7 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
         let event = Event {
@@ -233,6 +795,7 @@ mod tests {
                 ids: vec!["year7".to_string()],
             },
             applicable_genders: ApplicabilityRules::All,
+            tags: vec![],
         };
 
         assert!(!config.is_event_applicable_to_year(&event, "year7"));
@@ -249,6 +812,7 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
         let event = Event {
@@ -256,6 +820,7 @@ mod tests {
             name: "Test".to_string(),
             applicable_years: ApplicabilityRules::All,
             applicable_genders: ApplicabilityRules::All,
+            tags: vec![],
         };
 
         assert!(config.is_event_applicable_to_gender(&event, "boys"));
@@ -272,6 +837,7 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
         let event = Event {
@@ -281,6 +847,7 @@ mod tests {
             applicable_genders: ApplicabilityRules::Include {
                 ids: vec!["boys".to_string()],
             },
+            tags: vec![],
         };
 
         assert!(config.is_event_applicable_to_gender(&event, "boys"));
@@ -296,6 +863,7 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
         assert_eq!(config.get_version(), "2.5.3");
@@ -324,6 +892,328 @@ mod tests {
         assert_eq!(config.forms[0].name, "Form 1");
     }
 
+    #[test]
+    fn test_validate_limits() {
+        let mut config = Configuration {
+            genders: vec!["boys".to_string(), "girls".to_string()],
+            scores: vec![Score {
+                name: "1st".to_string(),
+                value: 10,
+                default: true,
+            }],
+            years: (0..10)
+                .map(|i| Year {
+                    id: format!("y{}", i),
+                    name: format!("Year {}", i),
+                })
+                .collect(),
+            events: (0..10)
+                .map(|i| Event {
+                    id: format!("event{}", i),
+                    name: format!("Event {}", i),
+                    applicable_years: ApplicabilityRules::All,
+                    applicable_genders: ApplicabilityRules::All,
+                    tags: vec![],
+                })
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(config.expected_event_count(), 200);
+        assert!(config.validate().is_ok());
+
+        config.limits.max_generated_events = 199;
+        assert_eq!(
+            config.validate(),
+            Err(vec![ConfigError::TooMany {
+                field: "generated events",
+                count: 200,
+                max: 199,
+            }])
+        );
+
+        config.limits.max_years = 5;
+        assert_eq!(config.validate().unwrap_err().len(), 2);
+    }
+
+    #[test]
+    fn test_validate_collects_every_problem() {
+        let year = |id: &str| Year {
+            id: id.to_string(),
+            name: id.to_string(),
+        };
+        let form = |id: &str| Form {
+            id: id.to_string(),
+            name: id.to_string(),
+            colour: "red".to_string(),
+        };
+        let event = |id: &str, genders: ApplicabilityRules| Event {
+            id: id.to_string(),
+            name: id.to_string(),
+            applicable_years: ApplicabilityRules::All,
+            applicable_genders: genders,
+            tags: vec![],
+        };
+        let score = |default: bool| Score {
+            name: "1st".to_string(),
+            value: 10,
+            default,
+        };
+
+        let config = Configuration {
+            genders: vec!["boys".to_string(), "girls".to_string()],
+            scores: vec![score(true), score(true)],
+            years: vec![year("y7"), year("y7"), year("y7"), year("y8")],
+            forms: vec![form("a"), form("b"), form("a")],
+            events: vec![
+                event(
+                    "100m",
+                    ApplicabilityRules::Include {
+                        ids: vec!["boys".to_string(), "mixed".to_string()],
+                    },
+                ),
+                event(
+                    "100m",
+                    ApplicabilityRules::Exclude {
+                        ids: vec!["other".to_string()],
+                    },
+                ),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.validate(),
+            Err(vec![
+                ConfigError::DuplicateId {
+                    field: "years",
+                    id: "y7".to_string(),
+                },
+                ConfigError::DuplicateId {
+                    field: "forms",
+                    id: "a".to_string(),
+                },
+                ConfigError::DuplicateId {
+                    field: "events",
+                    id: "100m".to_string(),
+                },
+                ConfigError::UnknownGender {
+                    event_id: "100m".to_string(),
+                    gender_id: "mixed".to_string(),
+                },
+                ConfigError::UnknownGender {
+                    event_id: "100m".to_string(),
+                    gender_id: "other".to_string(),
+                },
+                ConfigError::DefaultScores { count: 2 },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_validate_compares_canonical_ids() {
+        let year = |id: &str| Year {
+            id: id.to_string(),
+            name: id.to_string(),
+        };
+        let mut config = Configuration {
+            genders: vec!["Boys".to_string(), "boys".to_string()],
+            years: vec![year("Year7"), year(" year7 ")],
+            events: vec![Event {
+                id: "100m".to_string(),
+                name: "100m".to_string(),
+                applicable_years: ApplicabilityRules::Include {
+                    ids: vec!["YEAR7".to_string()],
+                },
+                applicable_genders: ApplicabilityRules::Include {
+                    ids: vec!["BOYS".to_string()],
+                },
+                tags: vec![],
+            }],
+            scores: vec![Score {
+                name: "1st".to_string(),
+                value: 10,
+                default: true,
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(config.expected_event_count(), 4);
+        assert_eq!(
+            config.validate(),
+            Err(vec![
+                ConfigError::DuplicateId {
+                    field: "years",
+                    id: " year7 ".to_string(),
+                },
+                ConfigError::DuplicateId {
+                    field: "genders",
+                    id: "boys".to_string(),
+                },
+            ])
+        );
+
+        config.genders = vec!["Boys".to_string()];
+        config.years.pop();
+        assert_eq!(config.expected_event_count(), 1);
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_gender_multipliers() {
+        let config = Configuration {
+            genders: vec!["boys".to_string(), "mixed".to_string()],
+            scores: vec![Score {
+                name: "1st".to_string(),
+                value: 10,
+                default: true,
+            }],
+            gender_multipliers: BTreeMap::from([
+                ("boys".to_string(), -1.0),
+                ("mixed".to_string(), 0.5),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.validate(),
+            Err(vec![ConfigError::InvalidMultiplier {
+                gender_id: "boys".to_string(),
+                multiplier: -1.0,
+            }])
+        );
+    }
+
+    #[test]
+    fn test_validate_invalid_ids() {
+        let config = Configuration {
+            genders: vec!["boys".to_string(), "mixed ".to_string()],
+            scores: vec![Score {
+                name: "1st".to_string(),
+                value: 10,
+                default: true,
+            }],
+            years: vec![Year {
+                id: "Year 7".to_string(),
+                name: "Year 7".to_string(),
+            }],
+            events: vec![Event {
+                id: "100m/relay".to_string(),
+                name: "Relay".to_string(),
+                applicable_years: ApplicabilityRules::All,
+                applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            config.validate(),
+            Err(vec![
+                ConfigError::InvalidId {
+                    field: "years",
+                    error: IdError::Whitespace("Year 7".to_string()),
+                },
+                ConfigError::InvalidId {
+                    field: "events",
+                    error: IdError::InvalidChar {
+                        id: "100m/relay".to_string(),
+                        ch: '/',
+                    },
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_from_yaml_file_reports_validation_errors() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let yaml_content = "version: \"1.0.0\"\ngenders: [boys]\nscores: []\nyears:\n  - id: y7\n    name: Year 7\n  - id: y7\n    name: Year 7 again\nforms: []\nevents: []\n";
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(yaml_content.as_bytes()).unwrap();
+        temp_file.flush().unwrap();
+
+        let error = Configuration::from_yaml_file(temp_file.path().to_str().unwrap()).unwrap_err();
+        let errors = error.downcast_ref::<ConfigErrors>().unwrap();
+        assert_eq!(
+            errors.0,
+            vec![
+                ConfigError::DuplicateId {
+                    field: "years",
+                    id: "y7".to_string(),
+                },
+                ConfigError::DefaultScores { count: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_checksum() {
+        let yaml = "version: \"1.0.0\"\ngenders: [boys]\nscores: []\nyears:\n  - id: y7\n    name: Year 7\nforms: []\nevents: []\n";
+        let config: Configuration = serde_yml::from_str(yaml).unwrap();
+        let reloaded: Configuration =
+            serde_yml::from_str(&format!("# reformatted\n{}", yaml)).unwrap();
+        assert_eq!(config.checksum(), reloaded.checksum());
+        assert_eq!(config.checksum().len(), 16);
+
+        let mut changed = config.clone();
+        changed.years[0].name = "Year Seven".to_string();
+        assert_ne!(config.checksum(), changed.checksum());
+    }
+
+    #[test]
+    fn test_number_format() {
+        assert_eq!(NumberFormat::Plain.format(&12345.0), "12345");
+        assert_eq!(NumberFormat::Grouped.format(&12345.0), "12,345");
+        assert_eq!(NumberFormat::Grouped.format(&-1234567.0), "-1,234,567");
+        assert_eq!(NumberFormat::Grouped.format(&12345.5), "12,345.5");
+
+        let config: Configuration =
+            serde_yml::from_str("version: '1'\ngenders: []\nscores: []\nyears: []\nforms: []\nevents: []\nnumber_format: grouped\n")
+                .unwrap();
+        assert_eq!(config.number_format, NumberFormat::Grouped);
+    }
+
+    #[test]
+    fn test_ordered_genders() {
+        let mut config = Configuration {
+            genders: vec!["mixed".to_string(), "girls".to_string(), "boys".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(config.ordered_genders(), vec!["mixed", "girls", "boys"]);
+
+        config.gender_order = vec!["boys".to_string(), "girls".to_string()];
+        assert_eq!(config.ordered_genders(), vec!["boys", "girls", "mixed"]);
+        assert_eq!(config.gender_rank("mixed"), 2);
+        assert_eq!(config.gender_rank("unknown"), 5);
+    }
+
+    #[test]
+    fn test_scoreboard_layout_parsing() {
+        let config: Configuration = serde_yml::from_str(
+            "version: \"1.0.0\"\ngenders: []\nscores: []\nyears: []\nforms: []\nevents: []\nscoreboard_layout: years_as_columns\n",
+        )
+        .unwrap();
+        assert_eq!(config.scoreboard_layout, ScoreboardLayout::YearsAsColumns);
+
+        let config: Configuration = serde_yml::from_str(
+            "version: \"1.0.0\"\ngenders: []\nscores: []\nyears: []\nforms: []\nevents: []\n",
+        )
+        .unwrap();
+        assert_eq!(config.scoreboard_layout, ScoreboardLayout::FormsAsColumns);
+    }
+
+    #[test]
+    fn test_event_tags_parsing() {
+        let config: Configuration = serde_yml::from_str(
+            "version: \"1.0.0\"\ngenders: []\nscores: []\nyears: []\nforms: []\nevents:\n  - id: shotput\n    name: Shot Put\n    applicable_years: {type: all}\n    applicable_genders: {type: all}\n    tags: [field]\n  - id: sack\n    name: Sack Race\n    applicable_years: {type: all}\n    applicable_genders: {type: all}\n",
+        )
+        .unwrap();
+        assert_eq!(config.events[0].tags, vec!["field"]);
+        assert!(config.events[1].tags.is_empty());
+    }
+
     #[test]
     fn test_configuration_from_yaml_file_not_found() {
         let result = Configuration::from_yaml_file("nonexistent.yaml");
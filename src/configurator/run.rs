@@ -1,41 +1,207 @@
-use async_sqlite::Pool;
-use log::{debug, info};
+use std::collections::HashSet;
+
+use async_sqlite::{rusqlite::TransactionBehavior, Pool};
+use log::{debug, info, warn};
 
 use crate::{
-    configurator::build::Plan,
-    db::{events::Events, years::Years},
+    configurator::{
+        build::{build_plan, Plan},
+        id_utils::IdError,
+        parser::Configuration,
+    },
+    db::{archived_events::ArchivedEvents, events::Events, timed_conn_mut},
 };
 
+/// What happened when the database was rebuilt from a config
+#[derive(Debug, Default, PartialEq)]
+pub struct RebuildReport {
+    /// Scored events the new config removed, copied to the archive before they were deleted
+    pub archived: usize,
+}
+
+impl RebuildReport {
+    /// Something for whoever reloaded the config to know, `None` if nothing needs saying
+    pub fn warning(&self) -> Option<String> {
+        (self.archived > 0).then(|| {
+            format!(
+                "Archived {} scored events that are no longer in the config",
+                self.archived
+            )
+        })
+    }
+}
+
+/// Why `rebuild` failed
+#[derive(Debug)]
+pub enum RebuildError {
+    /// A year, gender or event id in the config doesn't canonicalize
+    InvalidId(IdError),
+    Database(async_sqlite::Error),
+}
+
+impl std::fmt::Display for RebuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RebuildError::InvalidId(error) => write!(f, "invalid id in config: {}", error),
+            RebuildError::Database(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for RebuildError {}
+
+impl From<IdError> for RebuildError {
+    fn from(error: IdError) -> Self {
+        RebuildError::InvalidId(error)
+    }
+}
+
+impl From<async_sqlite::Error> for RebuildError {
+    fn from(error: async_sqlite::Error) -> Self {
+        RebuildError::Database(error)
+    }
+}
+
+/// Rebuild the years and events from `config`, keeping the scores of events that are still
+/// planned. When `auto_archive_on_reload` is set, scored events the config no longer has are
+/// archived before they are deleted.
+pub async fn rebuild(config: &Configuration, pool: &Pool) -> Result<RebuildReport, RebuildError> {
+    let plan = build_plan(config.clone())?;
+    let report = RebuildReport {
+        archived: implement(plan, pool, config.auto_archive_on_reload).await?,
+    };
+    if let Some(warning) = report.warning() {
+        warn!("{}", warning);
+    }
+    Ok(report)
+}
+
+/// Bring the database in line with `plan` in one transaction. Planned events that already exist
+/// keep their scores, `updated_at` and lock, and have their name, year, gender, filter key and
+/// tags updated. New events start with the planned scores. Events no longer planned are deleted,
+/// except manual ones whose year is still planned. Forms and years are replaced to match.
 pub async fn run(plan: Plan, pool: &Pool) -> Result<(), async_sqlite::Error> {
+    implement(plan, pool, false).await?;
+    Ok(())
+}
+
+/// `run`, archiving the scored events it deletes when `archive_removed` is set. Returns how many
+/// were archived.
+async fn implement(
+    plan: Plan,
+    pool: &Pool,
+    archive_removed: bool,
+) -> Result<usize, async_sqlite::Error> {
     info!("Implementing Plan");
-    Events::delete_all(&pool).await.unwrap();
-    Years::delete_all(&pool).await.unwrap();
-    for year in plan.year_plans.iter() {
-        debug!("Inserting Planned Year {}", year.id);
-        let mut year_struct = Years::new(year.id.clone(), year.name.clone())
-            .insert(&pool)
-            .await?;
-        for event in year.events.iter() {
-            debug!("Inserting Planned Event {}", event.id);
-            year_struct = year_struct
-                .new_event(
-                    &pool,
-                    event.clone().id,
-                    event.clone().name,
-                    event.clone().gender_id,
-                    event.clone().filter_key,
-                    event.clone().scores,
+    timed_conn_mut(pool, move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        tx.execute("DELETE FROM forms;", [])?;
+        for form in plan.forms.iter() {
+            debug!("Inserting Planned Form {}", form.id);
+            tx.execute(
+                "INSERT INTO forms(id, name, colour) VALUES (?1, ?2, ?3);",
+                [&form.id, &form.name, &form.colour],
+            )?;
+        }
+
+        let year_ids: HashSet<&str> = plan.year_plans.iter().map(|year| year.id.as_str()).collect();
+        let event_ids: HashSet<&str> = plan
+            .year_plans
+            .iter()
+            .flat_map(|year| year.events.iter().map(|event| event.id.as_str()))
+            .collect();
+
+        // Events go before years, as they reference them
+        let existing: Vec<Events> = {
+            let mut stmt = tx.prepare("SELECT * FROM events")?;
+            let rows = stmt.query_map([], Events::map_from_row)?;
+            rows.collect::<Result<_, _>>()?
+        };
+        let mut removed = vec![];
+        for event in existing {
+            if event_ids.contains(event.id.as_str()) {
+                continue;
+            }
+            if event.manual {
+                if year_ids.contains(event.year_id.as_str()) {
+                    continue;
+                }
+                warn!(
+                    "Dropping manual event {} as year {} is no longer configured",
+                    event.id, event.year_id
+                );
+            }
+            tx.execute("DELETE FROM events WHERE id = ?1;", [&event.id])?;
+            removed.push(event);
+        }
+        let archived: Vec<Events> = removed
+            .iter()
+            .filter(|event| archive_removed && event.is_scored())
+            .cloned()
+            .collect();
+        ArchivedEvents::archive_with(&tx, &archived)?;
+
+        let existing_years: Vec<String> = {
+            let mut stmt = tx.prepare("SELECT id FROM years")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<Result<_, _>>()?
+        };
+        for id in existing_years {
+            if !year_ids.contains(id.as_str()) {
+                tx.execute("DELETE FROM years WHERE id = ?1;", [&id])?;
+            }
+        }
+
+        let (mut kept, mut added) = (0, 0);
+        for year in plan.year_plans.iter() {
+            debug!("Upserting Planned Year {}", year.id);
+            tx.execute(
+                "INSERT INTO years(id, name) VALUES (?1, ?2) ON CONFLICT(id) DO UPDATE SET name = excluded.name;",
+                [&year.id, &year.name],
+            )?;
+            for event in year.events.iter() {
+                let tags = serde_json::to_string(&event.tags).unwrap();
+                let updated = tx.execute(
+                    "UPDATE events SET name = ?1, year_id = ?2, gender_id = ?3, filter_key = ?4, tags = ?5, manual = 0 WHERE id = ?6;",
+                    [&event.name, &year.id, &event.gender_id, &event.filter_key, &tags, &event.id],
+                )?;
+                if updated > 0 {
+                    kept += 1;
+                    continue;
+                }
+                debug!("Inserting Planned Event {}", event.id);
+                Events::new(
+                    event.id.clone(),
+                    event.name.clone(),
+                    year.id.clone(),
+                    event.gender_id.clone(),
+                    event.filter_key.clone(),
+                    event.scores.clone(),
                 )
-                .await?
+                .with_tags(event.tags.clone())
+                .insert_with(&tx)?;
+                added += 1;
+            }
         }
-    }
-    Ok(())
+
+        tx.commit()?;
+        info!(
+            "Plan implemented: kept {} events, added {}, removed {}",
+            kept,
+            added,
+            removed.len()
+        );
+        Ok(archived.len())
+    })
+    .await
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::configurator::parser::{ApplicabilityRules, Configuration, Event, Form, Year};
+    use crate::db::{forms::Forms, years::Years};
     use crate::test_harness;
 
     #[tokio::test]
@@ -49,9 +215,10 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
-        let plan = crate::configurator::build::build_plan(config);
+        let plan = crate::configurator::build::build_plan(config).unwrap();
         let result = run(plan, &db).await;
 
         assert!(result.is_ok());
@@ -71,9 +238,10 @@ mod tests {
             }],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
-        let plan = crate::configurator::build::build_plan(config);
+        let plan = crate::configurator::build::build_plan(config).unwrap();
         let result = run(plan, &db).await;
 
         assert!(result.is_ok());
@@ -105,10 +273,12 @@ mod tests {
                 name: "Event 1".to_string(),
                 applicable_years: ApplicabilityRules::All,
                 applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
             }],
+            ..Default::default()
         };
 
-        let plan = crate::configurator::build::build_plan(config);
+        let plan = crate::configurator::build::build_plan(config).unwrap();
         let result = run(plan, &db).await;
 
         assert!(result.is_ok());
@@ -121,6 +291,50 @@ mod tests {
         assert_eq!(events[0].name, "Event 1");
     }
 
+    #[tokio::test]
+    async fn test_run_inserts_forms() {
+        let db = test_harness::setup_db("run_inserts_forms").await;
+        Forms::new("old".to_string(), "Old".to_string(), "grey".to_string())
+            .insert(&db)
+            .await
+            .unwrap();
+
+        let config = Configuration {
+            forms: vec![
+                Form {
+                    id: "form1".to_string(),
+                    name: "Form 1".to_string(),
+                    colour: "#ff0000".to_string(),
+                },
+                Form {
+                    id: "form2".to_string(),
+                    name: "Form 2".to_string(),
+                    colour: "lightgreen".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        run(crate::configurator::build::build_plan(config).unwrap(), &db)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Forms::all(&db).await.unwrap(),
+            vec![
+                Forms::new(
+                    "form1".to_string(),
+                    "Form 1".to_string(),
+                    "#ff0000".to_string()
+                ),
+                Forms::new(
+                    "form2".to_string(),
+                    "Form 2".to_string(),
+                    "lightgreen".to_string()
+                ),
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn test_run_deletes_existing_data() {
         let db = test_harness::setup_db("run_deletes_existing").await;
@@ -158,9 +372,10 @@ mod tests {
             }],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
-        let plan = crate::configurator::build::build_plan(config);
+        let plan = crate::configurator::build::build_plan(config).unwrap();
         let result = run(plan, &db).await;
 
         assert!(result.is_ok());
@@ -202,10 +417,12 @@ mod tests {
                 name: "Event 1".to_string(),
                 applicable_years: ApplicabilityRules::All,
                 applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
             }],
+            ..Default::default()
         };
 
-        let plan = crate::configurator::build::build_plan(config);
+        let plan = crate::configurator::build::build_plan(config).unwrap();
         let result = run(plan, &db).await;
 
         assert!(result.is_ok());
@@ -218,6 +435,92 @@ mod tests {
         assert_eq!(events.len(), 4);
     }
 
+    #[tokio::test]
+    async fn test_run_keeps_scores() {
+        let db = test_harness::setup_db("run_keeps_scores").await;
+        let event = |id: &str| Event {
+            id: id.to_string(),
+            name: id.to_string(),
+            applicable_years: ApplicabilityRules::All,
+            applicable_genders: ApplicabilityRules::All,
+            tags: vec![],
+        };
+        let mut config = Configuration {
+            genders: vec!["mixed".to_string()],
+            years: vec![Year {
+                id: "year7".to_string(),
+                name: "Year 7".to_string(),
+            }],
+            forms: vec![Form {
+                id: "form1".to_string(),
+                name: "Form 1".to_string(),
+                colour: "#ff0000".to_string(),
+            }],
+            events: vec![event("sprint"), event("javelin")],
+            ..Default::default()
+        };
+        run(
+            crate::configurator::build::build_plan(config.clone()).unwrap(),
+            &db,
+        )
+        .await
+        .unwrap();
+        Events::set_scores(
+            &db,
+            "year7-mixed-sprint".to_string(),
+            serde_json::json!({"form1": "5"}),
+        )
+        .await
+        .unwrap();
+
+        // Add the relay and drop the javelin
+        config.events = vec![event("sprint"), event("relay")];
+        let scored_at = Events::find_by_id(&db, "year7-mixed-sprint".to_string())
+            .await
+            .unwrap()
+            .unwrap()
+            .updated_at;
+        rebuild(&config, &db).await.unwrap();
+
+        let events = Events::all(&db).await.unwrap();
+        let ids: Vec<&str> = events.iter().map(|event| event.id.as_str()).collect();
+        assert_eq!(ids, vec!["year7-mixed-sprint", "year7-mixed-relay"]);
+        assert_eq!(events[0].parsed_decimal_scores()["form1"], 5.0);
+        assert_eq!(events[0].updated_at, scored_at);
+        assert_eq!(events[1].parsed_decimal_scores()["form1"], 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_keeps_locks() {
+        let db = test_harness::setup_db("run_keeps_locks").await;
+        let config = Configuration {
+            genders: vec!["mixed".to_string()],
+            years: vec![Year {
+                id: "year7".to_string(),
+                name: "Year 7".to_string(),
+            }],
+            events: vec![Event {
+                id: "sprint".to_string(),
+                name: "Sprint".to_string(),
+                applicable_years: ApplicabilityRules::All,
+                applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
+            }],
+            ..Default::default()
+        };
+        rebuild(&config, &db).await.unwrap();
+        Events::set_locked(&db, "year7-mixed-sprint".to_string(), true)
+            .await
+            .unwrap();
+
+        rebuild(&config, &db).await.unwrap();
+        let event = Events::find_by_id(&db, "year7-mixed-sprint".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(event.locked);
+    }
+
     // E2E test
     #[tokio::test]
     async fn test_e2e_configuration_rebuild() {
@@ -250,10 +553,12 @@ mod tests {
                 name: "Sprint".to_string(),
                 applicable_years: crate::configurator::parser::ApplicabilityRules::All,
                 applicable_genders: crate::configurator::parser::ApplicabilityRules::All,
+                tags: vec![],
             }],
+            ..Default::default()
         };
 
-        let plan1 = crate::configurator::build::build_plan(config1);
+        let plan1 = crate::configurator::build::build_plan(config1).unwrap();
         run(plan1, &pool).await.unwrap();
 
         let events1 = Events::all(&pool).await.unwrap();
@@ -281,17 +586,20 @@ mod tests {
                     name: "Sprint".to_string(),
                     applicable_years: crate::configurator::parser::ApplicabilityRules::All,
                     applicable_genders: crate::configurator::parser::ApplicabilityRules::All,
+                    tags: vec![],
                 },
                 crate::configurator::parser::Event {
                     id: "relay".to_string(),
                     name: "Relay".to_string(),
                     applicable_years: crate::configurator::parser::ApplicabilityRules::All,
                     applicable_genders: crate::configurator::parser::ApplicabilityRules::All,
+                    tags: vec![],
                 },
             ],
+            ..Default::default()
         };
 
-        let plan2 = crate::configurator::build::build_plan(config2);
+        let plan2 = crate::configurator::build::build_plan(config2).unwrap();
         run(plan2, &pool).await.unwrap();
 
         let events2 = Events::all(&pool).await.unwrap();
@@ -301,4 +609,66 @@ mod tests {
         let years2 = Years::all(&pool).await.unwrap();
         assert_eq!(years2.len(), 2);
     }
+
+    #[tokio::test]
+    async fn test_rebuild_archives_scored_events() {
+        let db = test_harness::setup_db("run_rebuild_archives").await;
+
+        let mut config = Configuration {
+            version: "1.0.0".to_string(),
+            genders: vec!["mixed".to_string()],
+            scores: vec![],
+            years: vec![Year {
+                id: "year7".to_string(),
+                name: "Year 7".to_string(),
+            }],
+            forms: vec![Form {
+                id: "form1".to_string(),
+                name: "Form 1".to_string(),
+                colour: "#ff0000".to_string(),
+            }],
+            events: ["event1", "event2", "event3"]
+                .iter()
+                .map(|id| Event {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    applicable_years: ApplicabilityRules::All,
+                    applicable_genders: ApplicabilityRules::All,
+                    tags: vec![],
+                })
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            rebuild(&config, &db).await.unwrap(),
+            RebuildReport { archived: 0 }
+        );
+        for id in ["year7-mixed-event1", "year7-mixed-event2"] {
+            Events::set_scores(&db, id.to_string(), serde_json::json!({"form1": "8"}))
+                .await
+                .unwrap();
+        }
+
+        // Nothing is removed, so nothing is archived
+        config.auto_archive_on_reload = true;
+        let report = rebuild(&config, &db).await.unwrap();
+        assert_eq!(report, RebuildReport { archived: 0 });
+        assert_eq!(report.warning(), None);
+
+        // Only the scored event that goes is archived
+        config.events.retain(|event| event.id == "event1");
+        let report = rebuild(&config, &db).await.unwrap();
+        assert_eq!(report, RebuildReport { archived: 1 });
+        assert!(report
+            .warning()
+            .unwrap()
+            .contains("Archived 1 scored events"));
+
+        let archived = ArchivedEvents::all(&db).await.unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].id, "year7-mixed-event2");
+        let events = Events::all(&db).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].parsed_decimal_scores()["form1"], 8.0);
+    }
 }
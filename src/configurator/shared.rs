@@ -0,0 +1,50 @@
+use std::sync::{Arc, RwLock};
+
+use crate::configurator::parser::Configuration;
+
+/// The running config, shared by every worker so a reload is seen everywhere at once
+#[derive(Clone, Default)]
+pub struct SharedConfig(Arc<RwLock<Arc<Configuration>>>);
+
+impl SharedConfig {
+    pub fn new(config: Configuration) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(config))))
+    }
+
+    /// The config as it is now. Later reloads don't change the returned copy, so it can be held
+    /// across awaits.
+    pub fn get(&self) -> Arc<Configuration> {
+        self.0.read().unwrap().clone()
+    }
+
+    /// Swap in a new config for every request that starts after this
+    pub fn replace(&self, config: Configuration) {
+        *self.0.write().unwrap() = Arc::new(config);
+    }
+}
+
+impl From<Configuration> for SharedConfig {
+    fn from(config: Configuration) -> Self {
+        Self::new(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_test() {
+        let shared = SharedConfig::new(Configuration {
+            version: "1".to_string(),
+            ..Default::default()
+        });
+        let before = shared.get();
+        shared.clone().replace(Configuration {
+            version: "2".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(before.version, "1");
+        assert_eq!(shared.get().version, "2");
+    }
+}
@@ -0,0 +1,139 @@
+use async_sqlite::{
+    rusqlite::{Connection, Row},
+    Pool,
+};
+use chrono::{DateTime, Utc};
+use log::debug;
+
+use crate::db::{events::Events, timed_conn, timed_conn_mut};
+
+/// A copy of an event's scores taken before the events table was rebuilt
+#[derive(Clone, PartialEq, Debug)]
+pub struct ArchivedEvents {
+    pub archive_id: i64,
+    pub archived_at: DateTime<Utc>,
+    pub id: String,
+    pub name: String,
+    pub year_id: String,
+    pub gender_id: String,
+    pub filter_key: String,
+    pub scores: String,
+}
+
+impl ArchivedEvents {
+    fn map_from_row(row: &Row) -> Result<Self, async_sqlite::rusqlite::Error> {
+        Ok(Self {
+            archive_id: row.get(0)?,
+            archived_at: row
+                .get::<_, String>(1)?
+                .parse::<DateTime<Utc>>()
+                .unwrap_or_default(),
+            id: row.get(2)?,
+            name: row.get(3)?,
+            year_id: row.get(4)?,
+            gender_id: row.get(5)?,
+            filter_key: row.get(6)?,
+            scores: row.get(7)?,
+        })
+    }
+
+    /// Copy every event with a non-zero score into the archive, returning how many were archived
+    pub async fn archive_current(pool: &Pool) -> Result<usize, async_sqlite::Error> {
+        let scored: Vec<Events> = Events::all(pool)
+            .await?
+            .into_iter()
+            .filter(Events::is_scored)
+            .collect();
+
+        timed_conn_mut(pool, move |conn| {
+            let tx = conn.transaction()?;
+            Self::archive_with(&tx, &scored)?;
+            tx.commit()?;
+            Ok(scored.len())
+        })
+        .await
+    }
+
+    /// Copy `events` into the archive inside the caller's transaction
+    pub(crate) fn archive_with(
+        conn: &Connection,
+        events: &[Events],
+    ) -> Result<(), async_sqlite::rusqlite::Error> {
+        let archived_at = Utc::now().to_rfc3339();
+        for event in events {
+            debug!("Archiving Event with id {}", event.id);
+            conn.execute(
+                "INSERT INTO archived_events(archived_at, id, name, year_id, gender_id, filter_key, scores) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7);",
+                [
+                    &archived_at,
+                    &event.id,
+                    &event.name,
+                    &event.year_id,
+                    &event.gender_id,
+                    &event.filter_key,
+                    &event.scores,
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM archived_events ORDER BY archive_id")?;
+            let archived_iter = stmt.query_map([], Self::map_from_row)?;
+            let mut archived = Vec::new();
+
+            for event in archived_iter {
+                archived.push(event?);
+            }
+            Ok(archived)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{db::years::Years, test_harness};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn archive_current_test() {
+        let db = test_harness::setup_db("archived_events_archive_current").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        for (id, scores) in [
+            ("scored", json!({"a": "3", "b": 0})),
+            ("unscored", json!({"a": 0, "b": 0})),
+            ("decimal", json!({"a": "0.5", "b": 0})),
+        ] {
+            assert!(Events::new(
+                id.to_string(),
+                "Test".to_string(),
+                "test".to_string(),
+                "mixed".to_string(),
+                "test".to_string(),
+                scores.to_string()
+            )
+            .insert(&db)
+            .await
+            .is_ok());
+        }
+
+        // Fractional scores count as scored too
+        assert_eq!(ArchivedEvents::archive_current(&db).await.unwrap(), 2);
+
+        let mut archived = ArchivedEvents::all(&db).await.unwrap();
+        archived.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(archived.len(), 2);
+        assert_eq!(archived[0].id, "decimal");
+        assert_eq!(archived[1].id, "scored");
+        assert_eq!(archived[1].scores, json!({"a": "3", "b": 0}).to_string());
+    }
+}
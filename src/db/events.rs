@@ -1,8 +1,19 @@
-use async_sqlite::{rusqlite::Row, Pool};
+use std::collections::HashMap;
+
+use async_sqlite::{
+    rusqlite::{Connection, OptionalExtension, Row, TransactionBehavior},
+    Pool,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
 use log::debug;
+use serde::Serialize;
 use serde_json::Value;
 
-#[derive(Clone, PartialEq, Debug)]
+use crate::configurator::parser::Configuration;
+use crate::db::{timed_conn, timed_conn_mut};
+use crate::ternary;
+
+#[derive(Clone, PartialEq, Debug, Serialize)]
 pub struct Events {
     pub id: String,
     pub name: String,
@@ -10,6 +21,103 @@ pub struct Events {
     pub gender_id: String,
     pub filter_key: String,
     pub scores: String,
+    /// When the event was inserted or its scores last changed
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Categories copied from the config event, stored as a JSON array
+    pub tags: Vec<String>,
+    /// Added by an admin rather than generated from the config, kept across rebuilds
+    pub manual: bool,
+    /// Results are final, score routes refuse to change them until it is unlocked
+    pub locked: bool,
+}
+
+/// SQL for the current time, in the same format as `format_timestamp` so they compare as strings
+pub(crate) const SQL_NOW: &str = "strftime('%Y-%m-%dT%H:%M:%fZ', 'now')";
+
+/// SQL recording that the scoreboard changed now, run by triggers on events, years and forms
+pub(crate) const SQL_MARK_MODIFIED: &str = "INSERT INTO modified(id, at) VALUES (1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now')) ON CONFLICT(id) DO UPDATE SET at = excluded.at";
+
+/// One change to an event's scores, recorded by every method that writes them
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct ScoreHistoryEntry {
+    pub event_id: String,
+    pub old_scores: String,
+    pub new_scores: String,
+    pub changed_at: Option<DateTime<Utc>>,
+}
+
+/// `Events::parsed_decimal_scores` for a scores string from elsewhere, such as `score_history`
+pub fn parse_decimal_scores(scores: &str) -> HashMap<String, f64> {
+    serde_json::from_str::<HashMap<String, Value>>(scores)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(form_id, score)| Some((form_id, decimal_score(&score)?)))
+        .collect()
+}
+
+impl ScoreHistoryEntry {
+    fn map_from_row(row: &Row) -> Result<Self, async_sqlite::rusqlite::Error> {
+        Ok(Self {
+            event_id: row.get(0)?,
+            old_scores: row.get(1)?,
+            new_scores: row.get(2)?,
+            changed_at: row
+                .get::<_, Option<String>>(3)?
+                .and_then(|timestamp| DateTime::parse_from_rfc3339(&timestamp).ok())
+                .map(|timestamp| timestamp.with_timezone(&Utc)),
+        })
+    }
+}
+
+/// Save the scores an event had before a change, inside the caller's transaction
+pub(crate) fn record_history(
+    conn: &Connection,
+    id: &str,
+    old_scores: &str,
+    new_scores: &str,
+) -> Result<(), async_sqlite::rusqlite::Error> {
+    conn.execute(
+        &format!(
+            "INSERT INTO score_history(event_id, old_scores, new_scores, changed_at) VALUES (?1, ?2, ?3, {});",
+            SQL_NOW
+        ),
+        [id, old_scores, new_scores],
+    )?;
+    Ok(())
+}
+
+/// An event's stored scores and whether it is locked, read inside the caller's transaction so the
+/// lock can't change before the write. `None` if the event doesn't exist.
+fn scores_for_update(
+    conn: &Connection,
+    id: &str,
+) -> Result<Option<(String, bool)>, async_sqlite::rusqlite::Error> {
+    conn.query_row(
+        "SELECT scores, locked FROM events WHERE id = ?1;",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+}
+
+/// A single stored score as a number, allowing fractions such as "10.5". Anything that isn't a
+/// finite number gives `None`.
+fn decimal_score(score: &Value) -> Option<f64> {
+    match score {
+        Value::String(score) => score.trim().parse::<f64>().ok(),
+        Value::Number(score) => score.as_f64(),
+        _ => None,
+    }
+    .filter(|score| score.is_finite())
+}
+
+/// A single stored score for read-modify-write helpers, treating anything unreadable as zero
+fn score_value(score: &Value) -> f64 {
+    decimal_score(score).unwrap_or(0.0)
+}
+
+pub(crate) fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Millis, true)
 }
 
 impl Events {
@@ -28,10 +136,37 @@ impl Events {
             gender_id,
             filter_key,
             scores: scores,
+            updated_at: None,
+            tags: vec![],
+            manual: false,
+            locked: false,
         }
     }
 
-    fn map_from_row(row: &Row) -> Result<Self, async_sqlite::Error> {
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn with_manual(mut self, manual: bool) -> Self {
+        self.manual = manual;
+        self
+    }
+
+    /// Scores keyed by form id allowing fractions such as "10.5", skipping anything that isn't a
+    /// finite number
+    pub fn parsed_decimal_scores(&self) -> HashMap<String, f64> {
+        parse_decimal_scores(&self.scores)
+    }
+
+    /// Whether any form has a non-zero score. Blank, unparseable and `{}` scores count as unscored.
+    pub fn is_scored(&self) -> bool {
+        self.parsed_decimal_scores()
+            .values()
+            .any(|score| *score != 0.0)
+    }
+
+    pub(crate) fn map_from_row(row: &Row) -> Result<Self, async_sqlite::rusqlite::Error> {
         Ok(Self {
             id: row.get(0)?,
             name: row.get(1)?,
@@ -39,29 +174,95 @@ impl Events {
             gender_id: row.get(3)?,
             filter_key: row.get(4)?,
             scores: row.get(5)?,
+            updated_at: row
+                .get::<_, Option<String>>(6)?
+                .and_then(|timestamp| DateTime::parse_from_rfc3339(&timestamp).ok())
+                .map(|timestamp| timestamp.with_timezone(&Utc)),
+            tags: serde_json::from_str(&row.get::<_, String>(7)?).unwrap_or_default(),
+            manual: row.get(8)?,
+            locked: row.get(9)?,
         })
     }
 
     pub async fn insert(self, pool: &Pool) -> Result<(), async_sqlite::Error> {
-        pool.conn(move |conn| {
-            debug!("Inserting Event with id {}", self.id);
-            conn.execute(
-                "INSERT INTO events(id, name, year_id, gender_id, filter_key, scores) VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
-                [self.id, self.name, self.year_id, self.gender_id, self.filter_key, self.scores],
-            )
-            .unwrap();
-            Ok(())
+        timed_conn(pool, move |conn| self.insert_with(conn)).await
+    }
+
+    /// Insert several events in one transaction, so either all of them are added or none are
+    pub async fn insert_many(pool: &Pool, events: Vec<Self>) -> Result<(), async_sqlite::Error> {
+        timed_conn_mut(pool, move |conn| {
+            let tx = conn.transaction()?;
+            for event in events {
+                event.insert_with(&tx)?;
+            }
+            tx.commit()
         })
-        .await?;
+        .await
+    }
+
+    pub(crate) fn insert_with(
+        self,
+        conn: &Connection,
+    ) -> Result<(), async_sqlite::rusqlite::Error> {
+        debug!("Inserting Event with id {}", self.id);
+        conn.execute(
+            &format!("INSERT INTO events(id, name, year_id, gender_id, filter_key, scores, tags, manual, locked, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, {});", SQL_NOW),
+            [self.id, self.name, self.year_id, self.gender_id, self.filter_key, self.scores, serde_json::to_string(&self.tags).unwrap(), ternary!(self.manual => 1, 0).to_string(), ternary!(self.locked => 1, 0).to_string()],
+        )?;
         Ok(())
     }
 
+    /// Events added by an admin rather than generated from the config
+    pub async fn manual(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM events WHERE manual = 1 ORDER BY id")?;
+            let event_iter = stmt.query_map([], Self::map_from_row)?;
+            let mut events = Vec::new();
+
+            for event in event_iter {
+                events.push(event?);
+            }
+            Ok(events)
+        })
+        .await
+    }
+
+    /// Every event, in the order they were inserted
     pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
-        pool.conn(move |conn| {
-            let mut stmt = conn.prepare("SELECT * FROM events")?;
-            let event_iter = stmt
-                .query_map([], |row| Ok(Self::map_from_row(row).unwrap()))
-                .unwrap();
+        timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM events ORDER BY rowid")?;
+            let event_iter = stmt.query_map([], Self::map_from_row)?;
+            let mut events = Vec::new();
+
+            for event in event_iter {
+                events.push(event?);
+            }
+            Ok(events)
+        })
+        .await
+    }
+
+    /// Events nobody has entered a score for yet, ordered by id
+    pub async fn unscored(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
+        let mut events: Vec<Self> = Self::all(pool)
+            .await?
+            .into_iter()
+            .filter(|event| !event.is_scored())
+            .collect();
+        events.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(events)
+    }
+
+    /// Up to `limit` events ordered by id, skipping the first `offset`. Use `count` for the
+    /// number of pages.
+    pub async fn paged(
+        pool: &Pool,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Self>, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM events ORDER BY id LIMIT ?1 OFFSET ?2")?;
+            let event_iter = stmt.query_map([limit, offset], Self::map_from_row)?;
             let mut events = Vec::new();
 
             for event in event_iter {
@@ -72,17 +273,24 @@ impl Events {
         .await
     }
 
+    pub async fn find_by_id(pool: &Pool, id: String) -> Result<Option<Self>, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM events WHERE id = ?1")?;
+            stmt.query_row([id], Self::map_from_row).optional()
+        })
+        .await
+    }
+
     pub async fn r#where(
         pool: &Pool,
         year: Option<String>,
         activity: Option<String>,
         group: Option<String>,
+        tag: Option<String>,
     ) -> Result<Vec<Self>, async_sqlite::Error> {
-        pool.conn(move |conn| {
+        timed_conn(pool, move |conn| {
             let mut stmt = conn.prepare("SELECT * FROM events")?;
-            let event_iter = stmt
-                .query_map([], |row| Ok(Self::map_from_row(row).unwrap()))
-                .unwrap();
+            let event_iter = stmt.query_map([], Self::map_from_row)?;
             let mut events = Vec::new();
 
             for event in event_iter {
@@ -102,6 +310,11 @@ impl Events {
                         continue;
                     }
                 }
+                if let Some(ref t) = tag {
+                    if !evt.tags.contains(t) {
+                        continue;
+                    }
+                }
                 events.push(evt);
             }
             Ok(events)
@@ -109,40 +322,569 @@ impl Events {
         .await
     }
 
+    /// Replace one event's scores, refusing if the event is locked
     pub async fn set_scores(
         pool: &Pool,
         id: String,
         scores: Value,
-    ) -> Result<(), async_sqlite::Error> {
-        pool.conn(move |conn| {
+    ) -> Result<ScoreWrite, async_sqlite::Error> {
+        timed_conn_mut(pool, move |conn| {
             debug!("Setting Scores for Event with id {}", id);
-            conn.execute(
-                "UPDATE events SET scores = ?1 WHERE id = ?2;",
-                [serde_json::to_string(&scores).unwrap(), id],
-            )
-            .unwrap();
-            Ok(())
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let old_scores = match scores_for_update(&tx, &id)? {
+                None => return Ok(ScoreWrite::UnknownEvent),
+                Some((_, true)) => return Ok(ScoreWrite::Locked(id)),
+                Some((old_scores, false)) => old_scores,
+            };
+
+            let new_scores = serde_json::to_string(&scores).unwrap();
+            tx.execute(
+                &format!(
+                    "UPDATE events SET scores = ?1, updated_at = {} WHERE id = ?2;",
+                    SQL_NOW
+                ),
+                [&new_scores, &id],
+            )?;
+            record_history(&tx, &id, &old_scores, &new_scores)?;
+            tx.commit()?;
+            Ok(ScoreWrite::Written(()))
+        })
+        .await
+    }
+
+    /// Replace the scores of several events at once. Nothing is saved if any of them is locked,
+    /// ids that don't match an event are skipped.
+    pub async fn set_many_scores(
+        pool: &Pool,
+        scores: Vec<(String, Value)>,
+    ) -> Result<ScoreWrite, async_sqlite::Error> {
+        timed_conn_mut(pool, move |conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            for (id, scores) in scores {
+                let old_scores = match scores_for_update(&tx, &id)? {
+                    None => continue,
+                    Some((_, true)) => return Ok(ScoreWrite::Locked(id)),
+                    Some((old_scores, false)) => old_scores,
+                };
+
+                debug!("Setting Scores for Event with id {}", id);
+                let new_scores = serde_json::to_string(&scores).unwrap();
+                tx.execute(
+                    &format!(
+                        "UPDATE events SET scores = ?1, updated_at = {} WHERE id = ?2;",
+                        SQL_NOW
+                    ),
+                    [&new_scores, &id],
+                )?;
+                record_history(&tx, &id, &old_scores, &new_scores)?;
+            }
+            tx.commit()?;
+            Ok(ScoreWrite::Written(()))
+        })
+        .await
+    }
+
+    /// Put one event's scores back to `empty_scores`, refusing if the event is locked
+    pub async fn reset_scores(
+        pool: &Pool,
+        id: String,
+        empty_scores: String,
+    ) -> Result<ScoreWrite, async_sqlite::Error> {
+        timed_conn_mut(pool, move |conn| {
+            debug!("Resetting Scores for Event with id {}", id);
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let old_scores = match scores_for_update(&tx, &id)? {
+                None => return Ok(ScoreWrite::UnknownEvent),
+                Some((_, true)) => return Ok(ScoreWrite::Locked(id)),
+                Some((old_scores, false)) => old_scores,
+            };
+
+            tx.execute(
+                &format!(
+                    "UPDATE events SET scores = ?1, updated_at = {} WHERE id = ?2;",
+                    SQL_NOW
+                ),
+                [&empty_scores, &id],
+            )?;
+            record_history(&tx, &id, &old_scores, &empty_scores)?;
+            tx.commit()?;
+            Ok(ScoreWrite::Written(()))
+        })
+        .await
+    }
+
+    /// Exchange two forms' scores within one event
+    pub async fn swap_scores(
+        pool: &Pool,
+        id: String,
+        form_a: String,
+        form_b: String,
+    ) -> Result<SwapScores, async_sqlite::Error> {
+        timed_conn_mut(pool, move |conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let old_scores = match scores_for_update(&tx, &id)? {
+                None => return Ok(SwapScores::UnknownEvent),
+                Some((_, true)) => return Ok(SwapScores::Locked),
+                Some((old_scores, false)) => old_scores,
+            };
+
+            let mut scores = serde_json::from_str::<serde_json::Map<String, Value>>(&old_scores)
+                .unwrap_or_default();
+            for form in [&form_a, &form_b] {
+                if !scores.contains_key(form) {
+                    return Ok(SwapScores::UnknownForm(form.clone()));
+                }
+            }
+
+            let score_a = scores[&form_a].clone();
+            let score_b = scores.insert(form_b.clone(), score_a);
+            scores.insert(form_a.clone(), score_b.unwrap_or_default());
+
+            debug!(
+                "Swapping {} and {} for Event with id {}",
+                form_a, form_b, id
+            );
+            let new_scores = Value::Object(scores).to_string();
+            tx.execute(
+                &format!(
+                    "UPDATE events SET scores = ?1, updated_at = {} WHERE id = ?2;",
+                    SQL_NOW
+                ),
+                [&new_scores, &id],
+            )?;
+            record_history(&tx, &id, &old_scores, &new_scores)?;
+            tx.commit()?;
+            Ok(SwapScores::Swapped)
+        })
+        .await
+    }
+
+    /// Add `delta` to one form's score in a single transaction so concurrent quick-add buttons
+    /// don't lose updates, starting from zero if the form has no score yet. Returns the new score.
+    pub async fn add_to_form_score(
+        pool: &Pool,
+        id: String,
+        form_id: String,
+        delta: f64,
+    ) -> Result<ScoreWrite<f64>, async_sqlite::Error> {
+        timed_conn_mut(pool, move |conn| {
+            // Take the write lock up front so two increments can't both read the old value
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let old_scores = match scores_for_update(&tx, &id)? {
+                None => return Ok(ScoreWrite::UnknownEvent),
+                Some((_, true)) => return Ok(ScoreWrite::Locked(id)),
+                Some((old_scores, false)) => old_scores,
+            };
+
+            let mut scores = serde_json::from_str::<serde_json::Map<String, Value>>(&old_scores)
+                .unwrap_or_default();
+            let current = scores.get(&form_id).map(score_value).unwrap_or(0.0);
+            let total = current + delta;
+            scores.insert(
+                form_id.clone(),
+                Value::String(crate::utils::format_points(total, false)),
+            );
+
+            let new_scores = Value::Object(scores).to_string();
+            tx.execute(
+                &format!(
+                    "UPDATE events SET scores = ?1, updated_at = {} WHERE id = ?2;",
+                    SQL_NOW
+                ),
+                [&new_scores, &id],
+            )?;
+            record_history(&tx, &id, &old_scores, &new_scores)?;
+            tx.commit()?;
+            log::info!(
+                "Added {} to form {} in event {}, now {}",
+                delta,
+                form_id,
+                id,
+                total
+            );
+            Ok(ScoreWrite::Written(total))
+        })
+        .await
+    }
+
+    /// Lock or unlock an event's scores, returning whether the event exists
+    pub async fn set_locked(
+        pool: &Pool,
+        id: String,
+        locked: bool,
+    ) -> Result<bool, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let updated =
+                conn.execute("UPDATE events SET locked = ?1 WHERE id = ?2;", (locked, id))?;
+            Ok(updated > 0)
+        })
+        .await
+    }
+
+    /// Every recorded change to an event's scores, newest first
+    pub async fn score_history(
+        pool: &Pool,
+        id: String,
+    ) -> Result<Vec<ScoreHistoryEntry>, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT event_id, old_scores, new_scores, changed_at FROM score_history WHERE event_id = ?1 ORDER BY changed_at DESC, id DESC",
+            )?;
+            let entry_iter = stmt.query_map([id], ScoreHistoryEntry::map_from_row)?;
+            let mut entries = Vec::new();
+
+            for entry in entry_iter {
+                entries.push(entry?);
+            }
+            Ok(entries)
+        })
+        .await
+    }
+
+    /// Every recorded change to every event's scores, oldest first
+    pub async fn all_score_history(
+        pool: &Pool,
+    ) -> Result<Vec<ScoreHistoryEntry>, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT event_id, old_scores, new_scores, changed_at FROM score_history ORDER BY changed_at, id",
+            )?;
+            let entry_iter = stmt.query_map([], ScoreHistoryEntry::map_from_row)?;
+            entry_iter.collect()
+        })
+        .await
+    }
+
+    /// Every event with the scores it had at `at`, replayed from `score_history`. An event changed
+    /// since then takes the old scores of its first later change, the rest keep their current
+    /// scores. Events are not dated, so ones added after `at` are still listed.
+    pub async fn as_of(pool: &Pool, at: DateTime<Utc>) -> Result<Vec<Self>, async_sqlite::Error> {
+        let mut events = Self::all(pool).await?;
+        let earlier: HashMap<String, String> = timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT event_id, old_scores FROM score_history h WHERE changed_at > ?1 AND id = (SELECT id FROM score_history WHERE event_id = h.event_id AND changed_at > ?1 ORDER BY changed_at, id LIMIT 1)",
+            )?;
+            let rows = stmt.query_map([format_timestamp(at)], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+            rows.collect::<Result<_, _>>()
         })
         .await?;
-        Ok(())
+
+        for event in events.iter_mut() {
+            if let Some(scores) = earlier.get(&event.id) {
+                event.scores = scores.clone();
+            }
+        }
+        Ok(events)
+    }
+
+    /// Fold `from_form`'s scores into `into_form` in every event and drop `from_form`, for when
+    /// two forms combine. Returns how many events changed. Nothing is merged if a locked event
+    /// has a score for `from_form`.
+    pub async fn merge_forms(
+        pool: &Pool,
+        from_form: String,
+        into_form: String,
+    ) -> Result<ScoreWrite<usize>, async_sqlite::Error> {
+        timed_conn_mut(pool, move |conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let rows: Vec<(String, String, bool)> = {
+                let mut stmt = tx.prepare("SELECT id, scores, locked FROM events")?;
+                let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+                rows.collect::<Result<_, _>>()?
+            };
+
+            let mut merged = 0;
+            for (id, old_scores, locked) in rows {
+                let Ok(mut scores) =
+                    serde_json::from_str::<serde_json::Map<String, Value>>(&old_scores)
+                else {
+                    continue;
+                };
+                let Some(from_score) = scores.remove(&from_form) else {
+                    continue;
+                };
+                if locked {
+                    return Ok(ScoreWrite::Locked(id));
+                }
+
+                let total = scores.get(&into_form).map(score_value).unwrap_or(0.0)
+                    + score_value(&from_score);
+                scores.insert(
+                    into_form.clone(),
+                    Value::String(crate::utils::format_points(total, false)),
+                );
+
+                let new_scores = Value::Object(scores).to_string();
+                tx.execute(
+                    &format!(
+                        "UPDATE events SET scores = ?1, updated_at = {} WHERE id = ?2;",
+                        SQL_NOW
+                    ),
+                    [&new_scores, &id],
+                )?;
+                record_history(&tx, &id, &old_scores, &new_scores)?;
+                merged += 1;
+            }
+            tx.commit()?;
+            log::info!(
+                "Merged form {} into {} across {} events",
+                from_form,
+                into_form,
+                merged
+            );
+            Ok(ScoreWrite::Written(merged))
+        })
+        .await
+    }
+
+    /// Every event's raw scores keyed by event id, to put back later with `restore_scores`
+    pub async fn snapshot_scores(
+        pool: &Pool,
+    ) -> Result<HashMap<String, String>, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare("SELECT id, scores FROM events")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<Result<_, _>>()
+        })
+        .await
+    }
+
+    /// Put back scores saved by `snapshot_scores` without adding or removing events. Ids that no
+    /// longer exist are skipped. Returns how many events changed.
+    pub async fn restore_scores(
+        pool: &Pool,
+        snapshot: HashMap<String, String>,
+    ) -> Result<usize, async_sqlite::Error> {
+        timed_conn_mut(pool, move |conn| {
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let mut restored = 0;
+            for (id, new_scores) in snapshot {
+                let old_scores: Option<String> = tx
+                    .query_row("SELECT scores FROM events WHERE id = ?1;", [&id], |row| {
+                        row.get(0)
+                    })
+                    .optional()?;
+                let Some(old_scores) = old_scores else {
+                    debug!("Skipping snapshot scores for unknown event {}", id);
+                    continue;
+                };
+                if old_scores == new_scores {
+                    continue;
+                }
+
+                tx.execute(
+                    &format!(
+                        "UPDATE events SET scores = ?1, updated_at = {} WHERE id = ?2;",
+                        SQL_NOW
+                    ),
+                    [&new_scores, &id],
+                )?;
+                record_history(&tx, &id, &old_scores, &new_scores)?;
+                restored += 1;
+            }
+            tx.commit()?;
+            Ok(restored)
+        })
+        .await
+    }
+
+    /// When events, years or forms last changed in any way, including deletes, lock changes and
+    /// rebuilds, or when `mark_modified` was last called
+    pub async fn last_modified(pool: &Pool) -> Result<Option<DateTime<Utc>>, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let latest: Option<String> = conn
+                .query_row("SELECT at FROM modified WHERE id = 1", [], |row| row.get(0))
+                .optional()?;
+            Ok(latest
+                .and_then(|timestamp| DateTime::parse_from_rfc3339(&timestamp).ok())
+                .map(|timestamp| timestamp.with_timezone(&Utc)))
+        })
+        .await
+    }
+
+    /// Move `last_modified` to now for changes outside the database, such as new gender multipliers
+    pub async fn mark_modified(pool: &Pool) -> Result<(), async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            conn.execute(SQL_MARK_MODIFIED, [])?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Events inserted or rescored after `since`
+    pub async fn changed_since(
+        pool: &Pool,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Self>, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let mut stmt =
+                conn.prepare("SELECT * FROM events WHERE updated_at > ?1 ORDER BY updated_at, id")?;
+            let event_iter = stmt.query_map([format_timestamp(since)], Self::map_from_row)?;
+            let mut events = Vec::new();
+
+            for event in event_iter {
+                events.push(event?);
+            }
+            Ok(events)
+        })
+        .await
+    }
+
+    /// Year ids that have at least one event, which can differ from the configured years
+    pub async fn distinct_years(pool: &Pool) -> Result<Vec<String>, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare("SELECT DISTINCT year_id FROM events ORDER BY year_id")?;
+            let year_iter = stmt.query_map([], |row| row.get(0))?;
+            let mut years = Vec::new();
+
+            for year in year_iter {
+                years.push(year?);
+            }
+            Ok(years)
+        })
+        .await
+    }
+
+    /// Every event's scores flattened to `(event_id, form_id, score)` rows, ordered by event then
+    /// form. Events with corrupt scores are skipped with a warning.
+    pub async fn score_rows(
+        pool: &Pool,
+    ) -> Result<Vec<(String, String, f64)>, async_sqlite::Error> {
+        let mut rows = Vec::new();
+        for event in Self::all(pool).await? {
+            if serde_json::from_str::<serde_json::Map<String, Value>>(&event.scores).is_err() {
+                log::warn!("Skipping corrupt scores for event {}", event.id);
+                continue;
+            }
+            for (form_id, score) in event.parsed_decimal_scores() {
+                rows.push((event.id.clone(), form_id, score));
+            }
+        }
+        rows.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        Ok(rows)
+    }
+
+    /// Ids of events whose scores aren't a valid JSON object
+    pub async fn find_corrupt_scores(pool: &Pool) -> Result<Vec<String>, async_sqlite::Error> {
+        Ok(Self::all(pool)
+            .await?
+            .into_iter()
+            .filter(|event| {
+                serde_json::from_str::<serde_json::Map<String, Value>>(&event.scores).is_err()
+            })
+            .map(|event| event.id)
+            .collect())
+    }
+
+    /// Reset every event with corrupt scores to `empty_scores`, returning the repaired ids.
+    /// Locked events are left alone.
+    pub async fn repair_corrupt_scores(
+        pool: &Pool,
+        empty_scores: String,
+    ) -> Result<Vec<String>, async_sqlite::Error> {
+        let mut repaired = Vec::new();
+        for id in Self::find_corrupt_scores(pool).await? {
+            if let ScoreWrite::Written(()) =
+                Self::reset_scores(pool, id.clone(), empty_scores.clone()).await?
+            {
+                repaired.push(id);
+            }
+        }
+        Ok(repaired)
     }
 
     pub async fn delete_all(pool: &Pool) -> Result<(), async_sqlite::Error> {
-        pool.conn(move |conn| {
-            conn.execute("DELETE FROM events;", []).unwrap();
+        timed_conn(pool, move |conn| {
+            conn.execute("DELETE FROM events;", [])?;
             Ok(())
         })
         .await?;
         Ok(())
     }
 
+    /// Delete a single event, returning whether it existed
+    pub async fn delete(pool: &Pool, id: String) -> Result<bool, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let removed = conn.execute("DELETE FROM events WHERE id = ?1;", [id])?;
+            Ok(removed > 0)
+        })
+        .await
+    }
+
     pub async fn count(pool: &Pool) -> Result<i64, async_sqlite::Error> {
-        pool.conn(move |conn| {
+        timed_conn(pool, move |conn| {
             let count: i64 = conn.query_row("SELECT COUNT(*) FROM events", [], |row| row.get(0))?;
             Ok(count)
         })
         .await
     }
+
+    /// Compare every event's scores against the configured forms, reporting events with form ids
+    /// that aren't configured or configured forms that are missing
+    pub async fn scores_integrity(
+        pool: &Pool,
+        config: &Configuration,
+    ) -> Result<Vec<IntegrityIssue>, async_sqlite::Error> {
+        let form_ids: Vec<String> = config.forms.iter().map(|form| form.id.clone()).collect();
+        let mut issues = Vec::new();
+
+        for event in Self::all(pool).await? {
+            // Unparseable scores count as having no forms at all
+            let scores = serde_json::from_str::<serde_json::Map<String, Value>>(&event.scores)
+                .unwrap_or_default();
+
+            let extra_forms: Vec<String> = scores
+                .keys()
+                .filter(|key| !form_ids.contains(key))
+                .cloned()
+                .collect();
+            let missing_forms: Vec<String> = form_ids
+                .iter()
+                .filter(|id| !scores.contains_key(id.as_str()))
+                .cloned()
+                .collect();
+
+            if !extra_forms.is_empty() || !missing_forms.is_empty() {
+                issues.push(IntegrityIssue {
+                    event_id: event.id,
+                    extra_forms,
+                    missing_forms,
+                });
+            }
+        }
+        Ok(issues)
+    }
+}
+
+/// Outcome of a score write that can be refused by a lock
+#[derive(Clone, PartialEq, Debug)]
+pub enum ScoreWrite<T = ()> {
+    Written(T),
+    UnknownEvent,
+    /// The event with this id is locked, nothing was saved
+    Locked(String),
+}
+
+/// Outcome of `Events::swap_scores`
+#[derive(Clone, PartialEq, Debug)]
+pub enum SwapScores {
+    Swapped,
+    UnknownEvent,
+    Locked,
+    /// The form id isn't in the event's scores
+    UnknownForm(String),
+}
+
+/// An event whose scores don't line up with the configured forms
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct IntegrityIssue {
+    pub event_id: String,
+    /// Form ids in the scores that aren't in the config
+    pub extra_forms: Vec<String>,
+    /// Configured form ids absent from the scores
+    pub missing_forms: Vec<String>,
 }
 
 #[cfg(test)]
@@ -170,7 +912,11 @@ mod tests {
                 year_id: "test".to_string(),
                 gender_id: "mixed".to_string(),
                 filter_key: "test".to_string(),
-                scores: "{}".to_string()
+                scores: "{}".to_string(),
+                updated_at: None,
+                tags: vec![],
+                manual: false,
+                locked: false,
             }
         )
     }
@@ -196,26 +942,35 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn all_test() {
-        let db = test_harness::setup_db("events_all").await;
+    async fn insert_duplicate_test() {
+        let db = test_harness::setup_db("events_insert_duplicate").await;
         assert!(Years::new("test".to_string(), "Test".to_string())
             .insert(&db)
             .await
             .is_ok());
-        assert!(Events::new(
+        let event = Events::new(
             "test-test".to_string(),
             "Test".to_string(),
             "test".to_string(),
             "mixed".to_string(),
             "test".to_string(),
-            "{}".to_string()
-        )
-        .insert(&db)
-        .await
-        .is_ok());
+            "{}".to_string(),
+        );
+        assert!(event.clone().insert(&db).await.is_ok());
+        assert!(event.insert(&db).await.is_err());
+        assert_eq!(Events::count(&db).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn find_by_id_test() {
+        let db = test_harness::setup_db("events_find_by_id").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
         assert!(Events::new(
-            "test-test2".to_string(),
-            "Test2".to_string(),
+            "test-test".to_string(),
+            "Test".to_string(),
             "test".to_string(),
             "mixed".to_string(),
             "test".to_string(),
@@ -224,12 +979,61 @@ mod tests {
         .insert(&db)
         .await
         .is_ok());
-        assert!(Events::new(
-            "test-test3".to_string(),
-            "Test3".to_string(),
-            "test".to_string(),
-            "mixed".to_string(),
-            "test".to_string(),
+        let found = Events::find_by_id(&db, "test-test".to_string())
+            .await
+            .unwrap();
+        assert!(found.is_some());
+        let event = found.unwrap();
+        assert_eq!(event.id, "test-test");
+        assert_eq!(event.name, "Test");
+        assert_eq!(event.year_id, "test");
+        assert_eq!(event.gender_id, "mixed");
+    }
+
+    #[tokio::test]
+    async fn find_by_id_not_found_test() {
+        let db = test_harness::setup_db("events_find_by_id_not_found").await;
+        let found = Events::find_by_id(&db, "missing".to_string())
+            .await
+            .unwrap();
+        assert!(found.is_none());
+    }
+
+    #[tokio::test]
+    async fn all_test() {
+        let db = test_harness::setup_db("events_all").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Events::new(
+            "test-test".to_string(),
+            "Test".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string(),
+            "{}".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+        assert!(Events::new(
+            "test-test2".to_string(),
+            "Test2".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string(),
+            "{}".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+        assert!(Events::new(
+            "test-test3".to_string(),
+            "Test3".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string(),
             "{}".to_string()
         )
         .insert(&db)
@@ -302,7 +1106,7 @@ mod tests {
             .await
             .is_ok());
             assert_eq!(
-                Events::r#where(&db, Some(year_id.to_string()), None, None)
+                Events::r#where(&db, Some(year_id.to_string()), None, None, None)
                     .await
                     .unwrap()
                     .len(),
@@ -310,18 +1114,21 @@ mod tests {
             );
         }
         assert_eq!(
-            Events::r#where(&db, None, None, None).await.unwrap().len(),
+            Events::r#where(&db, None, None, None, None)
+                .await
+                .unwrap()
+                .len(),
             12
         );
         assert_eq!(
-            Events::r#where(&db, None, Some("test".to_string()), None)
+            Events::r#where(&db, None, Some("test".to_string()), None, None)
                 .await
                 .unwrap()
                 .len(),
             12
         );
         assert_eq!(
-            Events::r#where(&db, None, None, Some("mixed".to_string()))
+            Events::r#where(&db, None, None, Some("mixed".to_string()), None)
                 .await
                 .unwrap()
                 .len(),
@@ -367,8 +1174,8 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn delete_all_test() {
-        let db = test_harness::setup_db("events_delete_all").await;
+    async fn score_history_test() {
+        let db = test_harness::setup_db("events_score_history").await;
         assert!(Years::new("test".to_string(), "Test".to_string())
             .insert(&db)
             .await
@@ -384,31 +1191,217 @@ mod tests {
         .insert(&db)
         .await
         .is_ok());
+
+        Events::set_scores(&db, "test-test".to_string(), json!({"f1": "3"}))
+            .await
+            .unwrap();
+        Events::set_scores(&db, "test-test".to_string(), json!({"f1": "5"}))
+            .await
+            .unwrap();
+
+        let history = Events::score_history(&db, "test-test".to_string())
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].old_scores, json!({"f1": "3"}).to_string());
+        assert_eq!(history[0].new_scores, json!({"f1": "5"}).to_string());
+        assert_eq!(history[1].old_scores, "{}");
+        assert_eq!(history[1].new_scores, json!({"f1": "3"}).to_string());
+        assert!(history.iter().all(|entry| entry.changed_at.is_some()));
+
+        assert!(Events::score_history(&db, "missing".to_string())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn reset_scores_test() {
+        let db = test_harness::setup_db("events_reset_scores").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
         assert!(Events::new(
-            "test-test2".to_string(),
-            "Test2".to_string(),
+            "test-test".to_string(),
+            "Test".to_string(),
             "test".to_string(),
             "mixed".to_string(),
             "test".to_string(),
-            "{}".to_string()
+            json!({"a": "5"}).to_string()
         )
         .insert(&db)
         .await
         .is_ok());
+
+        assert_eq!(
+            Events::reset_scores(&db, "test-test".to_string(), json!({"a": 0}).to_string())
+                .await
+                .unwrap(),
+            ScoreWrite::Written(())
+        );
+        assert_eq!(
+            Events::all(&db).await.unwrap()[0].scores,
+            json!({"a": 0}).to_string()
+        );
+        assert_eq!(
+            Events::reset_scores(&db, "missing".to_string(), "{}".to_string())
+                .await
+                .unwrap(),
+            ScoreWrite::UnknownEvent
+        );
+    }
+
+    #[tokio::test]
+    async fn swap_scores_test() {
+        let db = test_harness::setup_db("events_swap_scores").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
         assert!(Events::new(
-            "test-test3".to_string(),
-            "Test3".to_string(),
+            "test-test".to_string(),
+            "Test".to_string(),
             "test".to_string(),
             "mixed".to_string(),
             "test".to_string(),
-            "{}".to_string()
+            json!({"a": "8", "b": "2", "c": "5"}).to_string()
         )
         .insert(&db)
         .await
         .is_ok());
+
+        assert_eq!(
+            Events::swap_scores(
+                &db,
+                "test-test".to_string(),
+                "a".to_string(),
+                "b".to_string()
+            )
+            .await
+            .unwrap(),
+            SwapScores::Swapped
+        );
+        assert_eq!(
+            Events::all(&db).await.unwrap()[0].parsed_decimal_scores(),
+            HashMap::from([
+                ("a".to_string(), 2.0),
+                ("b".to_string(), 8.0),
+                ("c".to_string(), 5.0)
+            ])
+        );
+
+        assert_eq!(
+            Events::swap_scores(
+                &db,
+                "test-test".to_string(),
+                "a".to_string(),
+                "z".to_string()
+            )
+            .await
+            .unwrap(),
+            SwapScores::UnknownForm("z".to_string())
+        );
+        assert_eq!(
+            Events::swap_scores(&db, "missing".to_string(), "a".to_string(), "b".to_string())
+                .await
+                .unwrap(),
+            SwapScores::UnknownEvent
+        );
+    }
+
+    #[tokio::test]
+    async fn unscored_test() {
+        let db = test_harness::setup_db("events_unscored").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        for (id, scores) in [
+            ("zeros", json!({"a": 0, "b": "0"})),
+            ("one-form", json!({"a": 0, "b": "3"})),
+            ("empty", json!({})),
+            ("fraction", json!({"a": "0.5"})),
+            ("blank", json!({"a": ""})),
+        ] {
+            assert!(Events::new(
+                id.to_string(),
+                "Test".to_string(),
+                "test".to_string(),
+                "mixed".to_string(),
+                "test".to_string(),
+                scores.to_string()
+            )
+            .insert(&db)
+            .await
+            .is_ok());
+        }
+
+        let ids: Vec<String> = Events::unscored(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| event.id)
+            .collect();
+        assert_eq!(ids, vec!["blank", "empty", "zeros"]);
+    }
+
+    #[tokio::test]
+    async fn changed_since_test() {
+        let db = test_harness::setup_db("events_changed_since").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        for id in ["first", "second"] {
+            assert!(Events::new(
+                id.to_string(),
+                "Test".to_string(),
+                "test".to_string(),
+                "mixed".to_string(),
+                "test".to_string(),
+                "{}".to_string()
+            )
+            .insert(&db)
+            .await
+            .is_ok());
+        }
+        assert!(Events::all(&db)
+            .await
+            .unwrap()
+            .iter()
+            .all(|event| event.updated_at.is_some()));
+
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let since = Utc::now();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        assert!(
+            Events::set_scores(&db, "second".to_string(), json!({"a": "1"}))
+                .await
+                .is_ok()
+        );
+
+        let changed = Events::changed_since(&db, since).await.unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].id, "second");
+        assert!(changed[0].updated_at.unwrap() > since);
+        assert_eq!(
+            Events::last_modified(&db).await.unwrap(),
+            changed[0].updated_at
+        );
+    }
+
+    #[tokio::test]
+    async fn last_modified_covers_every_change_test() {
+        let db = test_harness::setup_db("events_last_modified_every_change").await;
+        assert_eq!(Events::last_modified(&db).await.unwrap(), None);
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
         assert!(Events::new(
-            "test-test4".to_string(),
-            "Test4".to_string(),
+            "test-test".to_string(),
+            "Test".to_string(),
             "test".to_string(),
             "mixed".to_string(),
             "test".to_string(),
@@ -417,21 +1410,75 @@ mod tests {
         .insert(&db)
         .await
         .is_ok());
-        assert_eq!(Events::all(&db).await.unwrap().len(), 4);
-        assert!(Events::delete_all(&db).await.is_ok());
-        assert_eq!(Events::all(&db).await.unwrap().len(), 0);
+
+        let mut previous = Events::last_modified(&db).await.unwrap().unwrap();
+        for step in ["lock", "mark", "delete"] {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            match step {
+                "lock" => assert!(Events::set_locked(&db, "test-test".to_string(), true)
+                    .await
+                    .unwrap()),
+                "mark" => Events::mark_modified(&db).await.unwrap(),
+                _ => assert!(Events::delete(&db, "test-test".to_string()).await.unwrap()),
+            }
+            let latest = Events::last_modified(&db).await.unwrap().unwrap();
+            assert!(latest > previous, "{} didn't move last_modified", step);
+            previous = latest;
+        }
     }
 
     #[tokio::test]
-    async fn count_test() {
-        let db = test_harness::setup_db("events_count").await;
+    async fn corrupt_scores_test() {
+        let db = test_harness::setup_db("events_corrupt_scores").await;
         assert!(Years::new("test".to_string(), "Test".to_string())
             .insert(&db)
             .await
             .is_ok());
+        for (id, scores) in [
+            ("good", r#"{"a":"1"}"#),
+            ("bad", "not json"),
+            ("list", "[]"),
+        ] {
+            assert!(Events::new(
+                id.to_string(),
+                "Test".to_string(),
+                "test".to_string(),
+                "mixed".to_string(),
+                "test".to_string(),
+                scores.to_string()
+            )
+            .insert(&db)
+            .await
+            .is_ok());
+        }
 
-        assert_eq!(Events::count(&db).await.unwrap(), 0);
+        let mut corrupt = Events::find_corrupt_scores(&db).await.unwrap();
+        corrupt.sort();
+        assert_eq!(corrupt, vec!["bad", "list"]);
+
+        let mut repaired = Events::repair_corrupt_scores(&db, json!({"a": 0}).to_string())
+            .await
+            .unwrap();
+        repaired.sort();
+        assert_eq!(repaired, vec!["bad", "list"]);
+        assert!(Events::find_corrupt_scores(&db).await.unwrap().is_empty());
+
+        let good = Events::all(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|event| event.id == "good")
+            .unwrap();
+        assert_eq!(good.scores, r#"{"a":"1"}"#);
+    }
 
+    #[tokio::test]
+    async fn delete_all_test() {
+        let db = test_harness::setup_db("events_delete_all").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
         assert!(Events::new(
             "test-test".to_string(),
             "Test".to_string(),
@@ -443,9 +1490,6 @@ mod tests {
         .insert(&db)
         .await
         .is_ok());
-
-        assert_eq!(Events::count(&db).await.unwrap(), 1);
-
         assert!(Events::new(
             "test-test2".to_string(),
             "Test2".to_string(),
@@ -457,35 +1501,45 @@ mod tests {
         .insert(&db)
         .await
         .is_ok());
-
-        assert_eq!(Events::count(&db).await.unwrap(), 2);
-    }
-
-    #[tokio::test]
-    async fn where_with_multiple_filters_test() {
-        let db = test_harness::setup_db("events_where_multiple").await;
-        assert!(Years::new("y9".to_string(), "Year 9".to_string())
-            .insert(&db)
-            .await
-            .is_ok());
-
         assert!(Events::new(
-            "y9-boys-test".to_string(),
-            "Test".to_string(),
-            "y9".to_string(),
-            "boys".to_string(),
+            "test-test3".to_string(),
+            "Test3".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string(),
+            "{}".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+        assert!(Events::new(
+            "test-test4".to_string(),
+            "Test4".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
             "test".to_string(),
             "{}".to_string()
         )
         .insert(&db)
         .await
         .is_ok());
+        assert_eq!(Events::all(&db).await.unwrap().len(), 4);
+        assert!(Events::delete_all(&db).await.is_ok());
+        assert_eq!(Events::all(&db).await.unwrap().len(), 0);
+    }
 
+    #[tokio::test]
+    async fn set_locked_test() {
+        let db = test_harness::setup_db("events_set_locked").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
         assert!(Events::new(
-            "y9-girls-test".to_string(),
+            "test-test".to_string(),
             "Test".to_string(),
-            "y9".to_string(),
-            "girls".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
             "test".to_string(),
             "{}".to_string()
         )
@@ -493,20 +1547,194 @@ mod tests {
         .await
         .is_ok());
 
-        // Filter by year and group
-        let events = Events::r#where(&db, Some("y9".to_string()), None, Some("boys".to_string()))
+        assert!(Events::set_locked(&db, "test-test".to_string(), true)
+            .await
+            .unwrap());
+        let event = Events::find_by_id(&db, "test-test".to_string())
             .await
+            .unwrap()
             .unwrap();
+        assert!(event.locked);
 
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].id, "y9-boys-test");
-
-        // Filter by year, activity and group
-        let events = Events::r#where(
-            &db,
+        assert!(Events::set_locked(&db, "test-test".to_string(), false)
+            .await
+            .unwrap());
+        assert!(
+            !Events::find_by_id(&db, "test-test".to_string())
+                .await
+                .unwrap()
+                .unwrap()
+                .locked
+        );
+        assert!(!Events::set_locked(&db, "missing".to_string(), true)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn delete_test() {
+        let db = test_harness::setup_db("events_delete").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Events::new(
+            "test-test".to_string(),
+            "Test".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string(),
+            "{}".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+        assert!(Events::new(
+            "test-test2".to_string(),
+            "Test2".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string(),
+            "{}".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+
+        assert!(Events::delete(&db, "test-test".to_string()).await.unwrap());
+        assert_eq!(Events::count(&db).await.unwrap(), 1);
+        let remaining = Events::all(&db).await.unwrap();
+        assert_eq!(remaining[0].id, "test-test2");
+
+        assert!(!Events::delete(&db, "test-test".to_string()).await.unwrap());
+        assert_eq!(Events::count(&db).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn paged_test() {
+        let db = test_harness::setup_db("events_paged").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        // Inserted out of order to check the window follows id order
+        for n in [3, 7, 0, 9, 1, 5, 8, 2, 6, 4] {
+            Events::new(
+                format!("test-{}", n),
+                "Test".to_string(),
+                "test".to_string(),
+                "mixed".to_string(),
+                "test".to_string(),
+                "{}".to_string(),
+            )
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let ids = |events: Vec<Events>| -> Vec<String> {
+            events.into_iter().map(|event| event.id).collect()
+        };
+        assert_eq!(
+            ids(Events::paged(&db, 3, 4).await.unwrap()),
+            vec!["test-4", "test-5", "test-6"]
+        );
+        assert_eq!(ids(Events::paged(&db, 3, 9).await.unwrap()), vec!["test-9"]);
+        assert!(Events::paged(&db, 3, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn count_test() {
+        let db = test_harness::setup_db("events_count").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+
+        assert_eq!(Events::count(&db).await.unwrap(), 0);
+
+        assert!(Events::new(
+            "test-test".to_string(),
+            "Test".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string(),
+            "{}".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+
+        assert_eq!(Events::count(&db).await.unwrap(), 1);
+
+        assert!(Events::new(
+            "test-test2".to_string(),
+            "Test2".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string(),
+            "{}".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+
+        assert_eq!(Events::count(&db).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn where_with_multiple_filters_test() {
+        let db = test_harness::setup_db("events_where_multiple").await;
+        assert!(Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+
+        assert!(Events::new(
+            "y9-boys-test".to_string(),
+            "Test".to_string(),
+            "y9".to_string(),
+            "boys".to_string(),
+            "test".to_string(),
+            "{}".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+
+        assert!(Events::new(
+            "y9-girls-test".to_string(),
+            "Test".to_string(),
+            "y9".to_string(),
+            "girls".to_string(),
+            "test".to_string(),
+            "{}".to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+
+        // Filter by year and group
+        let events = Events::r#where(
+            &db,
+            Some("y9".to_string()),
+            None,
+            Some("boys".to_string()),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id, "y9-boys-test");
+
+        // Filter by year, activity and group
+        let events = Events::r#where(
+            &db,
             Some("y9".to_string()),
             Some("test".to_string()),
             Some("boys".to_string()),
+            None,
         )
         .await
         .unwrap();
@@ -515,6 +1743,500 @@ mod tests {
         assert_eq!(events[0].id, "y9-boys-test");
     }
 
+    #[tokio::test]
+    async fn add_to_form_score_test() {
+        let db = test_harness::setup_db("events_add_to_form_score").await;
+        assert!(Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Events::new(
+            "y9-boys-100m".to_string(),
+            "100m".to_string(),
+            "y9".to_string(),
+            "boys".to_string(),
+            "100m".to_string(),
+            r#"{"a":"5"}"#.to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+
+        let (first, second) = tokio::join!(
+            Events::add_to_form_score(&db, "y9-boys-100m".to_string(), "a".to_string(), 10.0),
+            Events::add_to_form_score(&db, "y9-boys-100m".to_string(), "a".to_string(), 10.0),
+        );
+        let mut results: Vec<f64> = [first.unwrap(), second.unwrap()]
+            .into_iter()
+            .map(|written| match written {
+                ScoreWrite::Written(total) => total,
+                other => panic!("expected a new total, got {:?}", other),
+            })
+            .collect();
+        results.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(results, vec![15.0, 25.0]);
+
+        // A form without a score starts from zero
+        assert_eq!(
+            Events::add_to_form_score(&db, "y9-boys-100m".to_string(), "b".to_string(), 3.0)
+                .await
+                .unwrap(),
+            ScoreWrite::Written(3.0)
+        );
+        assert_eq!(
+            Events::add_to_form_score(&db, "missing".to_string(), "a".to_string(), 3.0)
+                .await
+                .unwrap(),
+            ScoreWrite::UnknownEvent
+        );
+
+        let events = Events::r#where(&db, None, None, None, None).await.unwrap();
+        let scores = events[0].parsed_decimal_scores();
+        assert_eq!(scores["a"], 25.0);
+        assert_eq!(scores["b"], 3.0);
+    }
+
+    #[tokio::test]
+    async fn locked_event_refuses_writes_test() {
+        let db = test_harness::setup_db("events_locked_writes").await;
+        assert!(Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        let scores = json!({"a": "5", "b": "3"}).to_string();
+        for id in ["y9-boys-100m", "y9-boys-200m"] {
+            Events::new(
+                id.to_string(),
+                id.to_string(),
+                "y9".to_string(),
+                "boys".to_string(),
+                id.to_string(),
+                scores.clone(),
+            )
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+        Events::set_locked(&db, "y9-boys-100m".to_string(), true)
+            .await
+            .unwrap();
+        let locked = ScoreWrite::Locked("y9-boys-100m".to_string());
+
+        assert_eq!(
+            Events::add_to_form_score(&db, "y9-boys-100m".to_string(), "a".to_string(), 3.0)
+                .await
+                .unwrap(),
+            ScoreWrite::Locked("y9-boys-100m".to_string())
+        );
+        assert_eq!(
+            Events::set_scores(&db, "y9-boys-100m".to_string(), json!({"a": "1"}))
+                .await
+                .unwrap(),
+            locked
+        );
+        assert_eq!(
+            Events::reset_scores(&db, "y9-boys-100m".to_string(), "{}".to_string())
+                .await
+                .unwrap(),
+            locked
+        );
+        assert_eq!(
+            Events::swap_scores(
+                &db,
+                "y9-boys-100m".to_string(),
+                "a".to_string(),
+                "b".to_string()
+            )
+            .await
+            .unwrap(),
+            SwapScores::Locked
+        );
+        // A locked event in a bulk write or merge stops the whole change
+        assert_eq!(
+            Events::set_many_scores(
+                &db,
+                vec![
+                    ("y9-boys-200m".to_string(), json!({"a": "1"})),
+                    ("y9-boys-100m".to_string(), json!({"a": "1"})),
+                ]
+            )
+            .await
+            .unwrap(),
+            locked
+        );
+        assert_eq!(
+            Events::merge_forms(&db, "b".to_string(), "a".to_string())
+                .await
+                .unwrap(),
+            ScoreWrite::Locked("y9-boys-100m".to_string())
+        );
+
+        for event in Events::all(&db).await.unwrap() {
+            assert_eq!(event.scores, scores);
+        }
+        assert!(Events::score_history(&db, "y9-boys-200m".to_string())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn add_to_form_score_decimal_test() {
+        let db = test_harness::setup_db("events_add_to_form_score_decimal").await;
+        assert!(Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Events::new(
+            "y9-boys-100m".to_string(),
+            "100m".to_string(),
+            "y9".to_string(),
+            "boys".to_string(),
+            "100m".to_string(),
+            r#"{"a":"10.5"}"#.to_string()
+        )
+        .insert(&db)
+        .await
+        .is_ok());
+
+        assert_eq!(
+            Events::add_to_form_score(&db, "y9-boys-100m".to_string(), "a".to_string(), 2.25)
+                .await
+                .unwrap(),
+            ScoreWrite::Written(12.75)
+        );
+        let event = Events::find_by_id(&db, "y9-boys-100m".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.scores, r#"{"a":"12.75"}"#);
+    }
+
+    #[tokio::test]
+    async fn merge_forms_decimal_test() {
+        let db = test_harness::setup_db("events_merge_forms_decimal").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        Events::new(
+            "test-1".to_string(),
+            "Test".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string(),
+            json!({"a": "10.5", "b": 2.5}).to_string(),
+        )
+        .insert(&db)
+        .await
+        .unwrap();
+
+        Events::merge_forms(&db, "b".to_string(), "a".to_string())
+            .await
+            .unwrap();
+        let event = Events::find_by_id(&db, "test-1".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event.parsed_decimal_scores(),
+            HashMap::from([("a".to_string(), 13.0)])
+        );
+    }
+
+    #[tokio::test]
+    async fn merge_forms_test() {
+        let db = test_harness::setup_db("events_merge_forms").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        for (id, scores) in [
+            ("test-1", json!({"a": "5", "b": "3", "c": "1"})),
+            ("test-2", json!({"a": "2", "b": 4})),
+            ("test-3", json!({"a": "1", "c": "2"})),
+        ] {
+            Events::new(
+                id.to_string(),
+                "Test".to_string(),
+                "test".to_string(),
+                "mixed".to_string(),
+                "test".to_string(),
+                scores.to_string(),
+            )
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let merged = Events::merge_forms(&db, "b".to_string(), "a".to_string())
+            .await
+            .unwrap();
+        assert_eq!(merged, ScoreWrite::Written(2));
+
+        let mut events = Events::all(&db).await.unwrap();
+        events.sort_by(|a, b| a.id.cmp(&b.id));
+        let scores: Vec<HashMap<String, f64>> = events
+            .iter()
+            .map(|event| event.parsed_decimal_scores())
+            .collect();
+        assert_eq!(
+            scores[0],
+            HashMap::from([("a".to_string(), 8.0), ("c".to_string(), 1.0)])
+        );
+        assert_eq!(scores[1], HashMap::from([("a".to_string(), 6.0)]));
+        assert_eq!(
+            scores[2],
+            HashMap::from([("a".to_string(), 1.0), ("c".to_string(), 2.0)])
+        );
+
+        let history = Events::score_history(&db, "test-1".to_string())
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(
+            history[0].old_scores,
+            json!({"a": "5", "b": "3", "c": "1"}).to_string()
+        );
+    }
+
+    #[test]
+    fn parsed_decimal_scores_test() {
+        let event = Events::new(
+            "test-test".to_string(),
+            "Test".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string(),
+            json!({"a": "3", "b": 2.5, "c": " 10.25 ", "d": "x", "e": "NaN", "f": ""}).to_string(),
+        );
+        assert_eq!(
+            event.parsed_decimal_scores(),
+            HashMap::from([
+                ("a".to_string(), 3.0),
+                ("b".to_string(), 2.5),
+                ("c".to_string(), 10.25)
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn snapshot_restore_scores_test() {
+        let db = test_harness::setup_db("events_snapshot_restore_scores").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        for (id, scores) in [
+            ("test-1", json!({"a": "5", "b": "3"})),
+            ("test-2", json!({"a": "2", "b": "4"})),
+        ] {
+            Events::new(
+                id.to_string(),
+                "Test".to_string(),
+                "test".to_string(),
+                "mixed".to_string(),
+                "test".to_string(),
+                scores.to_string(),
+            )
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let mut snapshot = Events::snapshot_scores(&db).await.unwrap();
+        assert_eq!(snapshot.len(), 2);
+
+        let empty_scores = json!({"a": 0, "b": 0}).to_string();
+        for id in ["test-1", "test-2"] {
+            Events::reset_scores(&db, id.to_string(), empty_scores.clone())
+                .await
+                .unwrap();
+        }
+
+        snapshot.insert("missing".to_string(), json!({"a": "9"}).to_string());
+        assert_eq!(Events::restore_scores(&db, snapshot).await.unwrap(), 2);
+
+        let mut events = Events::all(&db).await.unwrap();
+        events.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].scores, json!({"a": "5", "b": "3"}).to_string());
+        assert_eq!(events[1].scores, json!({"a": "2", "b": "4"}).to_string());
+    }
+
+    #[tokio::test]
+    async fn distinct_years_test() {
+        let db = test_harness::setup_db("events_distinct_years").await;
+        for year in ["y7", "y8", "y9"] {
+            assert!(Years::new(year.to_string(), year.to_string())
+                .insert(&db)
+                .await
+                .is_ok());
+        }
+        for (id, year) in [
+            ("y9-boys-100m", "y9"),
+            ("y7-boys-100m", "y7"),
+            ("y9-girls-100m", "y9"),
+        ] {
+            assert!(Events::new(
+                id.to_string(),
+                "100m".to_string(),
+                year.to_string(),
+                "boys".to_string(),
+                "100m".to_string(),
+                "{}".to_string()
+            )
+            .insert(&db)
+            .await
+            .is_ok());
+        }
+
+        assert_eq!(Events::distinct_years(&db).await.unwrap(), vec!["y7", "y9"]);
+    }
+
+    #[tokio::test]
+    async fn score_rows_test() {
+        let db = test_harness::setup_db("events_score_rows").await;
+        assert!(Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        for (id, scores) in [
+            ("y9-mixed-100m", r#"{"a":"5","b":"3"}"#),
+            ("y9-mixed-relay", r#"{"a":"1","b":"8","c":"2"}"#),
+            ("y9-mixed-broken", "not json"),
+        ] {
+            assert!(Events::new(
+                id.to_string(),
+                "Test".to_string(),
+                "y9".to_string(),
+                "mixed".to_string(),
+                "test".to_string(),
+                scores.to_string()
+            )
+            .insert(&db)
+            .await
+            .is_ok());
+        }
+
+        let rows = Events::score_rows(&db).await.unwrap();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(
+            rows,
+            vec![
+                ("y9-mixed-100m".to_string(), "a".to_string(), 5.0),
+                ("y9-mixed-100m".to_string(), "b".to_string(), 3.0),
+                ("y9-mixed-relay".to_string(), "a".to_string(), 1.0),
+                ("y9-mixed-relay".to_string(), "b".to_string(), 8.0),
+                ("y9-mixed-relay".to_string(), "c".to_string(), 2.0),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn where_tag_test() {
+        let db = test_harness::setup_db("events_where_tag").await;
+        assert!(Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        for (id, tags) in [
+            ("y9-mixed-100m", vec!["track"]),
+            ("y9-mixed-shotput", vec!["field"]),
+            ("y9-mixed-relay", vec!["track", "fun"]),
+        ] {
+            assert!(Events::new(
+                id.to_string(),
+                "Test".to_string(),
+                "y9".to_string(),
+                "mixed".to_string(),
+                "test".to_string(),
+                "{}".to_string()
+            )
+            .with_tags(tags.into_iter().map(String::from).collect())
+            .insert(&db)
+            .await
+            .is_ok());
+        }
+
+        let mut ids: Vec<String> =
+            Events::r#where(&db, None, None, None, Some("track".to_string()))
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|event| event.id)
+                .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["y9-mixed-100m", "y9-mixed-relay"]);
+
+        let events = Events::r#where(&db, None, None, None, Some("fun".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].tags, vec!["track", "fun"]);
+    }
+
+    #[tokio::test]
+    async fn scores_integrity_test() {
+        use crate::configurator::parser::Form;
+
+        let db = test_harness::setup_db("events_scores_integrity").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        for (id, scores) in [
+            ("test-ok", r#"{"form1":"10","form2":"8"}"#),
+            ("test-missing", r#"{"form1":"10"}"#),
+            ("test-extra", r#"{"form1":"10","form2":"8","form3":"6"}"#),
+        ] {
+            assert!(Events::new(
+                id.to_string(),
+                "Test".to_string(),
+                "test".to_string(),
+                "mixed".to_string(),
+                "test".to_string(),
+                scores.to_string()
+            )
+            .insert(&db)
+            .await
+            .is_ok());
+        }
+
+        let config = Configuration {
+            forms: vec![
+                Form {
+                    id: "form1".to_string(),
+                    name: "Form 1".to_string(),
+                    colour: "#ff0000".to_string(),
+                },
+                Form {
+                    id: "form2".to_string(),
+                    name: "Form 2".to_string(),
+                    colour: "#00ff00".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+
+        let issues = Events::scores_integrity(&db, &config).await.unwrap();
+        assert_eq!(
+            issues,
+            vec![
+                IntegrityIssue {
+                    event_id: "test-missing".to_string(),
+                    extra_forms: vec![],
+                    missing_forms: vec!["form2".to_string()],
+                },
+                IntegrityIssue {
+                    event_id: "test-extra".to_string(),
+                    extra_forms: vec!["form3".to_string()],
+                    missing_forms: vec![],
+                },
+            ]
+        );
+    }
+
     // E2E tests
     #[actix_web::test]
     async fn test_e2e_event_filtering() {
@@ -557,6 +2279,7 @@ mod tests {
                     name: "100m Sprint".to_string(),
                     applicable_years: crate::configurator::parser::ApplicabilityRules::All,
                     applicable_genders: crate::configurator::parser::ApplicabilityRules::All,
+                    tags: vec![],
                 },
                 crate::configurator::parser::Event {
                     id: "relay".to_string(),
@@ -565,37 +2288,39 @@ mod tests {
                         ids: vec!["year8".to_string()],
                     },
                     applicable_genders: crate::configurator::parser::ApplicabilityRules::All,
+                    tags: vec![],
                 },
             ],
+            ..Default::default()
         };
 
-        let plan = crate::configurator::build::build_plan(config.clone());
+        let plan = crate::configurator::build::build_plan(config.clone()).unwrap();
         crate::configurator::run::run(plan, &pool).await.unwrap();
 
         // Test filtering by year
-        let year7_events = Events::r#where(&pool, Some("year7".to_string()), None, None)
+        let year7_events = Events::r#where(&pool, Some("year7".to_string()), None, None, None)
             .await
             .unwrap();
         assert_eq!(year7_events.len(), 3); // Only sprint events
 
-        let year8_events = Events::r#where(&pool, Some("year8".to_string()), None, None)
+        let year8_events = Events::r#where(&pool, Some("year8".to_string()), None, None, None)
             .await
             .unwrap();
         assert_eq!(year8_events.len(), 6); // Sprint + relay events
 
         // Test filtering by gender
-        let boys_events = Events::r#where(&pool, None, None, Some("boys".to_string()))
+        let boys_events = Events::r#where(&pool, None, None, Some("boys".to_string()), None)
             .await
             .unwrap();
         assert_eq!(boys_events.len(), 3); // boys events across all years
 
         // Test filtering by activity
-        let sprint_events = Events::r#where(&pool, None, Some("sprint".to_string()), None)
+        let sprint_events = Events::r#where(&pool, None, Some("sprint".to_string()), None, None)
             .await
             .unwrap();
         assert_eq!(sprint_events.len(), 6); // All sprint events
 
-        let relay_events = Events::r#where(&pool, None, Some("relay".to_string()), None)
+        let relay_events = Events::r#where(&pool, None, Some("relay".to_string()), None, None)
             .await
             .unwrap();
         assert_eq!(relay_events.len(), 3); // Only year8 relay events
@@ -642,10 +2367,12 @@ mod tests {
                 name: "Sprint".to_string(),
                 applicable_years: crate::configurator::parser::ApplicabilityRules::All,
                 applicable_genders: crate::configurator::parser::ApplicabilityRules::All,
+                tags: vec![],
             }],
+            ..Default::default()
         };
 
-        let plan = crate::configurator::build::build_plan(config.clone());
+        let plan = crate::configurator::build::build_plan(config.clone()).unwrap();
         crate::configurator::run::run(plan, &pool).await.unwrap();
 
         // Get an event
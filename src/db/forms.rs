@@ -0,0 +1,224 @@
+use async_sqlite::{rusqlite::Row, Pool};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::db::{events::Events, timed_conn};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Forms {
+    pub id: String,
+    pub name: String,
+    pub colour: String,
+}
+
+/// Why a form has no points
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UnusedReason {
+    /// Nothing has been entered for the form, it only has the zeros events start with
+    NeverScored,
+    /// Scores were entered for the form but they're all zero
+    ScoredZero,
+}
+
+/// A form whose total across every event is zero
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UnusedForm {
+    pub id: String,
+    pub name: String,
+    pub reason: UnusedReason,
+}
+
+impl Forms {
+    pub fn new(id: String, name: String, colour: String) -> Self {
+        Self { id, name, colour }
+    }
+
+    fn map_from_row(row: &Row) -> Result<Self, async_sqlite::rusqlite::Error> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            colour: row.get(2)?,
+        })
+    }
+
+    pub async fn insert(self, pool: &Pool) -> Result<Self, async_sqlite::Error> {
+        let form = self.clone();
+        timed_conn(pool, move |conn| {
+            conn.execute(
+                "INSERT INTO forms(id, name, colour) VALUES (?1, ?2, ?3);",
+                [form.id, form.name, form.colour],
+            )?;
+            Ok(())
+        })
+        .await?;
+        Ok(self)
+    }
+
+    /// Every form, in the order they were inserted
+    pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare("SELECT id, name, colour FROM forms ORDER BY rowid")?;
+            let form_iter = stmt.query_map([], Self::map_from_row)?;
+            let mut forms = Vec::new();
+
+            for form in form_iter {
+                forms.push(form?);
+            }
+            Ok(forms)
+        })
+        .await
+    }
+
+    /// Forms with a total of zero across all events, to check before printing certificates.
+    ///
+    /// New and reset events store the number 0 for every form while entered scores are strings,
+    /// so a form that only has numeric zeros (or no entry at all) has never been scored.
+    pub async fn unused(pool: &Pool) -> Result<Vec<UnusedForm>, async_sqlite::Error> {
+        let forms = Self::all(pool).await?;
+        let events = Events::all(pool).await?;
+        let scores: Vec<serde_json::Map<String, Value>> = events
+            .iter()
+            .filter_map(|event| serde_json::from_str(&event.scores).ok())
+            .collect();
+        let totals: Vec<_> = events
+            .iter()
+            .map(|event| event.parsed_decimal_scores())
+            .collect();
+
+        Ok(forms
+            .into_iter()
+            .filter(|form| {
+                totals
+                    .iter()
+                    .filter_map(|scores| scores.get(&form.id))
+                    .sum::<f64>()
+                    == 0.0
+            })
+            .map(|form| {
+                let entered = scores.iter().any(|scores| {
+                    matches!(scores.get(&form.id), Some(Value::String(score)) if !score.trim().is_empty())
+                });
+                UnusedForm {
+                    reason: if entered {
+                        UnusedReason::ScoredZero
+                    } else {
+                        UnusedReason::NeverScored
+                    },
+                    id: form.id,
+                    name: form.name,
+                }
+            })
+            .collect())
+    }
+
+    pub async fn delete_all(pool: &Pool) -> Result<(), async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            conn.execute("DELETE FROM forms;", [])?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{db::years::Years, test_harness};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn insert_test() {
+        let db = test_harness::setup_db("forms_insert").await;
+        assert!(
+            Forms::new("f1".to_string(), "Form 1".to_string(), "red".to_string())
+                .insert(&db)
+                .await
+                .is_ok()
+        );
+        assert!(
+            Forms::new("f1".to_string(), "Form 1".to_string(), "red".to_string())
+                .insert(&db)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn all_test() {
+        let db = test_harness::setup_db("forms_all").await;
+        let forms = vec![
+            Forms::new(
+                "f2".to_string(),
+                "Form 2".to_string(),
+                "#fdfd80".to_string(),
+            ),
+            Forms::new("f1".to_string(), "Form 1".to_string(), "red".to_string()),
+        ];
+        for form in forms.iter() {
+            form.clone().insert(&db).await.unwrap();
+        }
+        assert_eq!(Forms::all(&db).await.unwrap(), forms);
+
+        Forms::delete_all(&db).await.unwrap();
+        assert!(Forms::all(&db).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unused_test() {
+        let db = test_harness::setup_db("forms_unused").await;
+        for (id, name) in [
+            ("a", "Form A"),
+            ("b", "Form B"),
+            ("c", "Form C"),
+            ("d", "Form D"),
+        ] {
+            Forms::new(id.to_string(), name.to_string(), "red".to_string())
+                .insert(&db)
+                .await
+                .unwrap();
+        }
+        Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .unwrap();
+        // a scores, b is entered as 0, c only has the starting zeros, d isn't in any event
+        for (id, scores) in [
+            ("test-1", r#"{"a":"5","b":"0","c":0}"#),
+            ("test-2", r#"{"a":0,"b":"","c":0}"#),
+        ] {
+            Events::new(
+                id.to_string(),
+                "Test".to_string(),
+                "test".to_string(),
+                "mixed".to_string(),
+                "test".to_string(),
+                scores.to_string(),
+            )
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(
+            Forms::unused(&db).await.unwrap(),
+            vec![
+                UnusedForm {
+                    id: "b".to_string(),
+                    name: "Form B".to_string(),
+                    reason: UnusedReason::ScoredZero,
+                },
+                UnusedForm {
+                    id: "c".to_string(),
+                    name: "Form C".to_string(),
+                    reason: UnusedReason::NeverScored,
+                },
+                UnusedForm {
+                    id: "d".to_string(),
+                    name: "Form D".to_string(),
+                    reason: UnusedReason::NeverScored,
+                },
+            ]
+        );
+    }
+}
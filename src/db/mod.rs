@@ -1,12 +1,87 @@
-use async_sqlite::Pool;
+use async_sqlite::{
+    rusqlite::{self, Connection},
+    Pool, PoolBuilder,
+};
+use prometheus::IntGauge;
+use std::sync::LazyLock;
+use std::time::Instant;
 
+pub mod archived_events;
 pub mod events;
+pub mod forms;
+pub mod repair;
 pub mod user_sessions;
 pub mod users;
 pub mod years;
 
+/// DB operations currently queued for or holding a pooled connection
+pub static DB_IN_FLIGHT: LazyLock<IntGauge> = LazyLock::new(|| {
+    IntGauge::new(
+        "db_in_flight_operations",
+        "Database operations waiting for or using a pooled connection",
+    )
+    .unwrap()
+});
+
+/// Number of connections in the pool opened by `open_pool`
+pub static DB_POOL_SIZE: LazyLock<IntGauge> = LazyLock::new(|| {
+    IntGauge::new("db_pool_size", "Number of connections in the database pool").unwrap()
+});
+
+/// Open the database pool, defaulting to one connection per CPU when `num_conns` is unset
+pub async fn open_pool(path: &str, num_conns: Option<usize>) -> Result<Pool, async_sqlite::Error> {
+    let num_conns = num_conns
+        .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1)
+        .max(1);
+    let pool = PoolBuilder::new()
+        .path(path)
+        .num_conns(num_conns)
+        .open()
+        .await?;
+    DB_POOL_SIZE.set(num_conns as i64);
+    Ok(pool)
+}
+
+// Keeps DB_IN_FLIGHT accurate even if the caller's future is dropped mid-query
+struct InFlight(Instant);
+
+impl InFlight {
+    fn start() -> Self {
+        DB_IN_FLIGHT.inc();
+        Self(Instant::now())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        DB_IN_FLIGHT.dec();
+        log::trace!("DB operation took {:?}", self.0.elapsed());
+    }
+}
+
+/// `pool.conn` counted towards the in-flight gauge
+pub async fn timed_conn<F, T>(pool: &Pool, func: F) -> Result<T, async_sqlite::Error>
+where
+    F: FnOnce(&Connection) -> Result<T, rusqlite::Error> + Send + 'static,
+    T: Send + 'static,
+{
+    let _in_flight = InFlight::start();
+    pool.conn(func).await
+}
+
+/// `pool.conn_mut` counted towards the in-flight gauge
+pub async fn timed_conn_mut<F, T>(pool: &Pool, func: F) -> Result<T, async_sqlite::Error>
+where
+    F: FnOnce(&mut Connection) -> Result<T, rusqlite::Error> + Send + 'static,
+    T: Send + 'static,
+{
+    let _in_flight = InFlight::start();
+    pool.conn_mut(func).await
+}
+
 pub async fn create_tables(pool: &Pool) -> Result<(), async_sqlite::Error> {
-    pool.conn(move |conn| {
+    timed_conn(pool, move |conn| {
         conn.execute("PRAGMA foreign_keys = ON", []).unwrap();
 
         conn.execute(
@@ -26,11 +101,43 @@ pub async fn create_tables(pool: &Pool) -> Result<(), async_sqlite::Error> {
                 gender_id TEXT NOT NULL,
                 filter_key TEXT NOT NULL,
                 scores TEXT NOT NULL DEFAULT '{}',
+                updated_at TEXT,
+                tags TEXT NOT NULL DEFAULT '[]',
+                manual INTEGER NOT NULL DEFAULT 0,
+                locked INTEGER NOT NULL DEFAULT 0,
                 FOREIGN KEY (year_id) REFERENCES years(id)
             );",
             [],
         )
         .unwrap();
+        add_column_if_missing(conn, "events", "updated_at", "TEXT")?;
+        add_column_if_missing(conn, "events", "tags", "TEXT NOT NULL DEFAULT '[]'")?;
+        add_column_if_missing(conn, "events", "manual", "INTEGER NOT NULL DEFAULT 0")?;
+        add_column_if_missing(conn, "events", "locked", "INTEGER NOT NULL DEFAULT 0")?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS forms (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                colour TEXT NOT NULL DEFAULT ''
+            );",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS archived_events (
+                archive_id INTEGER PRIMARY KEY,
+                archived_at TEXT NOT NULL,
+                id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                year_id TEXT NOT NULL,
+                gender_id TEXT NOT NULL,
+                filter_key TEXT NOT NULL,
+                scores TEXT NOT NULL
+            );",
+            [],
+        )
+        .unwrap();
 
         conn.execute(
             "CREATE TABLE IF NOT EXISTS users (
@@ -49,13 +156,97 @@ pub async fn create_tables(pool: &Pool) -> Result<(), async_sqlite::Error> {
                     user_id INTEGER NOT NULL,
                     has_admin INTEGER NOT NULL DEFAULT 0,
                     has_set_score INTEGER NOT NULL DEFAULT 0,
+                    created_at TEXT,
                     FOREIGN KEY (user_id) REFERENCES users(id)
             );",
             [],
         )
         .unwrap();
+        add_column_if_missing(conn, "user_sessions", "created_at", "TEXT")?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS score_history (
+                id INTEGER PRIMARY KEY,
+                event_id TEXT NOT NULL,
+                old_scores TEXT NOT NULL,
+                new_scores TEXT NOT NULL,
+                changed_at TEXT NOT NULL
+            );",
+            [],
+        )?;
+
+        // When anything the scoreboard is built from last changed, for `Last-Modified`.
+        // `updated_at` alone misses deletes, lock changes and rebuilds.
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS modified (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                at TEXT NOT NULL
+            );",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO modified(id, at)
+                SELECT 1, latest FROM (SELECT MAX(updated_at) AS latest FROM events)
+                WHERE latest IS NOT NULL;",
+            [],
+        )?;
+        for table in ["events", "years", "forms"] {
+            for action in ["insert", "update", "delete"] {
+                conn.execute(
+                    &format!(
+                        "CREATE TRIGGER IF NOT EXISTS {table}_{action}_modified AFTER {action} ON {table}
+                            BEGIN {mark}; END;",
+                        mark = events::SQL_MARK_MODIFIED,
+                    ),
+                    [],
+                )?;
+            }
+        }
         Ok(())
     })
     .await?;
     Ok(())
 }
+
+/// Bring tables created by older versions up to date with columns added since
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<(), async_sqlite::rusqlite::Error> {
+    let exists = conn
+        .prepare(&format!(
+            "SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1",
+            table
+        ))?
+        .exists([column])?;
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_harness;
+
+    #[tokio::test]
+    async fn add_column_if_missing_test() {
+        let db = test_harness::setup_db("db_add_column_if_missing").await;
+        db.conn(|conn| {
+            conn.execute("CREATE TABLE legacy (id TEXT PRIMARY KEY);", [])?;
+            add_column_if_missing(conn, "legacy", "extra", "TEXT")?;
+            // Running again is a no-op rather than a duplicate column error
+            add_column_if_missing(conn, "legacy", "extra", "TEXT")?;
+            conn.execute("INSERT INTO legacy (id, extra) VALUES ('a', 'b');", [])?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+
+use async_sqlite::{rusqlite::TransactionBehavior, Pool};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::db::{
+    events::{record_history, SQL_NOW},
+    timed_conn_mut,
+};
+
+/// What `repair` fixed, or would fix on a dry run
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct RepairReport {
+    pub dry_run: bool,
+    /// Events whose year no longer exists, deleted
+    pub orphan_events: Vec<String>,
+    /// Events whose scores weren't a JSON object, reset to zero for every form
+    pub corrupt_scores: Vec<String>,
+    /// Event id to the form ids removed from its scores because they aren't configured
+    pub unknown_form_keys: BTreeMap<String, Vec<String>>,
+    /// Locked events whose scores need one of the fixes above, left unchanged until unlocked
+    pub locked_events: Vec<String>,
+    /// Sessions belonging to users that no longer exist, deleted
+    pub orphan_sessions: usize,
+}
+
+impl RepairReport {
+    /// Whether nothing was changed, locked events that were skipped aside
+    pub fn is_empty(&self) -> bool {
+        self.orphan_events.is_empty()
+            && self.corrupt_scores.is_empty()
+            && self.unknown_form_keys.is_empty()
+            && self.orphan_sessions == 0
+    }
+}
+
+/// Fix everything the diagnostics endpoints report in one transaction. A dry run makes the same
+/// changes and then rolls them back, so the report matches what a real run would do.
+pub async fn repair(
+    pool: &Pool,
+    form_ids: Vec<String>,
+    empty_scores: String,
+    dry_run: bool,
+) -> Result<RepairReport, async_sqlite::Error> {
+    timed_conn_mut(pool, move |conn| {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut report = RepairReport {
+            dry_run,
+            ..Default::default()
+        };
+
+        let mut stmt = tx.prepare(
+            "SELECT id FROM events WHERE year_id NOT IN (SELECT id FROM years) ORDER BY id",
+        )?;
+        report.orphan_events = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        drop(stmt);
+        tx.execute(
+            "DELETE FROM events WHERE year_id NOT IN (SELECT id FROM years);",
+            [],
+        )?;
+
+        let mut stmt = tx.prepare("SELECT id, scores, locked FROM events ORDER BY id")?;
+        let events: Vec<(String, String, bool)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<Result<_, _>>()?;
+        drop(stmt);
+        for (id, old_scores, locked) in events {
+            let new_scores = match serde_json::from_str::<Map<String, Value>>(&old_scores) {
+                Err(_) if locked => {
+                    report.locked_events.push(id);
+                    continue;
+                }
+                Err(_) => {
+                    report.corrupt_scores.push(id.clone());
+                    empty_scores.clone()
+                }
+                Ok(mut scores) => {
+                    let unknown: Vec<String> = scores
+                        .keys()
+                        .filter(|key| !form_ids.contains(key))
+                        .cloned()
+                        .collect();
+                    if unknown.is_empty() {
+                        continue;
+                    }
+                    if locked {
+                        report.locked_events.push(id);
+                        continue;
+                    }
+                    for key in unknown.iter() {
+                        scores.remove(key);
+                    }
+                    report.unknown_form_keys.insert(id.clone(), unknown);
+                    Value::Object(scores).to_string()
+                }
+            };
+
+            tx.execute(
+                &format!(
+                    "UPDATE events SET scores = ?1, updated_at = {} WHERE id = ?2;",
+                    SQL_NOW
+                ),
+                [&new_scores, &id],
+            )?;
+            record_history(&tx, &id, &old_scores, &new_scores)?;
+        }
+
+        report.orphan_sessions = tx.execute(
+            "DELETE FROM user_sessions WHERE user_id NOT IN (SELECT id FROM users);",
+            [],
+        )?;
+
+        if dry_run {
+            tx.rollback()?;
+        } else {
+            tx.commit()?;
+        }
+        Ok(report)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{events::Events, timed_conn, user_sessions::UserSessions, users::Users, years::Years},
+        test_harness,
+    };
+
+    async fn seed(pool: &Pool) {
+        Years::new("y7".to_string(), "Year 7".to_string())
+            .insert(pool)
+            .await
+            .unwrap();
+        let user = Users::get_or_create("a@example.com".to_string(), pool)
+            .await
+            .unwrap();
+        user.new_session().insert(pool).await.unwrap();
+
+        for (id, scores) in [
+            ("y7-mixed-100m", r#"{"f1": "3", "f2": "1"}"#),
+            ("y7-mixed-relay", r#"{"f1": "3", "old": "5"}"#),
+            ("y7-mixed-shot", "not json"),
+            ("y7-mixed-javelin", "not json"),
+        ] {
+            Events::new(
+                id.to_string(),
+                id.to_string(),
+                "y7".to_string(),
+                "mixed".to_string(),
+                id.to_string(),
+                scores.to_string(),
+            )
+            .insert(pool)
+            .await
+            .unwrap();
+        }
+
+        // Results are final, so repair has to leave them for an admin to unlock
+        Events::set_locked(pool, "y7-mixed-javelin".to_string(), true)
+            .await
+            .unwrap();
+
+        // Rows a manual edit could leave behind, which foreign keys would otherwise stop
+        timed_conn(pool, |conn| {
+            conn.execute("PRAGMA foreign_keys = OFF", [])?;
+            conn.execute(
+                "INSERT INTO events(id, name, year_id, gender_id, filter_key, scores) VALUES ('y13-mixed-100m', '100m', 'y13', 'mixed', '100m', '{}');",
+                [],
+            )?;
+            conn.execute(
+                "INSERT INTO user_sessions(id, user_id) VALUES ('stale', 999);",
+                [],
+            )?;
+            conn.execute("PRAGMA foreign_keys = ON", [])?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn repair_test() {
+        let pool = test_harness::setup_db("repair_test").await;
+        seed(&pool).await;
+        let form_ids = vec!["f1".to_string(), "f2".to_string()];
+        let empty_scores = r#"{"f1":0,"f2":0}"#.to_string();
+
+        let expected = RepairReport {
+            dry_run: true,
+            orphan_events: vec!["y13-mixed-100m".to_string()],
+            corrupt_scores: vec!["y7-mixed-shot".to_string()],
+            unknown_form_keys: BTreeMap::from([(
+                "y7-mixed-relay".to_string(),
+                vec!["old".to_string()],
+            )]),
+            locked_events: vec!["y7-mixed-javelin".to_string()],
+            orphan_sessions: 1,
+        };
+
+        // A dry run reports without changing anything
+        let report = repair(&pool, form_ids.clone(), empty_scores.clone(), true)
+            .await
+            .unwrap();
+        assert_eq!(report, expected);
+        assert_eq!(Events::count(&pool).await.unwrap(), 5);
+        assert_eq!(UserSessions::count(&pool).await.unwrap(), 2);
+
+        let report = repair(&pool, form_ids.clone(), empty_scores.clone(), false)
+            .await
+            .unwrap();
+        assert_eq!(
+            report,
+            RepairReport {
+                dry_run: false,
+                ..expected
+            }
+        );
+        assert_eq!(Events::count(&pool).await.unwrap(), 4);
+        assert_eq!(UserSessions::count(&pool).await.unwrap(), 1);
+        let relay = Events::find_by_id(&pool, "y7-mixed-relay".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(relay.scores, r#"{"f1":"3"}"#);
+        let shot = Events::find_by_id(&pool, "y7-mixed-shot".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(shot.scores, empty_scores);
+        let javelin = Events::find_by_id(&pool, "y7-mixed-javelin".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(javelin.scores, "not json");
+
+        // Nothing left to do but the locked event
+        let report = repair(&pool, form_ids, empty_scores, false).await.unwrap();
+        assert!(report.is_empty());
+        assert_eq!(report.locked_events, vec!["y7-mixed-javelin"]);
+    }
+}
@@ -1,14 +1,23 @@
-use async_sqlite::rusqlite::{Error as RusqliteError, OptionalExtension};
+use async_sqlite::rusqlite::{ffi, Error as RusqliteError, OptionalExtension};
 use async_sqlite::{rusqlite::Row, Pool};
+use chrono::{DateTime, Duration, Utc};
 
+use crate::db::{events::format_timestamp, timed_conn};
 use crate::ternary;
 
+fn is_primary_key_conflict(e: &RusqliteError) -> bool {
+    e.sqlite_error()
+        .is_some_and(|e| e.extended_code == ffi::SQLITE_CONSTRAINT_PRIMARYKEY)
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct UserSessions {
     pub id: String,
     pub user_id: i64,
     pub has_admin: bool,
     pub has_set_score: bool,
+    /// When the session was created, `None` for sessions from before this was recorded
+    pub created_at: Option<DateTime<Utc>>,
 }
 
 impl UserSessions {
@@ -19,6 +28,7 @@ impl UserSessions {
             user_id,
             has_admin,
             has_set_score,
+            created_at: Some(Utc::now()),
         }
     }
     fn map_from_row(row: &Row) -> Result<Self, RusqliteError> {
@@ -27,46 +37,185 @@ impl UserSessions {
             user_id: row.get(1)?,
             has_admin: ternary!(row.get(2)? => true, false),
             has_set_score: ternary!(row.get(3)? => true, false),
+            created_at: row
+                .get::<_, Option<String>>(4)?
+                .and_then(|timestamp| DateTime::parse_from_rfc3339(&timestamp).ok())
+                .map(|timestamp| timestamp.with_timezone(&Utc)),
         })
     }
 
-    pub async fn insert(self, pool: &Pool) -> Result<(), async_sqlite::Error> {
-        pool.conn(move |conn| {
-            conn.execute("INSERT INTO user_sessions(id, user_id, has_admin, has_set_score) VALUES (?1, ?2, ?3, ?4);", [self.id, self.user_id.to_string(), ternary!(self.has_admin => 1, 0).to_string(), ternary!(self.has_set_score => 1, 0).to_string()])
-                .unwrap();
+    /// The same session under a freshly generated id
+    pub fn with_new_id(self) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            ..self
+        }
+    }
+
+    /// Store the session, returning it as stored. If the id is already taken the session is
+    /// given a new id and inserted once more, so use the returned id for the cookie.
+    pub async fn insert(self, pool: &Pool) -> Result<Self, async_sqlite::Error> {
+        match self.clone().insert_row(pool).await {
+            Err(async_sqlite::Error::Rusqlite(e)) if is_primary_key_conflict(&e) => {
+                let session = self.with_new_id();
+                log::warn!("Session id already in use, retrying as {}", session.id);
+                session.clone().insert_row(pool).await?;
+                Ok(session)
+            }
+            Err(e) => Err(e),
+            Ok(()) => Ok(self),
+        }
+    }
+
+    async fn insert_row(self, pool: &Pool) -> Result<(), async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            conn.execute("INSERT INTO user_sessions(id, user_id, has_admin, has_set_score, created_at) VALUES (?1, ?2, ?3, ?4, ?5);", [Some(self.id), Some(self.user_id.to_string()), Some(ternary!(self.has_admin => 1, 0).to_string()), Some(ternary!(self.has_set_score => 1, 0).to_string()), self.created_at.map(format_timestamp)])?;
             Ok(())
         })
-        .await?;
-        Ok(())
+        .await
+    }
+
+    pub async fn count(pool: &Pool) -> Result<i64, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let count: i64 =
+                conn.query_row("SELECT COUNT(*) FROM user_sessions", [], |row| row.get(0))?;
+            Ok(count)
+        })
+        .await
     }
 
+    /// Remove a session, logging it out
+    pub async fn delete(pool: &Pool, id: String) -> Result<(), async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            conn.execute("DELETE FROM user_sessions WHERE id = ?1;", [id])?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Remove every session a user holds, returning how many were removed
+    pub async fn delete_all_for_user(
+        pool: &Pool,
+        user_id: i64,
+    ) -> Result<usize, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let deleted =
+                conn.execute("DELETE FROM user_sessions WHERE user_id = ?1;", [user_id])?;
+            Ok(deleted)
+        })
+        .await
+    }
+
+    /// Delete every session older than `ttl`, along with those of unknown age, returning how many
+    /// were removed. `verify_with_ttl` only removes the expired sessions it is asked about.
+    pub async fn purge_expired(pool: &Pool, ttl: Duration) -> Result<usize, async_sqlite::Error> {
+        let expired_before = format_timestamp(Utc::now() - ttl);
+        timed_conn(pool, move |conn| {
+            let purged = conn.execute(
+                "DELETE FROM user_sessions WHERE created_at IS NULL OR created_at < ?1;",
+                [expired_before],
+            )?;
+            Ok(purged)
+        })
+        .await
+    }
+
+    /// Sessions that will pass `ttl` within the next `within`. Empty when sessions don't expire.
+    pub async fn expiring_within(
+        pool: &Pool,
+        ttl: Option<Duration>,
+        within: Duration,
+    ) -> Result<Vec<Self>, async_sqlite::Error> {
+        let Some(ttl) = ttl else {
+            return Ok(vec![]);
+        };
+        // Created after this has not expired yet, created at or before this expires in the window
+        let now = Utc::now();
+        let expired_before = format_timestamp(now - ttl);
+        let expiring_before = format_timestamp(now - ttl + within);
+
+        timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare(
+                "SELECT * FROM user_sessions WHERE created_at > ?1 AND created_at <= ?2 ORDER BY created_at",
+            )?;
+            let session_iter =
+                stmt.query_map([expired_before, expiring_before], Self::map_from_row)?;
+            let mut sessions = Vec::new();
+
+            for session in session_iter {
+                sessions.push(session?);
+            }
+            Ok(sessions)
+        })
+        .await
+    }
+
+    /// Whether `expiring_within` would return this session at `now`
+    pub fn is_expiring_within(&self, ttl: Duration, within: Duration, now: DateTime<Utc>) -> bool {
+        self.created_at
+            .is_some_and(|created_at| created_at > now - ttl && created_at <= now - ttl + within)
+    }
+
+    /// Whether the session is older than `ttl`. Sessions without a `created_at` are treated as
+    /// expired, as their age is unknown.
+    pub fn is_expired(&self, ttl: Duration, now: DateTime<Utc>) -> bool {
+        match self.created_at {
+            Some(created_at) => now - created_at > ttl,
+            None => true,
+        }
+    }
+
+    /// When the session runs out under `ttl`, `None` if its age is unknown
+    pub fn expires_at(&self, ttl: Duration) -> Option<DateTime<Utc>> {
+        self.created_at.map(|created_at| created_at + ttl)
+    }
+
+    /// Check a session cookie, sessions never expire
     pub async fn verify(
         pool: &Pool,
         cookie_session: String,
     ) -> Result<VerifiedSession, async_sqlite::Error> {
-        pool.conn(move |conn| {
+        Self::verify_inner(pool, cookie_session, None).await
+    }
+
+    /// Check a session cookie, rejecting and deleting sessions older than `ttl`
+    pub async fn verify_with_ttl(
+        pool: &Pool,
+        cookie_session: String,
+        ttl: Duration,
+    ) -> Result<VerifiedSession, async_sqlite::Error> {
+        Self::verify_inner(pool, cookie_session, Some(ttl)).await
+    }
+
+    async fn verify_inner(
+        pool: &Pool,
+        cookie_session: String,
+        ttl: Option<Duration>,
+    ) -> Result<VerifiedSession, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
             let mut stmt = conn.prepare("SELECT * FROM user_sessions WHERE id = ?1")?;
             let session = stmt
                 .query_one([cookie_session.clone()], |row| Self::map_from_row(row))
                 .optional()?;
             match session {
+                Some(session) if ttl.is_some_and(|ttl| session.is_expired(ttl, Utc::now())) => {
+                    log::debug!("Session {} has expired, removing it", session.id);
+                    conn.execute("DELETE FROM user_sessions WHERE id = ?1;", [&session.id])?;
+                    Ok(VerifiedSession::unverified(cookie_session))
+                }
                 Some(session) => {
                     log::debug!("DB Session ID: {} (cookie: {cookie_session})", session.id);
-                    return Ok(VerifiedSession {
+                    Ok(VerifiedSession {
                         _id: cookie_session,
                         verified: true,
+                        user_id: Some(session.user_id),
                         has_admin: session.has_admin,
                         has_set_score: session.has_set_score,
-                    });
+                    })
                 }
                 None => {
                     log::debug!("No Session found in db");
-                    return Ok(VerifiedSession {
-                        _id: cookie_session,
-                        verified: false,
-                        has_admin: false,
-                        has_set_score: false,
-                    });
+                    Ok(VerifiedSession::unverified(cookie_session))
                 }
             }
         })
@@ -77,10 +226,24 @@ impl UserSessions {
 pub struct VerifiedSession {
     pub _id: String,
     pub verified: bool,
+    /// Who the session belongs to, `None` when it didn't verify
+    pub user_id: Option<i64>,
     pub has_admin: bool,
     pub has_set_score: bool,
 }
 
+impl VerifiedSession {
+    pub fn unverified(id: String) -> Self {
+        Self {
+            _id: id,
+            verified: false,
+            user_id: None,
+            has_admin: false,
+            has_set_score: false,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{db::users::Users, test_harness};
@@ -125,4 +288,178 @@ mod tests {
         let verified = verified_session.unwrap();
         assert_eq!(verified.verified, false);
     }
+
+    #[tokio::test]
+    async fn verify_with_ttl_test() {
+        let db = test_harness::setup_db("user_sessions_verify_with_ttl").await;
+        assert!(Users::new("example@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        let mut stale = UserSessions::new(1, true, true);
+        stale.created_at = Some(Utc::now() - Duration::hours(2));
+        assert!(stale.clone().insert(&db).await.is_ok());
+        let fresh = UserSessions::new(1, true, true);
+        assert!(fresh.clone().insert(&db).await.is_ok());
+
+        // Without a TTL the stale session still verifies
+        assert!(
+            UserSessions::verify(&db, stale.id.clone())
+                .await
+                .unwrap()
+                .verified
+        );
+
+        let verified = UserSessions::verify_with_ttl(&db, stale.id.clone(), Duration::hours(1))
+            .await
+            .unwrap();
+        assert!(!verified.verified);
+        assert!(!verified.has_admin);
+        let verified = UserSessions::verify_with_ttl(&db, fresh.id.clone(), Duration::hours(1))
+            .await
+            .unwrap();
+        assert!(verified.verified);
+
+        // The expired row is removed
+        assert!(
+            !UserSessions::verify(&db, stale.id.clone())
+                .await
+                .unwrap()
+                .verified
+        );
+    }
+
+    #[tokio::test]
+    async fn insert_duplicate_id_test() {
+        let db = test_harness::setup_db("user_sessions_insert_duplicate_id").await;
+        assert!(Users::new("example@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        let first = UserSessions::new(1, true, true);
+        assert_eq!(first.clone().insert(&db).await.unwrap(), first);
+
+        let mut forged = UserSessions::new(1, false, false);
+        forged.id = first.id.clone();
+        let stored = forged.insert(&db).await.unwrap();
+        assert_ne!(stored.id, first.id);
+        assert_eq!(UserSessions::count(&db).await.unwrap(), 2);
+
+        // The original session keeps its permissions
+        let verified = UserSessions::verify(&db, first.id).await.unwrap();
+        assert!(verified.verified && verified.has_admin);
+        let verified = UserSessions::verify(&db, stored.id).await.unwrap();
+        assert!(verified.verified && !verified.has_admin);
+    }
+
+    #[tokio::test]
+    async fn count_test() {
+        let db = test_harness::setup_db("user_sessions_count").await;
+        assert_eq!(UserSessions::count(&db).await.unwrap(), 0);
+
+        assert!(Users::new("example@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(UserSessions::new(1, true, true).insert(&db).await.is_ok());
+        assert!(UserSessions::new(1, true, true).insert(&db).await.is_ok());
+        assert_eq!(UserSessions::count(&db).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn delete_test() {
+        let db = test_harness::setup_db("user_sessions_delete").await;
+        assert!(Users::new("example@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        let session = UserSessions::new(1, true, true);
+        assert!(session.clone().insert(&db).await.is_ok());
+
+        assert!(UserSessions::delete(&db, session.id.clone()).await.is_ok());
+        let verified = UserSessions::verify(&db, session.id).await.unwrap();
+        assert!(!verified.verified);
+    }
+
+    #[tokio::test]
+    async fn delete_all_for_user_test() {
+        let db = test_harness::setup_db("user_sessions_delete_all_for_user").await;
+        for email in ["one@example.com", "two@example.com"] {
+            assert!(Users::new(email.to_string(), true, true)
+                .insert(&db)
+                .await
+                .is_ok());
+        }
+        let first = UserSessions::new(1, true, true);
+        let second = UserSessions::new(1, true, true);
+        let other = UserSessions::new(2, true, true);
+        for session in [&first, &second, &other] {
+            assert!(session.clone().insert(&db).await.is_ok());
+        }
+
+        assert_eq!(UserSessions::delete_all_for_user(&db, 1).await.unwrap(), 2);
+        for session in [first, second] {
+            assert!(
+                !UserSessions::verify(&db, session.id)
+                    .await
+                    .unwrap()
+                    .verified
+            );
+        }
+        assert!(UserSessions::verify(&db, other.id).await.unwrap().verified);
+    }
+
+    #[tokio::test]
+    async fn expiring_within_test() {
+        let db = test_harness::setup_db("user_sessions_expiring_within").await;
+        assert!(Users::new("example@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        let mut old = UserSessions::new(1, true, true);
+        old.created_at = Some(Utc::now() - Duration::minutes(55));
+        assert!(old.clone().insert(&db).await.is_ok());
+        let fresh = UserSessions::new(1, true, true);
+        assert!(fresh.clone().insert(&db).await.is_ok());
+
+        let expiring =
+            UserSessions::expiring_within(&db, Some(Duration::hours(1)), Duration::minutes(10))
+                .await
+                .unwrap();
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].id, old.id);
+        let now = Utc::now();
+        assert!(old.is_expiring_within(Duration::hours(1), Duration::minutes(10), now));
+        assert!(!fresh.is_expiring_within(Duration::hours(1), Duration::minutes(10), now));
+
+        // Sessions never expire without a TTL
+        let expiring = UserSessions::expiring_within(&db, None, Duration::minutes(10))
+            .await
+            .unwrap();
+        assert!(expiring.is_empty());
+    }
+
+    #[tokio::test]
+    async fn purge_expired_test() {
+        let db = test_harness::setup_db("user_sessions_purge_expired").await;
+        assert!(Users::new("example@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        let mut stale = UserSessions::new(1, true, true);
+        stale.created_at = Some(Utc::now() - Duration::hours(2));
+        assert!(stale.clone().insert(&db).await.is_ok());
+        let fresh = UserSessions::new(1, true, true);
+        assert!(fresh.clone().insert(&db).await.is_ok());
+
+        assert_eq!(
+            UserSessions::purge_expired(&db, Duration::hours(1))
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(UserSessions::count(&db).await.unwrap(), 1);
+        assert!(!UserSessions::verify(&db, stale.id).await.unwrap().verified);
+        assert!(UserSessions::verify(&db, fresh.id).await.unwrap().verified);
+    }
 }
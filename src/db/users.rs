@@ -1,11 +1,12 @@
 use async_sqlite::rusqlite::Error as RusqliteError;
 use async_sqlite::{rusqlite::Row, Pool};
 use log::debug;
+use serde::Serialize;
 
-use crate::db::user_sessions::UserSessions;
+use crate::db::{timed_conn, timed_conn_mut, user_sessions::UserSessions};
 use crate::ternary;
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize)]
 pub struct Users {
     pub id: Option<i64>,
     pub email: String,
@@ -35,7 +36,7 @@ impl Users {
         email: String,
         pool: &Pool,
     ) -> Result<Option<Self>, async_sqlite::Error> {
-        pool.conn(move |conn| {
+        timed_conn(pool, move |conn| {
             let mut stmt = conn.prepare(
                 "SELECT id, email, has_admin, has_set_score FROM users WHERE email = ?1",
             )?;
@@ -64,19 +65,18 @@ impl Users {
         let new_user = Self::new(email.clone(), false, false);
 
         // Insert the user and get the ID
-        let user_id = pool
-            .conn(move |conn| {
-                conn.execute(
-                    "INSERT INTO users(email, has_admin, has_set_score) VALUES (?1, ?2, ?3);",
-                    [
-                        email.clone(),
-                        ternary!(new_user.has_admin => 1, 0).to_string(),
-                        ternary!(new_user.has_set_score => 1, 0).to_string(),
-                    ],
-                )?;
-                Ok(conn.last_insert_rowid())
-            })
-            .await?;
+        let user_id = timed_conn(pool, move |conn| {
+            conn.execute(
+                "INSERT INTO users(email, has_admin, has_set_score) VALUES (?1, ?2, ?3);",
+                [
+                    email.clone(),
+                    ternary!(new_user.has_admin => 1, 0).to_string(),
+                    ternary!(new_user.has_set_score => 1, 0).to_string(),
+                ],
+            )?;
+            Ok(conn.last_insert_rowid())
+        })
+        .await?;
 
         debug!("Created user with id: {}", user_id);
 
@@ -88,8 +88,21 @@ impl Users {
         })
     }
 
+    /// Make sure a user with `email` exists with admin and set score permissions, used to
+    /// bootstrap a known admin from `ADMIN_EMAIL`
+    pub async fn ensure_admin(email: String, pool: &Pool) -> Result<Self, async_sqlite::Error> {
+        let mut user = Self::get_or_create(email, pool).await?;
+        if !(user.has_admin && user.has_set_score) {
+            debug!("Granting admin to {}", user.email);
+            Self::update(pool, user.id.unwrap(), user.email.clone(), true, true).await?;
+            user.has_admin = true;
+            user.has_set_score = true;
+        }
+        Ok(user)
+    }
+
     pub async fn insert(self, pool: &Pool) -> Result<(), async_sqlite::Error> {
-        pool.conn(move |conn| {
+        timed_conn(pool, move |conn| {
             conn.execute(
                 "INSERT INTO users(email, has_admin, has_set_score) VALUES (?1, ?2, ?3);",
                 [
@@ -97,8 +110,7 @@ impl Users {
                     ternary!(self.has_admin => 1, 0).to_string(),
                     ternary!(self.has_set_score => 1, 0).to_string(),
                 ],
-            )
-            .unwrap();
+            )?;
             Ok(())
         })
         .await?;
@@ -106,11 +118,9 @@ impl Users {
     }
 
     pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
-        pool.conn(move |conn| {
+        timed_conn(pool, move |conn| {
             let mut stmt = conn.prepare("SELECT * FROM users")?;
-            let event_iter = stmt
-                .query_map([], |row| Ok(Self::map_from_row(row).unwrap()))
-                .unwrap();
+            let event_iter = stmt.query_map([], Self::map_from_row)?;
             let mut events = Vec::new();
 
             for event in event_iter {
@@ -122,7 +132,7 @@ impl Users {
     }
 
     pub async fn find_by_id(id: i64, pool: &Pool) -> Result<Option<Self>, async_sqlite::Error> {
-        pool.conn(move |conn| {
+        timed_conn(pool, move |conn| {
             let mut stmt = conn.prepare("SELECT * FROM users WHERE id = ?1")?;
             let mut rows = stmt.query([id])?;
 
@@ -142,7 +152,7 @@ impl Users {
         has_admin: bool,
         has_set_score: bool,
     ) -> Result<(), async_sqlite::Error> {
-        pool.conn(move |conn| {
+        timed_conn(pool, move |conn| {
             conn.execute(
                 "UPDATE users SET email = ?1, has_admin = ?2, has_set_score = ?3 WHERE id = ?4;",
                 [
@@ -151,25 +161,80 @@ impl Users {
                     ternary!(has_set_score => 1, 0).to_string(),
                     id.to_string(),
                 ],
-            )
-            .unwrap();
+            )?;
             Ok(())
         })
         .await?;
         Ok(())
     }
 
+    /// Grant permissions to every user with an email at `domain`, returning how many were updated.
+    /// Permissions are only ever added, so nobody is demoted.
+    pub async fn grant_by_domain(
+        pool: &Pool,
+        domain: String,
+        has_admin: bool,
+        has_set_score: bool,
+    ) -> Result<usize, async_sqlite::Error> {
+        let suffix = format!("@{}", domain.to_lowercase());
+        timed_conn(pool, move |conn| {
+            debug!("Granting permissions to users at {}", suffix);
+            // Compare the suffix directly rather than with LIKE so `%` and `_` aren't wildcards
+            let updated = conn.execute(
+                // Bind the flags as integers, SQLite sorts any text above any number so `MAX` with
+                // '0' would demote an admin
+                "UPDATE users SET has_admin = MAX(has_admin, ?1), has_set_score = MAX(has_set_score, ?2) WHERE lower(substr(email, -length(?3))) = ?3;",
+                (has_admin, has_set_score, suffix),
+            )?;
+            Ok(updated)
+        })
+        .await
+    }
+
+    /// Removes a user along with any sessions they hold, returning whether a user was removed
+    pub async fn delete(pool: &Pool, id: i64) -> Result<bool, async_sqlite::Error> {
+        timed_conn_mut(pool, move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM user_sessions WHERE user_id = ?1;", [id])?;
+            let deleted = tx.execute("DELETE FROM users WHERE id = ?1;", [id])?;
+            tx.commit()?;
+            Ok(deleted > 0)
+        })
+        .await
+    }
+
     pub fn new_session(self) -> UserSessions {
         UserSessions::new(self.id.unwrap(), self.has_admin, self.has_set_score)
     }
 
     pub async fn count(pool: &Pool) -> Result<i64, async_sqlite::Error> {
-        pool.conn(move |conn| {
+        timed_conn(pool, move |conn| {
             let count: i64 = conn.query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))?;
             Ok(count)
         })
         .await
     }
+
+    pub async fn admin_count(pool: &Pool) -> Result<i64, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let count: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM users WHERE has_admin = 1",
+                [],
+                |row| row.get(0),
+            )?;
+            Ok(count)
+        })
+        .await
+    }
+
+    /// Whether the user with `id` is the only remaining admin, so demoting or deleting them would
+    /// lock everyone out of the admin UI
+    pub async fn is_last_admin(pool: &Pool, id: i64) -> Result<bool, async_sqlite::Error> {
+        match Self::find_by_id(id, pool).await? {
+            Some(user) if user.has_admin => Ok(Self::admin_count(pool).await? <= 1),
+            _ => Ok(false),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -230,6 +295,62 @@ mod tests {
         )
     }
 
+    #[tokio::test]
+    async fn ensure_admin_fresh_db_test() {
+        let db = test_harness::setup_db("users_ensure_admin_fresh").await;
+        let user = Users::ensure_admin("admin@example.com".to_string(), &db)
+            .await
+            .unwrap();
+        assert!(user.has_admin);
+        assert!(user.has_set_score);
+
+        let stored = Users::find_by_email("admin@example.com".to_string(), &db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.id, user.id);
+        assert!(stored.has_admin);
+        assert!(stored.has_set_score);
+        assert_eq!(Users::count(&db).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn ensure_admin_existing_user_test() {
+        let db = test_harness::setup_db("users_ensure_admin_existing").await;
+        Users::new("first@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .unwrap();
+        Users::new("admin@example.com".to_string(), false, false)
+            .insert(&db)
+            .await
+            .unwrap();
+
+        Users::ensure_admin("admin@example.com".to_string(), &db)
+            .await
+            .unwrap();
+        let stored = Users::find_by_email("admin@example.com".to_string(), &db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(stored.has_admin);
+        assert!(stored.has_set_score);
+        assert_eq!(Users::count(&db).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn insert_duplicate_email_test() {
+        let db = test_harness::setup_db("users_insert_duplicate").await;
+        assert!(Users::new("example@example.com".to_string(), false, false)
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Users::new("example@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_err());
+    }
+
     #[tokio::test]
     async fn get_or_create_get_test() {
         let db = test_harness::setup_db("users_get_or_create_get").await;
@@ -361,6 +482,113 @@ mod tests {
         assert_eq!(Users::count(&db).await.unwrap(), 2);
     }
 
+    #[tokio::test]
+    async fn admin_count_test() {
+        let db = test_harness::setup_db("users_admin_count").await;
+        assert_eq!(Users::admin_count(&db).await.unwrap(), 0);
+
+        assert!(Users::new("example@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Users::new("example2@example.com".to_string(), false, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        assert_eq!(Users::admin_count(&db).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn grant_by_domain_test() {
+        let db = test_harness::setup_db("users_grant_by_domain").await;
+        for email in [
+            "teacher@staff.school.edu",
+            "Head@Staff.School.edu",
+            "pupil@school.edu",
+            "someone@notstaff.school.edu",
+        ] {
+            assert!(Users::new(email.to_string(), false, false)
+                .insert(&db)
+                .await
+                .is_ok());
+        }
+        assert!(Users::new("admin@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Users::new("boss@staff.school.edu".to_string(), true, false)
+            .insert(&db)
+            .await
+            .is_ok());
+
+        assert_eq!(
+            Users::grant_by_domain(&db, "staff.school.edu".to_string(), false, true)
+                .await
+                .unwrap(),
+            3
+        );
+
+        let users = Users::all(&db).await.unwrap();
+        let set_score: Vec<&str> = users
+            .iter()
+            .filter(|user| user.has_set_score)
+            .map(|user| user.email.as_str())
+            .collect();
+        assert_eq!(
+            set_score,
+            vec![
+                "teacher@staff.school.edu",
+                "Head@Staff.School.edu",
+                "admin@example.com",
+                "boss@staff.school.edu"
+            ]
+        );
+        // Existing admins in the domain stay admins
+        let admins: Vec<&str> = users
+            .iter()
+            .filter(|user| user.has_admin)
+            .map(|user| user.email.as_str())
+            .collect();
+        assert_eq!(admins, vec!["admin@example.com", "boss@staff.school.edu"]);
+    }
+
+    #[tokio::test]
+    async fn is_last_admin_test() {
+        let db = test_harness::setup_db("users_is_last_admin").await;
+        assert!(Users::new("example@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Users::new("example2@example.com".to_string(), false, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Users::is_last_admin(&db, 1).await.unwrap());
+        assert!(!Users::is_last_admin(&db, 2).await.unwrap());
+        assert!(!Users::is_last_admin(&db, 999).await.unwrap());
+
+        assert!(Users::new("example3@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(!Users::is_last_admin(&db, 1).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn delete_test() {
+        let db = test_harness::setup_db("users_delete").await;
+        assert!(Users::new("example@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        let user = Users::find_by_id(1, &db).await.unwrap().unwrap();
+        assert!(user.new_session().insert(&db).await.is_ok());
+
+        assert!(Users::delete(&db, 1).await.unwrap());
+        assert!(Users::find_by_id(1, &db).await.unwrap().is_none());
+        assert!(!Users::delete(&db, 1).await.unwrap());
+    }
+
     #[tokio::test]
     async fn find_by_email_not_found_test() {
         let db = test_harness::setup_db("users_find_by_email_not_found").await;
@@ -1,6 +1,9 @@
-use async_sqlite::{rusqlite::Row, Pool};
+use async_sqlite::{
+    rusqlite::{OptionalExtension, Row},
+    Pool,
+};
 
-use crate::db::events::Events;
+use crate::db::{events::Events, timed_conn};
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Years {
@@ -18,7 +21,7 @@ impl Years {
         }
     }
 
-    fn map_from_row(row: &Row) -> Result<Self, async_sqlite::Error> {
+    fn map_from_row(row: &Row) -> Result<Self, async_sqlite::rusqlite::Error> {
         Ok(Self {
             id: row.get(0)?,
             name: row.get(1)?,
@@ -29,21 +32,27 @@ impl Years {
     pub async fn insert(self, pool: &Pool) -> Result<Self, async_sqlite::Error> {
         let id = self.id.clone();
         let name = self.name.clone();
-        pool.conn(move |conn| {
-            conn.execute("INSERT INTO years(id, name) VALUES (?1, ?2);", [id, name])
-                .unwrap();
+        timed_conn(pool, move |conn| {
+            conn.execute("INSERT INTO years(id, name) VALUES (?1, ?2);", [id, name])?;
             Ok(())
         })
         .await?;
         Ok(self)
     }
 
+    pub async fn find_by_id(pool: &Pool, id: String) -> Result<Option<Self>, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM years WHERE id = ?1")?;
+            stmt.query_row([id], Self::map_from_row).optional()
+        })
+        .await
+    }
+
+    /// Every year, in the order they were inserted
     pub async fn all(pool: &Pool) -> Result<Vec<Self>, async_sqlite::Error> {
-        pool.conn(move |conn| {
-            let mut stmt = conn.prepare("SELECT * FROM years")?;
-            let year_iter = stmt
-                .query_map([], |row| Ok(Self::map_from_row(row).unwrap()))
-                .unwrap();
+        timed_conn(pool, move |conn| {
+            let mut stmt = conn.prepare("SELECT * FROM years ORDER BY rowid")?;
+            let year_iter = stmt.query_map([], Self::map_from_row)?;
             let mut years = Vec::new();
 
             for year in year_iter {
@@ -55,7 +64,7 @@ impl Years {
     }
 
     pub async fn new_event(
-        mut self,
+        self,
         pool: &Pool,
         id: String,
         name: String,
@@ -64,15 +73,32 @@ impl Years {
         scores: String,
     ) -> Result<Self, async_sqlite::Error> {
         let event = Events::new(id, name, self.clone().id, gender_id, filter_key, scores);
+        self.add_event(pool, event).await
+    }
+
+    /// Insert an already built event under this year
+    pub async fn add_event(
+        mut self,
+        pool: &Pool,
+        event: Events,
+    ) -> Result<Self, async_sqlite::Error> {
         self.events.push(event.clone());
         event.insert(&pool).await?;
 
         Ok(self)
     }
 
+    pub async fn count(pool: &Pool) -> Result<i64, async_sqlite::Error> {
+        timed_conn(pool, move |conn| {
+            let count: i64 = conn.query_row("SELECT COUNT(*) FROM years", [], |row| row.get(0))?;
+            Ok(count)
+        })
+        .await
+    }
+
     pub async fn delete_all(pool: &Pool) -> Result<(), async_sqlite::Error> {
-        pool.conn(move |conn| {
-            conn.execute("DELETE FROM years;", []).unwrap();
+        timed_conn(pool, move |conn| {
+            conn.execute("DELETE FROM years;", [])?;
             Ok(())
         })
         .await?;
@@ -107,6 +133,49 @@ mod tests {
             .is_ok());
     }
 
+    #[tokio::test]
+    async fn insert_duplicate_test() {
+        let db = test_harness::setup_db("years_insert_duplicate").await;
+        assert!(Years::new("test-test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        assert!(Years::new("test-test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn count_test() {
+        let db = test_harness::setup_db("years_count").await;
+        assert_eq!(Years::count(&db).await.unwrap(), 0);
+
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+        assert_eq!(Years::count(&db).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn find_by_id_test() {
+        let db = test_harness::setup_db("years_find_by_id").await;
+        assert!(Years::new("test".to_string(), "Test".to_string())
+            .insert(&db)
+            .await
+            .is_ok());
+
+        assert_eq!(
+            Years::find_by_id(&db, "test".to_string()).await.unwrap(),
+            Some(Years::new("test".to_string(), "Test".to_string()))
+        );
+        assert_eq!(
+            Years::find_by_id(&db, "missing".to_string()).await.unwrap(),
+            None
+        );
+    }
+
     #[tokio::test]
     async fn all_test() {
         let db = test_harness::setup_db("years_all").await;
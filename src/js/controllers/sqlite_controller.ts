@@ -4,14 +4,17 @@ interface SqliteResult {
   success: boolean;
   output: string;
   error?: string;
+  rows_affected?: number;
+  truncated: boolean;
 }
 
 export default class extends Controller<HTMLElement> {
-  static override targets = ["terminal", "history", "input"];
+  static override targets = ["terminal", "history", "input", "allowWrites"];
 
   declare readonly terminalTarget: HTMLElement;
   declare readonly historyTarget: HTMLElement;
   declare readonly inputTarget: HTMLInputElement;
+  declare readonly allowWritesTarget: HTMLInputElement;
 
   private commandHistory: string[] = [];
 
@@ -45,18 +48,30 @@ export default class extends Controller<HTMLElement> {
         headers: {
           "Content-Type": "application/json",
         },
-        body: JSON.stringify({ query: command }),
+        body: JSON.stringify({
+          query: command,
+          allow_writes: this.allowWritesTarget.checked,
+        }),
       });
 
       // Remove loading indicator
       loadingElement.remove();
 
-      if (response.ok) {
-        const result: SqliteResult = await response.json();
-
+      const result: SqliteResult | null = await response
+        .json()
+        .catch(() => null);
+      if (result) {
         if (result.success) {
-          if (result.output.trim()) {
-            this.addToHistory(result.output, "command-output");
+          if (result.rows_affected !== undefined && result.rows_affected !== null) {
+            this.addToHistory(
+              `${result.rows_affected} row(s) affected.`,
+              "command-output",
+            );
+          } else if (result.output.trim()) {
+            this.addToHistory(
+              result.truncated ? `${result.output}\n(truncated)` : result.output,
+              "command-output",
+            );
           } else {
             this.addToHistory("Query executed successfully.", "command-output");
           }
@@ -6,6 +6,8 @@ pub mod logger;
 pub mod middleware;
 pub mod prometheus;
 pub mod routes;
+pub mod session_store;
+pub mod settings;
 pub mod templates;
 pub mod utils;
 pub mod websocket;
@@ -16,16 +18,27 @@ pub mod test_harness;
 // Re-export commonly used items
 pub use db::create_tables;
 
+use std::sync::{atomic::AtomicBool, Arc};
+
 use async_sqlite::Pool;
-use configurator::parser::Configuration;
+use configurator::shared::SharedConfig;
 use logger::LogCollector;
+use session_store::SessionStore;
+use settings::Settings;
 
 pub struct AppState {
     pub client: reqwest::Client,
-    pub config: Configuration,
+    /// Read with `config.get()`, swapped out by `/admin/config/reload`
+    pub config: SharedConfig,
+    /// File `config` was loaded from, so it can be read again
+    pub config_path: String,
     pub log_collector: LogCollector,
+    /// Shows the maintenance page to non-admins, shared by every worker
+    pub maintenance: Arc<AtomicBool>,
     pub oauth_creds: OauthCreds,
     pub pool: Pool,
+    pub sessions: Box<dyn SessionStore>,
+    pub settings: Settings,
 }
 
 pub struct OauthCreds {
@@ -1,23 +1,50 @@
 use chrono::{DateTime, Utc};
 use log::Level;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::VecDeque;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
+/// Module recorded for entries that don't come from a module
+pub const DEFAULT_MODULE: &str = "app";
+
 /// A single log entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
     pub timestamp: DateTime<Utc>,
     pub level: String,
     pub message: String,
+    // Older records stored the module as optional, so backfill missing/null modules on load
+    #[serde(default = "default_module", deserialize_with = "deserialize_module")]
     pub module: String, // Changed from Option<String> to String
 }
 
+impl LogEntry {
+    /// Whether the entry is at `min_level` or more severe. Entries whose level can't be read
+    /// count as matching rather than being hidden.
+    pub fn is_at_least(&self, min_level: Level) -> bool {
+        Level::from_str(&self.level).map_or(true, |level| level <= min_level)
+    }
+}
+
+fn default_module() -> String {
+    DEFAULT_MODULE.to_string()
+}
+
+fn deserialize_module<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_else(default_module))
+}
+
 /// Thread-safe log collector that stores recent log entries
 #[derive(Debug, Clone)]
 pub struct LogCollector {
     entries: Arc<Mutex<VecDeque<LogEntry>>>,
     max_entries: usize,
+    /// Modules whose entries are kept out of the collector, they still reach env_logger
+    suppressed: Arc<Mutex<Vec<String>>>,
 }
 
 impl LogCollector {
@@ -26,16 +53,38 @@ impl LogCollector {
         Self {
             entries: Arc::new(Mutex::new(VecDeque::with_capacity(max_entries))),
             max_entries,
+            suppressed: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
+    /// Stop collecting entries from these modules, replacing any previous list. A name matches
+    /// any part of the module path, so `db` covers `sportsday_scoreboard_v2::db::events`.
+    pub fn set_suppressed_modules(&self, modules: Vec<String>) {
+        *self.suppressed.lock().unwrap() = modules;
+    }
+
+    pub fn suppressed_modules(&self) -> Vec<String> {
+        self.suppressed.lock().unwrap().clone()
+    }
+
+    fn is_suppressed(&self, module: &str) -> bool {
+        let suppressed = self.suppressed.lock().unwrap();
+        module
+            .split("::")
+            .any(|part| suppressed.iter().any(|name| name == part))
+    }
+
     /// Add a new log entry
     pub fn add_entry(&self, level: Level, message: &str, module: Option<&str>) {
+        if module.is_some_and(|module| self.is_suppressed(module)) {
+            return;
+        }
+
         let entry = LogEntry {
             timestamp: Utc::now(),
             level: level.to_string(),
             message: message.to_string(),
-            module: module.unwrap_or("app").to_string(), // Default to "app" if no module
+            module: module.unwrap_or(DEFAULT_MODULE).to_string(), // Default to "app" if no module
         };
 
         let mut entries = self.entries.lock().unwrap();
@@ -56,6 +105,36 @@ impl LogCollector {
         result
     }
 
+    /// Entries at `min_level` or more severe (newest first), e.g. `Level::Warn` gives warnings
+    /// and errors, see `LogEntry::is_at_least`
+    pub fn get_entries_filtered(&self, min_level: Level) -> Vec<LogEntry> {
+        self.get_entries()
+            .into_iter()
+            .filter(|entry| entry.is_at_least(min_level))
+            .collect()
+    }
+
+    /// Up to `max_results` entries whose message contains `needle`, ignoring case, newest first.
+    /// With `min_level` only entries at that level or more severe count towards `max_results`.
+    /// Only the matches are cloned while the lock is held.
+    pub fn search(
+        &self,
+        needle: &str,
+        min_level: Option<Level>,
+        max_results: usize,
+    ) -> Vec<LogEntry> {
+        let needle = needle.to_lowercase();
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .rev()
+            .filter(|entry| min_level.is_none_or(|min_level| entry.is_at_least(min_level)))
+            .filter(|entry| entry.message.to_lowercase().contains(&needle))
+            .take(max_results)
+            .cloned()
+            .collect()
+    }
+
     /// Clear all log entries
     pub fn clear(&self) {
         let mut entries = self.entries.lock().unwrap();
@@ -63,6 +142,16 @@ impl LogCollector {
     }
 }
 
+/// Split a comma separated list of module names, e.g. from `LOG_SUPPRESS_MODULES`
+pub fn parse_module_list(modules: &str) -> Vec<String> {
+    modules
+        .split(',')
+        .map(str::trim)
+        .filter(|module| !module.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
 /// Custom logger that writes to both env_logger and our collector
 pub struct CustomLogger {
     collector: LogCollector,
@@ -122,6 +211,27 @@ mod tests {
         assert_eq!(entry.module, "test_module");
     }
 
+    #[test]
+    fn test_log_entry_backfills_missing_module() {
+        let with_null: LogEntry = serde_json::from_str(
+            r#"{"timestamp":"2025-01-01T00:00:00Z","level":"INFO","message":"Old","module":null}"#,
+        )
+        .unwrap();
+        assert_eq!(with_null.module, "app");
+
+        let without: LogEntry = serde_json::from_str(
+            r#"{"timestamp":"2025-01-01T00:00:00Z","level":"INFO","message":"Old"}"#,
+        )
+        .unwrap();
+        assert_eq!(without.module, "app");
+
+        let with_module: LogEntry = serde_json::from_str(
+            r#"{"timestamp":"2025-01-01T00:00:00Z","level":"INFO","message":"New","module":"db"}"#,
+        )
+        .unwrap();
+        assert_eq!(with_module.module, "db");
+    }
+
     #[test]
     fn test_log_collector_new() {
         let collector = LogCollector::new(100);
@@ -153,6 +263,40 @@ mod tests {
         assert_eq!(entries[0].module, "app");
     }
 
+    #[test]
+    fn test_log_collector_suppressed_modules() {
+        let collector = LogCollector::new(10);
+        collector.set_suppressed_modules(parse_module_list(" websocket, ,db"));
+        assert_eq!(collector.suppressed_modules(), vec!["websocket", "db"]);
+
+        collector.add_entry(
+            Level::Debug,
+            "Subscribed",
+            Some("sportsday_scoreboard_v2::websocket"),
+        );
+        collector.add_entry(
+            Level::Info,
+            "Query",
+            Some("sportsday_scoreboard_v2::db::events"),
+        );
+        collector.add_entry(Level::Info, "Started", Some("sportsday_scoreboard_v2"));
+        collector.add_entry(Level::Info, "No module", None);
+
+        let entries = collector.get_entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].message, "No module");
+        assert_eq!(entries[1].message, "Started");
+
+        // Clearing the list lets them through again
+        collector.set_suppressed_modules(vec![]);
+        collector.add_entry(
+            Level::Debug,
+            "Subscribed",
+            Some("sportsday_scoreboard_v2::websocket"),
+        );
+        assert_eq!(collector.get_entries().len(), 3);
+    }
+
     #[test]
     fn test_log_collector_max_entries() {
         let collector = LogCollector::new(3);
@@ -249,6 +393,73 @@ mod tests {
         assert_eq!(entries[2].message, "First");
     }
 
+    #[test]
+    fn test_get_entries_filtered() {
+        let collector = LogCollector::new(10);
+        collector.add_entry(Level::Info, "Started", Some("main"));
+        collector.add_entry(Level::Warn, "Slow query", Some("db"));
+        collector.add_entry(Level::Debug, "Tick", Some("prometheus"));
+        collector.add_entry(Level::Error, "Query failed", Some("db"));
+        collector.add_entry(Level::Info, "Scores set", Some("routes"));
+
+        let messages = |level| -> Vec<String> {
+            collector
+                .get_entries_filtered(level)
+                .into_iter()
+                .map(|entry| entry.message)
+                .collect()
+        };
+        assert_eq!(messages(Level::Error), vec!["Query failed"]);
+        assert_eq!(messages(Level::Warn), vec!["Query failed", "Slow query"]);
+        assert_eq!(
+            messages(Level::Info),
+            vec!["Scores set", "Query failed", "Slow query", "Started"]
+        );
+        assert_eq!(messages(Level::Trace).len(), 5);
+    }
+
+    #[test]
+    fn test_search() {
+        let collector = LogCollector::new(10);
+        collector.add_entry(Level::Info, "Scores set for y7-boys-100m", Some("routes"));
+        collector.add_entry(Level::Error, "DB locked", Some("db"));
+        collector.add_entry(Level::Info, "scores SET for y8-girls-relay", Some("routes"));
+        collector.add_entry(Level::Info, "Scores set for y9-mixed-shot", Some("routes"));
+
+        let messages = |needle, min_level, max_results| -> Vec<String> {
+            collector
+                .search(needle, min_level, max_results)
+                .into_iter()
+                .map(|entry| entry.message)
+                .collect()
+        };
+        // Case-insensitive, newest first
+        assert_eq!(
+            messages("SCORES SET", None, 10),
+            vec![
+                "Scores set for y9-mixed-shot",
+                "scores SET for y8-girls-relay",
+                "Scores set for y7-boys-100m",
+            ]
+        );
+        // Capped to the newest matches
+        assert_eq!(
+            messages("scores set", None, 2),
+            vec![
+                "Scores set for y9-mixed-shot",
+                "scores SET for y8-girls-relay",
+            ]
+        );
+        assert!(messages("timeout", None, 10).is_empty());
+        // The level filter comes before the cap, so older errors aren't crowded out
+        collector.add_entry(Level::Error, "Scores set failed", Some("routes"));
+        collector.add_entry(Level::Info, "Scores set for y10-boys-200m", Some("routes"));
+        assert_eq!(
+            messages("scores set", Some(Level::Error), 1),
+            vec!["Scores set failed"]
+        );
+    }
+
     // E2E test
     #[tokio::test]
     async fn test_e2e_logger_integration() {
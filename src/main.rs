@@ -1,17 +1,24 @@
 use std::io::{Error, ErrorKind};
+use std::sync::{atomic::AtomicBool, Arc};
+use std::time::Duration;
 
 use actix::{Actor, Addr};
 use actix_files::Files;
 use actix_web::{middleware as ActixMiddleware, web, App, HttpServer};
-use async_sqlite::PoolBuilder;
 use log::debug;
 
 use sportsday_scoreboard_v2::{
-    configurator, db, logger, middleware, prometheus, routes, websocket,
+    configurator, db, logger, middleware, prometheus, routes, session_store, websocket,
 };
 
+use configurator::shared::SharedConfig;
 use logger::LogCollector;
 use middleware::authentication::{AuthConfig, Authentication};
+use middleware::maintenance::Maintenance;
+use middleware::pretty_json::PrettyJson;
+use middleware::timeout::RequestTimeout;
+use middleware::write_limit::WriteLimit;
+use session_store::{MemorySessionStore, SqliteSessionStore};
 use websocket::ChannelsActor;
 
 #[actix_web::main]
@@ -21,6 +28,9 @@ async fn main() -> std::io::Result<()> {
 
     // Create log collector (store up to 1000 log entries)
     let log_collector = LogCollector::new(1000);
+    if let Ok(modules) = std::env::var("LOG_SUPPRESS_MODULES") {
+        log_collector.set_suppressed_modules(logger::parse_module_list(&modules));
+    }
     let custom_logger = logger::CustomLogger::new(log_collector.clone());
 
     // Initialize custom logger
@@ -38,8 +48,46 @@ async fn main() -> std::io::Result<()> {
     let oauth_client_id = std::env::var("GITHUB_OAUTH_CLIENT_ID").unwrap();
     let oauth_client_secret = std::env::var("GITHUB_OAUTH_CLIENT_SECRET").unwrap();
 
+    // Base path, cookie path and domain for deploying behind a proxy
+    let settings = sportsday_scoreboard_v2::settings::Settings::from_env();
+
+    // Session storage backend ("sqlite" or "memory")
+    let session_backend = std::env::var("SESSION_STORE").unwrap_or_else(|_| "sqlite".to_string());
+    let memory_sessions = MemorySessionStore::default().with_ttl(settings.session_ttl);
+
+    // Requests taking longer than this many seconds are failed with a 503
+    let request_timeout = std::env::var("REQUEST_TIMEOUT_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .unwrap_or(30);
+
+    // Score writes allowed to hit the database at once, and how long the rest wait for a turn
+    let score_writes = WriteLimit::new(
+        std::env::var("SCORE_WRITE_PERMITS")
+            .ok()
+            .and_then(|permits| permits.parse::<usize>().ok())
+            .unwrap_or(4),
+        Duration::from_secs(
+            std::env::var("SCORE_WRITE_WAIT_SECS")
+                .ok()
+                .and_then(|secs| secs.parse::<u64>().ok())
+                .unwrap_or(5),
+        ),
+    );
+
+    // Show the "Back soon" page to everyone but admins, can be toggled from /admin/maintenance
+    let maintenance = Arc::new(AtomicBool::new(matches!(
+        std::env::var("MAINTENANCE").as_deref(),
+        Ok("1" | "true")
+    )));
+
+    // Number of pooled DB connections, defaults to one per CPU
+    let db_pool_size = std::env::var("DB_POOL_SIZE")
+        .ok()
+        .and_then(|size| size.parse::<usize>().ok());
+
     // Create the DB
-    let pool = match PoolBuilder::new().path(db_url).open().await {
+    let pool = match db::open_pool(&db_url, db_pool_size).await {
         Ok(pool) => {
             log::info!("Established DB pool");
             pool
@@ -61,6 +109,14 @@ async fn main() -> std::io::Result<()> {
         }
     }
 
+    // Guarantee a known admin exists, even on a pre-populated DB
+    if let Ok(admin_email) = std::env::var("ADMIN_EMAIL") {
+        match db::users::Users::ensure_admin(admin_email, &pool).await {
+            Ok(user) => log::info!("Ensured {} is an admin", user.email),
+            Err(e) => log::error!("Failed to bootstrap ADMIN_EMAIL {e}"),
+        }
+    }
+
     // Reqwest Client
     let client = reqwest::Client::builder()
         .user_agent("SportsDayScore")
@@ -68,70 +124,157 @@ async fn main() -> std::io::Result<()> {
         .unwrap();
 
     // Create the Plan & Run it
-    let config = match configurator::parser::Configuration::from_yaml_file("./config.yaml") {
+    let config_path = configurator::parser::Configuration::path_from_env();
+    let config = match configurator::parser::Configuration::from_yaml_file(&config_path) {
         Ok(config) => {
-            let plan = configurator::build::build_plan(config.clone());
             // Check if the version has already been built
-            if std::fs::exists("./version.txt").unwrap() {
-                if std::fs::read_to_string("./version.txt").unwrap() == config.get_version() {
+            if std::fs::exists(&settings.version_file).unwrap() {
+                if std::fs::read_to_string(&settings.version_file).unwrap() == config.get_version()
+                {
                     debug!("Config Version matches DB, not rebuilding");
                 } else {
                     debug!("Config Version doesn't match DB, rebuilding");
-                    configurator::run::run(plan, &pool).await.unwrap();
-                    std::fs::write("./version.txt", config.get_version())?;
+                    configurator::run::rebuild(&config, &pool).await.unwrap();
+                    std::fs::write(&settings.version_file, config.get_version())?;
                 }
             } else {
                 debug!("Version state doesn't exist, rebuilding");
-                configurator::run::run(plan, &pool).await.unwrap();
-                std::fs::write("./version.txt", config.get_version())?;
+                configurator::run::rebuild(&config, &pool).await.unwrap();
+                std::fs::write(&settings.version_file, config.get_version())?;
             }
             config
         }
         Err(e) => {
-            eprintln!("Error loading config: {}", e);
+            match e.downcast_ref::<configurator::parser::ConfigErrors>() {
+                Some(errors) => {
+                    for error in errors.0.iter() {
+                        eprintln!("Invalid config: {}", error);
+                    }
+                }
+                None => eprintln!("Error loading config: {}", e),
+            }
             std::process::exit(1);
         }
     };
 
+    log::info!("Loaded config with checksum {}", config.checksum());
+
     let ws_channels: Addr<ChannelsActor> = ChannelsActor::new().start();
 
+    // Created outside the factory so a reload reaches every worker and the metrics collector
+    let config = SharedConfig::new(config);
+
+    // One registry shared by every worker, sampling process and database metrics in the background
+    let (metrics, metrics_collector) = prometheus::build_prom(
+        pool.clone(),
+        prometheus::PromConfig {
+            interval: Duration::from_secs(
+                std::env::var("METRICS_INTERVAL_SECS")
+                    .ok()
+                    .and_then(|secs| secs.parse::<u64>().ok())
+                    .filter(|secs| *secs > 0)
+                    .unwrap_or(1),
+            ),
+            config: config.clone(),
+        },
+    );
+    // Expired sessions are only removed when presented, so clear out the rest now and then
+    let session_purge = match (session_backend.as_str(), settings.session_ttl) {
+        ("memory", _) | (_, None) => None,
+        (_, Some(ttl)) => Some(session_store::spawn_purge(
+            pool.clone(),
+            ttl,
+            settings.session_purge_interval,
+        )),
+    };
+
     HttpServer::new(move || {
         App::new()
             .wrap(ActixMiddleware::Logger::default())
             .wrap(middleware::headers::DefaultHtmlContentType)
-            .wrap(prometheus::build_prom(pool.clone()))
+            .wrap(Maintenance)
+            .wrap(RequestTimeout::new(Duration::from_secs(request_timeout)))
+            .wrap(metrics.clone())
             .app_data(web::Data::new(sportsday_scoreboard_v2::AppState {
                 client: client.clone(),
                 config: config.clone(),
+                config_path: config_path.clone(),
                 pool: pool.clone(),
                 log_collector: log_collector.clone(),
+                maintenance: maintenance.clone(),
                 oauth_creds: sportsday_scoreboard_v2::OauthCreds {
                     client_id: oauth_client_id.clone(),
                     client_secret: oauth_client_secret.clone(),
                 },
+                sessions: match session_backend.as_str() {
+                    "memory" => Box::new(memory_sessions.clone()),
+                    _ => Box::new(
+                        SqliteSessionStore::new(pool.clone()).with_ttl(settings.session_ttl),
+                    ),
+                },
+                settings: settings.clone(),
             }))
             .app_data(web::Data::new(ws_channels.clone()))
             .service(Files::new("assets/", "assets/"))
+            .service(routes::health::healthz)
             .service(routes::index::get)
             .service(routes::scoreboard::get)
+            .service(routes::scoreboard::year)
             .service(routes::results::get)
+            .service(routes::results::export_csv)
             .service(routes::ws::get)
+            .service(routes::sse::scoreboard)
             .service(routes::oauth::callback_get)
+            .service(web::scope("/public").service(routes::public::scoreboard))
             .service(
                 web::scope("/set_scores")
+                    .wrap(score_writes.clone())
                     .wrap(Authentication::new(AuthConfig::require_set_score()))
                     .service(routes::set_scores::get)
                     .service(routes::set_scores::post),
             )
+            .service(
+                web::scope("/events")
+                    .wrap(score_writes.clone())
+                    .wrap(Authentication::new(AuthConfig::require_set_score()))
+                    .service(routes::events::set_scores)
+                    .service(routes::events::reset_scores)
+                    .service(routes::events::swap_scores),
+            )
             .service(
                 web::scope("/admin")
                     .wrap(Authentication::new(AuthConfig::require_admin()))
                     .service(routes::admin::get)
+                    .service(routes::admin::stats::get)
+                    .service(routes::admin::repair::post)
+                    .service(routes::admin::events::raw)
+                    .service(
+                        web::scope("/config")
+                            .service(routes::admin::config::event_preview)
+                            .service(routes::admin::config::validate)
+                            .service(routes::admin::config::reload),
+                    )
                     .service(
                         web::scope("/console")
                             .service(routes::admin::console::get)
-                            .service(routes::admin::console::clear),
+                            .service(routes::admin::console::clear)
+                            .service(routes::admin::console::export)
+                            .service(routes::admin::console::suppress),
+                    )
+                    .service(
+                        web::scope("/diagnostics")
+                            .service(routes::admin::diagnostics::integrity)
+                            .service(routes::admin::diagnostics::corrupt_scores)
+                            .service(routes::admin::diagnostics::unused_forms)
+                            .service(routes::admin::diagnostics::repair_corrupt_scores),
+                    )
+                    .service(
+                        web::scope("/events")
+                            .service(routes::admin::events::create)
+                            .service(routes::admin::events::lock)
+                            .service(routes::admin::events::unlock),
                     )
+                    .service(web::scope("/maintenance").service(routes::admin::maintenance::set))
                     .service(
                         web::scope("/sqlite")
                             .service(routes::admin::sqlite::get)
@@ -143,12 +286,46 @@ async fn main() -> std::io::Result<()> {
                             .service(routes::admin::users::create)
                             .service(routes::admin::users::edit)
                             .service(routes::admin::users::update)
+                            .service(routes::admin::users::delete)
                             .service(routes::admin::users::new),
                     ),
             )
+            .service(
+                web::scope("/api")
+                    .wrap(PrettyJson)
+                    .service(routes::api::podium::podium)
+                    .service(routes::api::scoreboard::scoreboard)
+                    .service(routes::api::session::expiry)
+                    .service(routes::api::ticker::ticker)
+                    .service(routes::api::version::version)
+                    .service(
+                        web::scope("/events")
+                            .service(routes::api::events::index)
+                            .service(routes::api::events::changes),
+                    )
+                    .service(web::scope("/export").service(routes::api::export::county))
+                    .service(
+                        web::scope("/forms")
+                            .service(routes::api::forms::timeline)
+                            .service(routes::api::forms::get),
+                    )
+                    .service(
+                        web::scope("/users")
+                            .wrap(Authentication::new(AuthConfig::require_admin()))
+                            .service(routes::api::users::list)
+                            .service(routes::api::users::grant_domain)
+                            .service(routes::api::users::update)
+                            .service(routes::api::users::delete),
+                    ),
+            )
     })
     .bind((host, port))?
     .run()
     .await?;
+
+    metrics_collector.stop();
+    if let Some(session_purge) = session_purge {
+        session_purge.stop();
+    }
     Ok(())
 }
@@ -2,15 +2,12 @@ use std::future::{ready, Future, Ready};
 use std::pin::Pin;
 use std::rc::Rc;
 
-use actix_web::cookie::Cookie;
 use actix_web::{
     body::EitherBody,
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
     Error, HttpMessage, HttpResponse,
 };
 
-use crate::db::user_sessions::UserSessions;
-
 /// Configuration for the authentication middleware
 #[derive(Clone)]
 pub struct AuthConfig {
@@ -100,19 +97,51 @@ where
         let config = self.config.clone();
 
         Box::pin(async move {
+            // Get the app state for the session store and settings
+            let state = match req.app_data::<actix_web::web::Data<crate::AppState>>() {
+                Some(state) => state.clone(),
+                None => {
+                    log::error!("Could not get app state");
+                    return Ok(req.into_response(
+                        HttpResponse::InternalServerError()
+                            .body("Internal server error")
+                            .map_into_right_body(),
+                    ));
+                }
+            };
+
             // Extract the session_data cookie
             let session_data = req.cookie("session_data").map(|c| c.value().to_string());
 
             if session_data.is_none() {
                 log::debug!("No session_data cookie found");
                 let oauth_client_id = std::env::var("GITHUB_OAUTH_CLIENT_ID").unwrap();
-                let redirect_url = format!(
-                    "https://github.com/login/oauth/authorize?client_id={}&scope=user:email",
-                    oauth_client_id
-                );
+                let mut params = vec![
+                    ("client_id", oauth_client_id),
+                    ("scope", "user:email".to_string()),
+                ];
+                // Behind a proxy the callback GitHub has registered may not be the URL we see
+                if state.settings.trust_proxy {
+                    params.push((
+                        "redirect_uri",
+                        state
+                            .settings
+                            .absolute_url(req.request(), "/oauth/callback"),
+                    ));
+                }
+                let redirect_url = reqwest::Url::parse_with_params(
+                    "https://github.com/login/oauth/authorize",
+                    &params,
+                )
+                .unwrap();
                 let res = HttpResponse::Found()
-                    .append_header(("Location", redirect_url))
-                    .cookie(Cookie::build("redirect-to", req.path()).path("/").finish())
+                    .append_header(("Location", redirect_url.to_string()))
+                    .cookie(
+                        state
+                            .settings
+                            .cookie(req.request(), "redirect-to", state.settings.url(req.path()))
+                            .finish(),
+                    )
                     .finish();
                 return Ok(req.into_response(res).map_into_right_body());
             }
@@ -124,21 +153,8 @@ where
 
             let session_data = session_data.unwrap();
 
-            // Get the database pool from app data
-            let pool = match req.app_data::<actix_web::web::Data<crate::AppState>>() {
-                Some(state) => state.pool.clone(),
-                None => {
-                    log::error!("Could not get database pool from app state");
-                    return Ok(req.into_response(
-                        HttpResponse::InternalServerError()
-                            .body("Internal server error")
-                            .map_into_right_body(),
-                    ));
-                }
-            };
-
             // Verify the session
-            let verified_session = match UserSessions::verify(&pool, session_data).await {
+            let verified_session = match state.sessions.verify(session_data).await {
                 Ok(session) => session,
                 Err(e) => {
                     log::error!("Error verifying session: {}", e);
@@ -188,3 +204,128 @@ where
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, web, App};
+
+    use crate::{session_store::SessionStore, settings::Settings, test_harness};
+
+    async fn protected() -> HttpResponse {
+        HttpResponse::Ok().body("secret")
+    }
+
+    #[actix_web::test]
+    async fn test_login_redirect_respects_base_path() {
+        std::env::set_var("GITHUB_OAUTH_CLIENT_ID", "test");
+        let pool = test_harness::setup_db("authentication_base_path").await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(crate::AppState {
+                    settings: Settings {
+                        base_path: "/sportsday".to_string(),
+                        cookie_path: "/sportsday".to_string(),
+                        ..Default::default()
+                    },
+                    ..test_harness::app_state(pool, Default::default())
+                }))
+                .service(
+                    web::scope("/admin")
+                        .wrap(Authentication::new(AuthConfig::require_admin()))
+                        .route("", web::get().to(protected)),
+                ),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/admin").to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        let cookie = resp
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == "redirect-to")
+            .unwrap();
+        assert_eq!(cookie.value(), "/sportsday/admin");
+        assert_eq!(cookie.path(), Some("/sportsday"));
+    }
+
+    /// An app with `/admin` behind `require_admin` and a session store holding an admin and a
+    /// set-score-only session
+    async fn admin_app(
+        db_name: &str,
+    ) -> (
+        impl Service<actix_http::Request, Response = ServiceResponse, Error = Error>,
+        String,
+        String,
+    ) {
+        let pool = test_harness::setup_db(db_name).await;
+        let sessions = crate::session_store::MemorySessionStore::default();
+        let admin = crate::db::user_sessions::UserSessions::new(1, true, true);
+        let scorer = crate::db::user_sessions::UserSessions::new(2, false, true);
+        let (admin_id, scorer_id) = (admin.id.clone(), scorer.id.clone());
+        sessions.insert(admin).await.unwrap();
+        sessions.insert(scorer).await.unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(crate::AppState {
+                    sessions: Box::new(sessions),
+                    ..test_harness::app_state(pool, Default::default())
+                }))
+                .service(
+                    web::scope("/admin")
+                        .wrap(Authentication::new(AuthConfig::require_admin()))
+                        .route("", web::get().to(protected)),
+                ),
+        )
+        .await;
+        (app, admin_id, scorer_id)
+    }
+
+    #[actix_web::test]
+    async fn test_admin_session_is_allowed() {
+        let (app, admin_id, _) = admin_app("authentication_admin_allowed").await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin")
+            .cookie(actix_web::cookie::Cookie::new("session_data", admin_id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_non_admin_session_is_forbidden() {
+        let (app, _, scorer_id) = admin_app("authentication_non_admin_forbidden").await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin")
+            .cookie(actix_web::cookie::Cookie::new("session_data", scorer_id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_missing_session_is_redirected_to_login() {
+        std::env::set_var("GITHUB_OAUTH_CLIENT_ID", "test");
+        let (app, _, _) = admin_app("authentication_missing_session").await;
+
+        let req = test::TestRequest::get().uri("/admin").to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert!(resp
+            .headers()
+            .get("Location")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("https://github.com/login/oauth/authorize"));
+    }
+}
@@ -0,0 +1,169 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpResponse,
+};
+use askama::Template;
+
+use crate::templates::MaintenanceTemplate;
+
+/// Paths that keep working in maintenance mode so admins can log in and turn it off, and the
+/// load balancer doesn't take the instance out of service
+const ALLOWED_PREFIXES: [&str; 4] = ["/admin", "/assets", "/healthz", "/oauth"];
+
+/// Serves a "Back soon" page with a 503 to everyone but admins while `AppState::maintenance`
+/// is set
+pub struct Maintenance;
+
+impl<S, B> Transform<S, ServiceRequest> for Maintenance
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = MaintenanceMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MaintenanceMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct MaintenanceMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for MaintenanceMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            let state = req
+                .app_data::<actix_web::web::Data<crate::AppState>>()
+                .cloned();
+            let in_maintenance = state
+                .as_ref()
+                .is_some_and(|state| state.maintenance.load(Ordering::Relaxed));
+            let allowed = ALLOWED_PREFIXES
+                .iter()
+                .any(|prefix| req.path().starts_with(prefix));
+
+            if in_maintenance && !allowed {
+                let session = req.cookie("session_data").map(|c| c.value().to_string());
+                let is_admin = match (state, session) {
+                    (Some(state), Some(session)) => state
+                        .sessions
+                        .verify(session)
+                        .await
+                        .is_ok_and(|session| session.verified && session.has_admin),
+                    _ => false,
+                };
+
+                if !is_admin {
+                    let html = MaintenanceTemplate {}
+                        .render()
+                        .expect("Template should be valid");
+                    return Ok(req.into_response(
+                        HttpResponse::ServiceUnavailable()
+                            .content_type("text/html")
+                            .body(html)
+                            .map_into_right_body(),
+                    ));
+                }
+            }
+
+            let res = service.call(req).await?;
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{cookie::Cookie, get, http::StatusCode, test, web, App};
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    use crate::{
+        db::user_sessions::UserSessions,
+        session_store::{MemorySessionStore, SessionStore},
+        test_harness,
+    };
+
+    #[get("/scoreboard")]
+    async fn scoreboard() -> HttpResponse {
+        HttpResponse::Ok().body("scoreboard")
+    }
+
+    #[get("/admin")]
+    async fn admin() -> HttpResponse {
+        HttpResponse::Ok().body("admin")
+    }
+
+    #[actix_web::test]
+    async fn maintenance_mode_test() {
+        let sessions = MemorySessionStore::default();
+        let admin_session = UserSessions::new(1, true, true);
+        sessions.insert(admin_session.clone()).await.unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .wrap(Maintenance)
+                .app_data(web::Data::new(crate::AppState {
+                    maintenance: Arc::new(AtomicBool::new(true)),
+                    sessions: Box::new(sessions),
+                    ..test_harness::app_state(
+                        test_harness::setup_db("middleware_maintenance").await,
+                        Default::default(),
+                    )
+                }))
+                .service(scoreboard)
+                .service(admin),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/scoreboard").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = test::read_body(resp).await;
+        assert!(String::from_utf8(body.to_vec())
+            .unwrap()
+            .contains("Back soon"));
+
+        let req = test::TestRequest::get()
+            .uri("/admin")
+            .cookie(Cookie::new("session_data", admin_session.id.clone()))
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(body, "admin");
+
+        // Admins can still see the public pages
+        let req = test::TestRequest::get()
+            .uri("/scoreboard")
+            .cookie(Cookie::new("session_data", admin_session.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
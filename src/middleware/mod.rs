@@ -1,2 +1,6 @@
 pub mod authentication;
 pub mod headers;
+pub mod maintenance;
+pub mod pretty_json;
+pub mod timeout;
+pub mod write_limit;
@@ -0,0 +1,123 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+
+use actix_web::{
+    body::{self, BoxBody, EitherBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    web, Error,
+};
+
+/// Whether the request asked for indented JSON with `?pretty=1` or `?pretty=true`
+fn wants_pretty(req: &ServiceRequest) -> bool {
+    web::Query::<std::collections::HashMap<String, String>>::from_query(req.query_string())
+        .ok()
+        .and_then(|query| query.get("pretty").cloned())
+        .is_some_and(|pretty| pretty == "1" || pretty == "true")
+}
+
+/// Re-serialize a JSON body with indentation, `None` if it isn't valid JSON
+pub fn to_pretty(json: &[u8]) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_slice(json).ok()?;
+    serde_json::to_string_pretty(&value).ok()
+}
+
+/// Indents the JSON responses of the wrapped services when `Settings::dev_mode` is set or the
+/// request has `?pretty=1`, leaving them compact otherwise
+pub struct PrettyJson;
+
+impl<S, B> Transform<S, ServiceRequest> for PrettyJson
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = PrettyJsonMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(PrettyJsonMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct PrettyJsonMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for PrettyJsonMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let dev_mode = req
+            .app_data::<web::Data<crate::AppState>>()
+            .is_some_and(|state| state.settings.dev_mode);
+        let pretty = dev_mode || wants_pretty(&req);
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+            let is_json = res
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.starts_with("application/json"));
+            if !pretty || !is_json {
+                return Ok(res.map_into_left_body());
+            }
+
+            let (req, res) = res.into_parts();
+            let (res, body) = res.into_parts();
+            let bytes = body::to_bytes(body)
+                .await
+                .map_err(|e| actix_web::error::ErrorInternalServerError(e.into()))?;
+            let body = match to_pretty(&bytes) {
+                Some(pretty) => BoxBody::new(pretty),
+                None => BoxBody::new(bytes),
+            };
+
+            Ok(ServiceResponse::new(req, res.set_body(body)).map_into_right_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{get, test, App, HttpResponse};
+    use serde_json::json;
+
+    #[get("/api/thing")]
+    async fn thing() -> HttpResponse {
+        HttpResponse::Ok().json(json!({"a": 1}))
+    }
+
+    #[actix_web::test]
+    async fn pretty_query_test() {
+        let app = test::init_service(App::new().wrap(PrettyJson).service(thing)).await;
+
+        let req = test::TestRequest::get().uri("/api/thing").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(body, r#"{"a":1}"#);
+
+        let req = test::TestRequest::get()
+            .uri("/api/thing?pretty=1")
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        assert_eq!(body, "{\n  \"a\": 1\n}");
+    }
+}
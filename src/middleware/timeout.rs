@@ -0,0 +1,150 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Duration;
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    error::ErrorServiceUnavailable,
+    http::header,
+    Error,
+};
+
+/// Fails requests with a 503 Service Unavailable error once they take longer than the timeout.
+/// WebSocket upgrades are long-lived so they are never timed out.
+pub struct RequestTimeout {
+    timeout: Duration,
+}
+
+impl RequestTimeout {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTimeout
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestTimeoutMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTimeoutMiddleware {
+            service: Rc::new(service),
+            timeout: self.timeout,
+        }))
+    }
+}
+
+pub struct RequestTimeoutMiddleware<S> {
+    service: Rc<S>,
+    timeout: Duration,
+}
+
+fn is_websocket_upgrade(req: &ServiceRequest) -> bool {
+    req.headers()
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTimeoutMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if is_websocket_upgrade(&req) {
+            return Box::pin(self.service.call(req));
+        }
+
+        let path = req.path().to_string();
+        let fut = self.service.call(req);
+        let timeout = self.timeout;
+
+        Box::pin(async move {
+            match actix_web::rt::time::timeout(timeout, fut).await {
+                Ok(res) => res,
+                Err(_) => {
+                    log::warn!("Request to {} timed out after {:?}", path, timeout);
+                    Err(ErrorServiceUnavailable("Request timed out"))
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+
+    async fn slow_handler() -> HttpResponse {
+        actix_web::rt::time::sleep(Duration::from_millis(500)).await;
+        HttpResponse::Ok().body("slow")
+    }
+
+    #[actix_web::test]
+    async fn test_slow_request_times_out() {
+        let app = test::init_service(
+            App::new()
+                .wrap(RequestTimeout::new(Duration::from_millis(50)))
+                .route("/slow", web::get().to(slow_handler)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/slow").to_request();
+        let err = test::try_call_service(&app, req).await.unwrap_err();
+
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_fast_request_passes_through() {
+        let app = test::init_service(
+            App::new()
+                .wrap(RequestTimeout::new(Duration::from_secs(5)))
+                .route("/slow", web::get().to(slow_handler)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/slow").to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_websocket_upgrade_is_exempt() {
+        let app = test::init_service(
+            App::new()
+                .wrap(RequestTimeout::new(Duration::from_millis(50)))
+                .route("/slow", web::get().to(slow_handler)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/slow")
+            .insert_header((header::UPGRADE, "websocket"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,177 @@
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{header, Method},
+    Error, HttpResponse,
+};
+use tokio::sync::Semaphore;
+
+/// Bounds how many score writes reach SQLite at once. Writes wait for a permit and get a 503
+/// with `Retry-After` if none frees up within `max_wait`. GET requests aren't limited.
+///
+/// Clones share the same permits, so wrap every scope that writes scores with one instance.
+#[derive(Clone)]
+pub struct WriteLimit {
+    permits: Arc<Semaphore>,
+    max_wait: Duration,
+}
+
+impl WriteLimit {
+    pub fn new(permits: usize, max_wait: Duration) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(permits.max(1))),
+            max_wait,
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for WriteLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = WriteLimitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(WriteLimitMiddleware {
+            service: Rc::new(service),
+            limit: self.clone(),
+        }))
+    }
+}
+
+pub struct WriteLimitMiddleware<S> {
+    service: Rc<S>,
+    limit: WriteLimit,
+}
+
+impl<S, B> Service<ServiceRequest> for WriteLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let limit = self.limit.clone();
+
+        Box::pin(async move {
+            if req.method() == Method::GET {
+                return Ok(service.call(req).await?.map_into_left_body());
+            }
+
+            let permit = match actix_web::rt::time::timeout(
+                limit.max_wait,
+                limit.permits.acquire_owned(),
+            )
+            .await
+            {
+                Ok(Ok(permit)) => permit,
+                _ => {
+                    log::warn!(
+                        "Rejecting write to {}, no permit after {:?}",
+                        req.path(),
+                        limit.max_wait
+                    );
+                    let retry_after = limit.max_wait.as_secs().max(1).to_string();
+                    return Ok(req.into_response(
+                        HttpResponse::ServiceUnavailable()
+                            .insert_header((header::RETRY_AFTER, retry_after))
+                            .json(serde_json::json!({"error": "Too many score submissions, try again"}))
+                            .map_into_right_body(),
+                    ));
+                }
+            };
+
+            let res = service.call(req).await;
+            drop(permit);
+            Ok(res?.map_into_left_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, web, App};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct Concurrency {
+        current: AtomicUsize,
+        max: AtomicUsize,
+    }
+
+    async fn slow_write(concurrency: web::Data<Concurrency>) -> HttpResponse {
+        let current = concurrency.current.fetch_add(1, Ordering::SeqCst) + 1;
+        concurrency.max.fetch_max(current, Ordering::SeqCst);
+        actix_web::rt::time::sleep(Duration::from_millis(20)).await;
+        concurrency.current.fetch_sub(1, Ordering::SeqCst);
+        HttpResponse::NoContent().finish()
+    }
+
+    #[actix_web::test]
+    async fn test_writes_are_serialized() {
+        let concurrency = web::Data::new(Concurrency::default());
+        let app = test::init_service(
+            App::new()
+                .app_data(concurrency.clone())
+                .wrap(WriteLimit::new(1, Duration::from_secs(5)))
+                .route("/scores", web::post().to(slow_write)),
+        )
+        .await;
+
+        let responses = futures::future::join_all((0..5).map(|_| {
+            test::call_service(&app, test::TestRequest::post().uri("/scores").to_request())
+        }))
+        .await;
+
+        assert!(responses
+            .iter()
+            .all(|resp| resp.status() == StatusCode::NO_CONTENT));
+        assert_eq!(concurrency.max.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_waiting_too_long_is_rejected() {
+        let concurrency = web::Data::new(Concurrency::default());
+        let limit = WriteLimit::new(1, Duration::from_millis(1));
+        let app = test::init_service(
+            App::new()
+                .app_data(concurrency.clone())
+                .wrap(limit.clone())
+                .route("/scores", web::post().to(slow_write))
+                .route("/scores", web::get().to(slow_write)),
+        )
+        .await;
+
+        // Hold the only permit so the write can't get one
+        let _held = limit.permits.clone().acquire_owned().await.unwrap();
+        let resp =
+            test::call_service(&app, test::TestRequest::post().uri("/scores").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get(header::RETRY_AFTER).unwrap(), "1");
+
+        // Reads don't need a permit
+        let resp =
+            test::call_service(&app, test::TestRequest::get().uri("/scores").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    }
+}
@@ -1,13 +1,24 @@
 use actix_web_prom::{PrometheusMetrics, PrometheusMetricsBuilder};
 use async_sqlite::Pool;
-use prometheus::Gauge;
+use prometheus::{Gauge, GaugeVec, Opts};
+use std::collections::{BTreeMap, HashMap, HashSet};
+#[cfg(target_os = "linux")]
 use std::fs;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::db::{events::Events, users::Users};
+use crate::configurator::shared::SharedConfig;
+use crate::db::{
+    events::Events, forms::Forms, users::Users, years::Years, DB_IN_FLIGHT, DB_POOL_SIZE,
+};
+use crate::utils;
 
 // Parse total jiffies from /proc/stat (first "cpu" line)
+#[cfg(target_os = "linux")]
 fn read_total_jiffies() -> Option<u64> {
     let s = fs::read_to_string("/proc/stat").ok()?;
     for line in s.lines() {
@@ -27,6 +38,7 @@ fn read_total_jiffies() -> Option<u64> {
 }
 
 // Parse process jiffies (utime + stime) from /proc/self/stat
+#[cfg(target_os = "linux")]
 fn read_proc_jiffies() -> Option<u64> {
     let s = fs::read_to_string("/proc/self/stat").ok()?;
     // stat fields: see proc manpage. utime is field 14, stime 15 (1-based)
@@ -40,6 +52,7 @@ fn read_proc_jiffies() -> Option<u64> {
 }
 
 // Read resident set size (VmRSS) in bytes from /proc/self/status
+#[cfg(target_os = "linux")]
 fn read_proc_rss_bytes() -> Option<u64> {
     let s = fs::read_to_string("/proc/self/status").ok()?;
     for line in s.lines() {
@@ -56,8 +69,143 @@ fn read_proc_rss_bytes() -> Option<u64> {
     None
 }
 
-// Collect CPU and memory usage for the current process only (Linux /proc implementation).
-pub fn build_prom(pool: Pool) -> PrometheusMetrics {
+// Without /proc (macOS, the BSDs) the same readings come from getrusage, in microseconds rather
+// than jiffies. "Total" is wall-clock time across every CPU, so the percentage works out the same.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn read_rusage() -> Option<libc::rusage> {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    // SAFETY: getrusage only writes to the struct it's given
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // SAFETY: getrusage returned 0 so the struct is filled in
+    Some(unsafe { usage.assume_init() })
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn read_total_jiffies() -> Option<u64> {
+    let since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?;
+    let cpus = thread::available_parallelism().map_or(1, |cpus| cpus.get());
+    Some((since_epoch.as_micros() as u64).saturating_mul(cpus as u64))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn read_proc_jiffies() -> Option<u64> {
+    let usage = read_rusage()?;
+    let micros = |time: libc::timeval| time.tv_sec as u64 * 1_000_000 + time.tv_usec as u64;
+    Some(micros(usage.ru_utime).saturating_add(micros(usage.ru_stime)))
+}
+
+// Peak rather than current RSS, the closest getrusage has. macOS reports bytes, the BSDs kilobytes.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn read_proc_rss_bytes() -> Option<u64> {
+    let max_rss = read_rusage()?.ru_maxrss as u64;
+    if cfg!(target_os = "macos") {
+        Some(max_rss)
+    } else {
+        Some(max_rss * 1024)
+    }
+}
+
+// No fallback for other platforms yet, the gauges stay at zero
+#[cfg(not(unix))]
+fn read_total_jiffies() -> Option<u64> {
+    None
+}
+
+#[cfg(not(unix))]
+fn read_proc_jiffies() -> Option<u64> {
+    None
+}
+
+#[cfg(not(unix))]
+fn read_proc_rss_bytes() -> Option<u64> {
+    None
+}
+
+// Each form's scoreboard total. Forms come from the database rather than the startup config, so
+// a rebuild from a new config adds and drops series.
+async fn read_form_totals(
+    pool: &Pool,
+    gender_multipliers: &BTreeMap<String, f64>,
+) -> Result<HashMap<String, f64>, async_sqlite::Error> {
+    let form_ids: Vec<String> = Forms::all(pool)
+        .await?
+        .into_iter()
+        .map(|form| form.id)
+        .collect();
+    let years = Years::all(pool).await?;
+    Ok(
+        utils::tally_scoreboard(pool, &years, &form_ids, gender_multipliers, None)
+            .await
+            .form_totals,
+    )
+}
+
+// Set a series per form and remove the ones for forms that have gone
+fn set_form_totals(gauge: &GaugeVec, previous: &mut HashSet<String>, totals: HashMap<String, f64>) {
+    let current: HashSet<String> = totals.keys().cloned().collect();
+    for form_id in previous.difference(&current) {
+        let _ = gauge.remove_label_values(&[form_id]);
+    }
+    for (form_id, total) in totals {
+        gauge.with_label_values(&[&form_id]).set(total);
+    }
+    *previous = current;
+}
+
+/// Settings for the background metrics collector
+#[derive(Clone)]
+pub struct PromConfig {
+    /// Time between samples of CPU, memory and database counts
+    pub interval: Duration,
+    /// The running config. Its `gender_multipliers` are applied to `form_total_points` like on the
+    /// scoreboard, read on every sample so a reload takes effect.
+    pub config: SharedConfig,
+}
+
+impl Default for PromConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            config: SharedConfig::default(),
+        }
+    }
+}
+
+/// Stops the collector thread started by [`build_prom`]
+pub struct PromHandle {
+    stop: Arc<AtomicBool>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl PromHandle {
+    /// Wake the collector, let it finish any sample in progress and wait for it to exit
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.thread.thread().unpark();
+        let _ = self.thread.join();
+    }
+}
+
+// Sleep for `interval` unless asked to stop first, returning whether to take another sample
+pub(crate) fn wait_for_next_sample(stop: &AtomicBool, interval: Duration) -> bool {
+    let deadline = Instant::now() + interval;
+    while !stop.load(Ordering::SeqCst) {
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        thread::park_timeout(deadline - now);
+    }
+    false
+}
+
+// Collect CPU and memory usage for the current process only (/proc on Linux, getrusage on other
+// Unix platforms).
+pub fn build_prom(pool: Pool, config: PromConfig) -> (PrometheusMetrics, PromHandle) {
     let prometheus = PrometheusMetricsBuilder::new("api")
         .endpoint("/metrics")
         .build()
@@ -75,6 +223,11 @@ pub fn build_prom(pool: Pool) -> PrometheusMetrics {
     .unwrap();
     let event_count = Gauge::new("event_count", "Total number of events in the database").unwrap();
     let user_count = Gauge::new("user_count", "Total number of users in the database").unwrap();
+    let form_total_points = GaugeVec::new(
+        Opts::new("form_total_points", "Each form's total on the scoreboard"),
+        &["form"],
+    )
+    .unwrap();
 
     prometheus
         .registry
@@ -96,7 +249,24 @@ pub fn build_prom(pool: Pool) -> PrometheusMetrics {
         .register(Box::new(user_count.clone()))
         .unwrap();
 
-    thread::spawn(move || {
+    prometheus
+        .registry
+        .register(Box::new(form_total_points.clone()))
+        .unwrap();
+
+    prometheus
+        .registry
+        .register(Box::new(DB_IN_FLIGHT.clone()))
+        .unwrap();
+
+    prometheus
+        .registry
+        .register(Box::new(DB_POOL_SIZE.clone()))
+        .unwrap();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let collector_stop = stop.clone();
+    let thread = thread::spawn(move || {
         // Create a new tokio runtime for async operations
         let rt = tokio::runtime::Builder::new_current_thread()
             .enable_all()
@@ -106,10 +276,9 @@ pub fn build_prom(pool: Pool) -> PrometheusMetrics {
         // initial values
         let mut prev_total = read_total_jiffies().unwrap_or(0);
         let mut prev_proc = read_proc_jiffies().unwrap_or(0);
+        let mut scored_forms = HashSet::new();
 
-        loop {
-            thread::sleep(Duration::from_secs(1));
-
+        while wait_for_next_sample(&collector_stop, config.interval) {
             let total = match read_total_jiffies() {
                 Some(v) => v,
                 None => continue,
@@ -145,52 +314,44 @@ pub fn build_prom(pool: Pool) -> PrometheusMetrics {
             if let Ok(count) = rt.block_on(async { Users::count(&pool_clone).await }) {
                 user_count.set(count as f64);
             }
+
+            let pool_clone = pool.clone();
+            let running = config.config.get();
+            if let Ok(totals) = rt.block_on(async {
+                read_form_totals(&pool_clone, &running.gender_multipliers).await
+            }) {
+                set_form_totals(&form_total_points, &mut scored_forms, totals);
+            }
         }
     });
 
-    prometheus
+    (prometheus, PromHandle { stop, thread })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use prometheus::core::Collector;
 
     #[test]
-    #[cfg(target_os = "linux")]
+    #[cfg(unix)]
     fn test_read_total_jiffies() {
         let result = read_total_jiffies();
-        // On Linux, this should return Some value
-        if cfg!(target_os = "linux") {
-            assert!(result.is_some());
-            if let Some(jiffies) = result {
-                assert!(jiffies > 0);
-            }
-        }
+        assert!(result.unwrap() > 0);
     }
 
     #[test]
-    #[cfg(target_os = "linux")]
+    #[cfg(unix)]
     fn test_read_proc_jiffies() {
         let result = read_proc_jiffies();
-        // Should return some value on Linux
-        if cfg!(target_os = "linux") {
-            assert!(result.is_some());
-            // jiffies is u64, so it's always >= 0
-            assert!(result.unwrap() > 0 || result.unwrap() == 0);
-        }
+        assert!(result.is_some());
     }
 
     #[test]
-    #[cfg(target_os = "linux")]
+    #[cfg(unix)]
     fn test_read_proc_rss_bytes() {
         let result = read_proc_rss_bytes();
-        // Should return some value on Linux
-        if cfg!(target_os = "linux") {
-            assert!(result.is_some());
-            if let Some(bytes) = result {
-                assert!(bytes > 0);
-            }
-        }
+        assert!(result.unwrap() > 0);
     }
 
     #[tokio::test]
@@ -198,14 +359,168 @@ mod tests {
         use crate::test_harness;
 
         let db = test_harness::setup_db("prometheus_test").await;
-        let prom = build_prom(db.clone());
+        let (prom, handle) = build_prom(db.clone(), PromConfig::default());
 
         // Verify the prometheus metrics builder was created successfully
         assert_eq!(prom.registry.gather().len() >= 4, true);
+        handle.stop();
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_build_prom_samples_host_usage() {
+        use crate::test_harness;
+
+        let db = test_harness::setup_db("prometheus_interval_test").await;
+        let (prom, handle) = build_prom(
+            db.clone(),
+            PromConfig {
+                interval: Duration::from_millis(100),
+                ..Default::default()
+            },
+        );
+        // Keep a CPU busy across a few samples
+        let started = Instant::now();
+        let mut sum = 0u64;
+        while started.elapsed() < Duration::from_millis(350) {
+            sum = sum.wrapping_add(1);
+        }
+        handle.stop();
+        assert!(sum > 0);
+
+        let gauge = |name: &str| {
+            prom.registry
+                .gather()
+                .into_iter()
+                .find(|family| family.name() == name)
+                .unwrap()
+                .get_metric()[0]
+                .get_gauge()
+                .value()
+        };
+        assert!(gauge("process_cpu_usage_percent") > 0.0);
+        assert!(gauge("process_memory_bytes") > 0.0);
+    }
+
+    #[test]
+    fn test_set_form_totals_drops_stale_forms() {
+        let gauge =
+            GaugeVec::new(Opts::new("test_form_totals", "Test form totals"), &["form"]).unwrap();
+        let mut previous = HashSet::new();
+
+        set_form_totals(
+            &gauge,
+            &mut previous,
+            HashMap::from([("f1".to_string(), 3.0), ("f2".to_string(), 5.5)]),
+        );
+        assert_eq!(gauge.with_label_values(&["f2"]).get(), 5.5);
+
+        set_form_totals(
+            &gauge,
+            &mut previous,
+            HashMap::from([("f2".to_string(), 6.0), ("f3".to_string(), 1.0)]),
+        );
+        let mut forms: Vec<String> = gauge
+            .collect()
+            .iter()
+            .flat_map(|family| family.get_metric())
+            .map(|metric| metric.get_label()[0].value().to_string())
+            .collect();
+        forms.sort();
+        assert_eq!(forms, vec!["f2", "f3"]);
+        assert_eq!(gauge.with_label_values(&["f2"]).get(), 6.0);
+    }
+
+    #[tokio::test]
+    async fn test_build_prom_tracks_form_totals() {
+        use crate::test_harness;
+
+        let db = test_harness::setup_db("prometheus_form_totals_test").await;
+        Years::new("y7".to_string(), "Year 7".to_string())
+            .insert(&db)
+            .await
+            .unwrap();
+        for id in ["f1", "f2"] {
+            Forms::new(id.to_string(), id.to_string(), "red".to_string())
+                .insert(&db)
+                .await
+                .unwrap();
+        }
+        Events::new(
+            "y7-mixed-100m".to_string(),
+            "100m".to_string(),
+            "y7".to_string(),
+            "mixed".to_string(),
+            "100m".to_string(),
+            r#"{"f1": "3", "f2": "1"}"#.to_string(),
+        )
+        .insert(&db)
+        .await
+        .unwrap();
+
+        let config = SharedConfig::default();
+        let (prom, handle) = build_prom(
+            db.clone(),
+            PromConfig {
+                interval: Duration::from_millis(10),
+                config: config.clone(),
+            },
+        );
+        let form_total = |form_id: &str| {
+            prom.registry
+                .gather()
+                .into_iter()
+                .find(|family| family.name() == "form_total_points")?
+                .get_metric()
+                .iter()
+                .find(|metric| metric.get_label()[0].value() == form_id)
+                .map(|metric| metric.get_gauge().value())
+        };
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(form_total("f1"), Some(3.0));
+
+        Events::set_scores(
+            &db,
+            "y7-mixed-100m".to_string(),
+            serde_json::json!({"f1": "8", "f2": "2.5"}),
+        )
+        .await
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(form_total("f1"), Some(8.0));
+        assert_eq!(form_total("f2"), Some(2.5));
+
+        // A reload with new multipliers is picked up without restarting the collector
+        config.replace(crate::configurator::parser::Configuration {
+            gender_multipliers: [("mixed".to_string(), 2.0)].into(),
+            ..Default::default()
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        handle.stop();
+
+        assert_eq!(form_total("f1"), Some(16.0));
+        assert_eq!(form_total("f2"), Some(5.0));
+    }
+
+    #[test]
+    fn test_prom_handle_stops_without_waiting_for_interval() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let handle = PromHandle {
+            stop,
+            thread: thread::spawn(move || {
+                while wait_for_next_sample(&thread_stop, Duration::from_secs(3600)) {}
+            }),
+        };
+
+        let started = Instant::now();
+        handle.stop();
+        assert!(started.elapsed() < Duration::from_secs(5));
     }
 
     #[test]
-    #[cfg(target_os = "linux")]
+    #[cfg(unix)]
     fn test_jiffies_increase_over_time() {
         let first = read_total_jiffies();
 
@@ -227,9 +542,9 @@ mod tests {
     }
 
     #[test]
-    #[cfg(not(target_os = "linux"))]
-    fn test_read_functions_on_non_linux() {
-        // On non-Linux systems, these should return None
+    #[cfg(not(unix))]
+    fn test_read_functions_without_fallback() {
+        // Platforms without a fallback report nothing
         assert_eq!(read_total_jiffies(), None);
         assert_eq!(read_proc_jiffies(), None);
         assert_eq!(read_proc_rss_bytes(), None);
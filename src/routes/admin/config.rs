@@ -0,0 +1,289 @@
+use actix_web::{get, post, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    configurator::{
+        build::expand_event,
+        parser::{ConfigErrors, Configuration, Event, Year},
+        run::rebuild,
+    },
+    db::events::Events,
+    AppState,
+};
+
+#[derive(Deserialize)]
+pub struct EventPreviewRequest {
+    pub event: Event,
+    pub years: Vec<Year>,
+    pub genders: Vec<String>,
+}
+
+/// Ids of the events a single config event would generate
+#[post("/event-preview")]
+pub async fn event_preview(body: web::Json<EventPreviewRequest>) -> HttpResponse {
+    match expand_event(&body.event, &body.years, &body.genders) {
+        Ok(expanded) => HttpResponse::Ok().json(
+            expanded
+                .into_iter()
+                .map(|expanded| expanded.id)
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => HttpResponse::BadRequest().json(serde_json::json!({"error": e.to_string()})),
+    }
+}
+
+/// Re-read the config file the server was started from and report any problems with it
+#[get("/validate")]
+pub async fn validate(state: web::Data<AppState>) -> HttpResponse {
+    let errors: Vec<String> = match Configuration::from_yaml_file(&state.config_path) {
+        Ok(_) => vec![],
+        Err(e) => match e.downcast_ref::<ConfigErrors>() {
+            Some(errors) => errors.0.iter().map(|error| error.to_string()).collect(),
+            // Unreadable or not valid YAML at all
+            None => {
+                return HttpResponse::UnprocessableEntity().json(json!({
+                    "path": state.config_path,
+                    "valid": false,
+                    "errors": [e.to_string()],
+                }));
+            }
+        },
+    };
+    HttpResponse::Ok().json(json!({
+        "path": state.config_path,
+        "valid": errors.is_empty(),
+        "errors": errors,
+    }))
+}
+
+/// Load the config file again and rebuild the years and events from it. The running config is
+/// only replaced once the new one has validated and been built, otherwise it keeps serving.
+#[post("/reload")]
+pub async fn reload(state: web::Data<AppState>) -> HttpResponse {
+    let config = match Configuration::from_yaml_file(&state.config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            let errors: Vec<String> = match e.downcast_ref::<ConfigErrors>() {
+                Some(errors) => errors.0.iter().map(|error| error.to_string()).collect(),
+                None => vec![e.to_string()],
+            };
+            return HttpResponse::UnprocessableEntity().json(json!({
+                "path": state.config_path,
+                "reloaded": false,
+                "errors": errors,
+            }));
+        }
+    };
+
+    let report = match rebuild(&config, &state.pool).await {
+        Ok(report) => report,
+        Err(e) => {
+            log::error!("Failed to rebuild from {}: {}", state.config_path, e);
+            return HttpResponse::InternalServerError().json(json!({
+                "path": state.config_path,
+                "reloaded": false,
+                "errors": [e.to_string()],
+            }));
+        }
+    };
+    // Otherwise the next restart would see an old version and rebuild again
+    if let Err(e) = std::fs::write(&state.settings.version_file, config.get_version()) {
+        log::warn!("Failed to record config version: {}", e);
+    }
+
+    let body = json!({
+        "path": state.config_path,
+        "reloaded": true,
+        "version": config.get_version(),
+        "checksum": config.checksum(),
+        "archived": report.archived,
+        "warning": report.warning(),
+    });
+    log::info!(
+        "Reloaded config {} with checksum {}",
+        config.get_version(),
+        config.checksum()
+    );
+    state.config.replace(config);
+    // Multipliers and names can change without touching a row
+    if let Err(e) = Events::mark_modified(&state.pool).await {
+        log::warn!("Failed to mark the scoreboard modified: {}", e);
+    }
+    HttpResponse::Ok().json(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    use crate::test_harness;
+
+    #[actix_web::test]
+    async fn validate_uses_config_path_test() {
+        let config_path = std::env::temp_dir().join("admin_config_validate.yaml");
+        std::fs::write(
+            &config_path,
+            "version: \"1.0.0\"\ngenders: []\nscores:\n  - name: 1st\n    value: 10\n    default: true\nyears: []\nforms:\n  - id: a\n    name: A\n    colour: red\nevents: []\nlimits:\n  max_forms: 0\n",
+        )
+        .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    config_path: config_path.to_string_lossy().to_string(),
+                    ..test_harness::app_state(
+                        test_harness::setup_db("admin_config_validate").await,
+                        Default::default(),
+                    )
+                }))
+                .service(web::scope("/admin/config").service(validate)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/config/validate")
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        std::fs::remove_file(&config_path).unwrap();
+
+        assert_eq!(body["path"], config_path.to_string_lossy().as_ref());
+        assert_eq!(body["valid"], false);
+        assert_eq!(
+            body["errors"],
+            json!(["forms has 1 entries, the limit is 0"])
+        );
+    }
+
+    #[actix_web::test]
+    async fn event_preview_test() {
+        let app = test::init_service(
+            App::new().service(web::scope("/admin/config").service(event_preview)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/config/event-preview")
+            .set_json(json!({
+                "event": {
+                    "id": "shotput",
+                    "name": "Shot Put",
+                    "applicable_years": {"type": "include", "ids": ["year8", "year9"]},
+                    "applicable_genders": {"type": "exclude", "ids": ["mixed"]},
+                },
+                "years": [
+                    {"id": "year7", "name": "Year 7"},
+                    {"id": "year8", "name": "Year 8"},
+                    {"id": "year9", "name": "Year 9"},
+                ],
+                "genders": ["boys", "girls", "mixed"],
+            }))
+            .to_request();
+        let ids: Vec<String> = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(
+            ids,
+            vec![
+                "year8-boys-shotput",
+                "year8-girls-shotput",
+                "year9-boys-shotput",
+                "year9-girls-shotput",
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn reload_test() {
+        let config_path = std::env::temp_dir().join("admin_config_reload.yaml");
+        let version_file = std::env::temp_dir().join("admin_config_reload_version.txt");
+        let config_yaml = |version: &str, events: &str| {
+            format!(
+                "version: \"{}\"\ngenders: [mixed]\nscores:\n  - name: 1st\n    value: 10\n    default: true\nyears:\n  - id: y7\n    name: Year 7\nforms:\n  - id: a\n    name: A\n    colour: red\nevents:\n{}",
+                version, events
+            )
+        };
+        let event = |id: &str| {
+            format!(
+                "  - id: {}\n    name: {}\n    applicable_years:\n      type: all\n    applicable_genders:\n      type: all\n",
+                id, id
+            )
+        };
+        std::fs::write(
+            &config_path,
+            config_yaml("2.0.0", &(event("100m") + &event("relay"))),
+        )
+        .unwrap();
+
+        let pool = test_harness::setup_db("admin_config_reload").await;
+        let state = web::Data::new(AppState {
+            config_path: config_path.to_string_lossy().to_string(),
+            settings: crate::settings::Settings {
+                version_file: version_file.to_string_lossy().to_string(),
+                ..Default::default()
+            },
+            ..test_harness::app_state(pool.clone(), Default::default())
+        });
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/admin/config").service(reload)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/config/reload")
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["reloaded"], true);
+        assert_eq!(body["version"], "2.0.0");
+        assert_eq!(state.config.get().events.len(), 2);
+        let ids: Vec<String> = Events::all(&pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| event.id)
+            .collect();
+        assert_eq!(ids, vec!["y7-mixed-100m", "y7-mixed-relay"]);
+        assert_eq!(std::fs::read_to_string(&version_file).unwrap(), "2.0.0");
+
+        // A broken config leaves the running one and its events alone
+        std::fs::write(&config_path, config_yaml("3.0.0", "  - id: 100m\n")).unwrap();
+        let req = test::TestRequest::post()
+            .uri("/admin/config/reload")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(
+            resp.status(),
+            actix_web::http::StatusCode::UNPROCESSABLE_ENTITY
+        );
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["reloaded"], false);
+        assert_eq!(state.config.get().get_version(), "2.0.0");
+        assert_eq!(Events::count(&pool).await.unwrap(), 2);
+
+        // Dropping a scored event with auto-archive on says so in the response
+        Events::set_scores(&pool, "y7-mixed-relay".to_string(), json!({"a": "4"}))
+            .await
+            .unwrap();
+        std::fs::write(
+            &config_path,
+            "auto_archive_on_reload: true\n".to_string() + &config_yaml("4.0.0", &event("100m")),
+        )
+        .unwrap();
+        let req = test::TestRequest::post()
+            .uri("/admin/config/reload")
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        std::fs::remove_file(&config_path).unwrap();
+        std::fs::remove_file(&version_file).unwrap();
+
+        assert_eq!(body["reloaded"], true);
+        assert_eq!(body["archived"], 1);
+        assert_eq!(
+            body["warning"],
+            "Archived 1 scored events that are no longer in the config"
+        );
+    }
+}
@@ -1,11 +1,47 @@
+use std::str::FromStr;
+
 use actix_web::{get, post, web, HttpResponse};
 use askama::Template;
+use chrono::{DateTime, Utc};
+use log::Level;
+use serde_json::json;
 
 use crate::templates::AdminConsoleTemplate;
 
+/// Most entries a `?q=` search returns
+const MAX_SEARCH_RESULTS: usize = 200;
+
+#[derive(serde::Deserialize)]
+pub struct ConsoleQuery {
+    /// Least severe level to show, e.g. `warn`
+    level: Option<String>,
+    /// Only entries whose message contains this, ignoring case
+    q: Option<String>,
+}
+
 #[get("")]
-pub async fn get(app_state: web::Data<crate::AppState>) -> HttpResponse {
-    let log_entries = app_state.log_collector.get_entries();
+pub async fn get(
+    app_state: web::Data<crate::AppState>,
+    query: web::Query<ConsoleQuery>,
+) -> HttpResponse {
+    let min_level = match query.level.as_deref() {
+        Some(level) => match Level::from_str(level) {
+            Ok(level) => Some(level),
+            Err(_) => {
+                return HttpResponse::BadRequest().body(format!("Unknown log level {}", level));
+            }
+        },
+        None => None,
+    };
+    let log_entries = match (query.q.as_deref(), min_level) {
+        (Some(needle), min_level) => {
+            app_state
+                .log_collector
+                .search(needle, min_level, MAX_SEARCH_RESULTS)
+        }
+        (None, Some(min_level)) => app_state.log_collector.get_entries_filtered(min_level),
+        (None, None) => app_state.log_collector.get_entries(),
+    };
 
     HttpResponse::Ok().body(
         AdminConsoleTemplate { log_entries }
@@ -14,8 +50,109 @@ pub async fn get(app_state: web::Data<crate::AppState>) -> HttpResponse {
     )
 }
 
+#[derive(serde::Deserialize)]
+pub struct ExportQuery {
+    /// Only entries logged after this RFC 3339 timestamp
+    since: Option<String>,
+}
+
+/// Log entries as JSON (newest first) for scraping
+#[get("/export")]
+pub async fn export(
+    app_state: web::Data<crate::AppState>,
+    query: web::Query<ExportQuery>,
+) -> HttpResponse {
+    let since = match query.since.as_deref().map(DateTime::parse_from_rfc3339) {
+        Some(Ok(since)) => Some(since.with_timezone(&Utc)),
+        Some(Err(_)) => {
+            return HttpResponse::BadRequest()
+                .json(json!({"error": "since must be an RFC 3339 timestamp"}));
+        }
+        None => None,
+    };
+
+    let entries: Vec<_> = app_state
+        .log_collector
+        .get_entries()
+        .into_iter()
+        .filter(|entry| since.is_none_or(|since| entry.timestamp > since))
+        .collect();
+    HttpResponse::Ok().json(entries)
+}
+
+#[derive(serde::Deserialize)]
+pub struct SuppressBody {
+    modules: Vec<String>,
+}
+
+/// Replace the list of modules kept out of the console
+#[post("/suppress")]
+pub async fn suppress(
+    app_state: web::Data<crate::AppState>,
+    body: web::Json<SuppressBody>,
+) -> HttpResponse {
+    app_state
+        .log_collector
+        .set_suppressed_modules(body.into_inner().modules);
+    HttpResponse::Ok().json(app_state.log_collector.suppressed_modules())
+}
+
 #[post("/clear")]
 pub async fn clear(app_state: web::Data<crate::AppState>) -> HttpResponse {
     app_state.log_collector.clear();
-    HttpResponse::Ok().json(serde_json::json!({"success": true}))
+    HttpResponse::Ok().json(json!({"success": true}))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    use crate::{logger::LogEntry, test_harness};
+
+    #[actix_web::test]
+    async fn export_test() {
+        let pool = test_harness::setup_db("admin_console_export").await;
+        let log_collector = crate::logger::LogCollector::new(1000);
+        log_collector.add_entry(Level::Info, "Before", Some("main"));
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(5)).await;
+        let since = Utc::now();
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(5)).await;
+        log_collector.add_entry(Level::Warn, "After", Some("db"));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(crate::AppState {
+                    log_collector,
+                    ..test_harness::app_state(pool, Default::default())
+                }))
+                .service(web::scope("/admin/console").service(export)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/console/export")
+            .to_request();
+        let entries: Vec<LogEntry> = test::call_and_read_body_json(&app, req).await;
+        let messages: Vec<&str> = entries.iter().map(|entry| entry.message.as_str()).collect();
+        assert_eq!(messages, vec!["After", "Before"]);
+        assert_eq!(entries[0].level, "WARN");
+        assert_eq!(entries[0].module, "db");
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/admin/console/export?since={}",
+                since.to_rfc3339().replace('+', "%2B")
+            ))
+            .to_request();
+        let entries: Vec<LogEntry> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].message, "After");
+
+        let req = test::TestRequest::get()
+            .uri("/admin/console/export?since=yesterday")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,99 @@
+use actix_web::{get, post, web, HttpResponse};
+
+use crate::{
+    db::{events::Events, forms::Forms},
+    AppState,
+};
+
+#[get("/integrity")]
+pub async fn integrity(state: web::Data<AppState>) -> HttpResponse {
+    let issues = Events::scores_integrity(&state.pool, &state.config.get())
+        .await
+        .unwrap();
+
+    HttpResponse::Ok().json(issues)
+}
+
+/// Forms with no points anywhere, and whether they were never scored or only scored zero
+#[get("/unused-forms")]
+pub async fn unused_forms(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(Forms::unused(&state.pool).await.unwrap())
+}
+
+/// Ids of events whose scores column isn't valid JSON
+#[get("/corrupt-scores")]
+pub async fn corrupt_scores(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(Events::find_corrupt_scores(&state.pool).await.unwrap())
+}
+
+/// Reset events with corrupt scores back to zero for every form
+#[post("/corrupt-scores/repair")]
+pub async fn repair_corrupt_scores(state: web::Data<AppState>) -> HttpResponse {
+    let repaired = Events::repair_corrupt_scores(&state.pool, state.config.get().empty_scores())
+        .await
+        .unwrap();
+    log::info!("Repaired corrupt scores for events {:?}", repaired);
+
+    HttpResponse::Ok().json(repaired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    use crate::{
+        configurator::parser::{Configuration, Form},
+        db::years::Years,
+        test_harness,
+    };
+
+    #[actix_web::test]
+    async fn integrity_test() {
+        let pool = test_harness::setup_db("admin_diagnostics_integrity").await;
+        Years::new("test".to_string(), "Test".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        Events::new(
+            "test-mixed-test".to_string(),
+            "Test".to_string(),
+            "test".to_string(),
+            "mixed".to_string(),
+            "test".to_string(),
+            r#"{"form2":"8"}"#.to_string(),
+        )
+        .insert(&pool)
+        .await
+        .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(
+                    pool,
+                    Configuration {
+                        forms: vec![Form {
+                            id: "form1".to_string(),
+                            name: "Form 1".to_string(),
+                            colour: "#ff0000".to_string(),
+                        }],
+                        ..Default::default()
+                    },
+                )))
+                .service(integrity),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/integrity").to_request();
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(
+            resp,
+            serde_json::json!([{
+                "event_id": "test-mixed-test",
+                "extra_forms": ["form2"],
+                "missing_forms": ["form1"]
+            }])
+        );
+    }
+}
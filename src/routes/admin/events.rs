@@ -0,0 +1,295 @@
+use actix_web::{get, post, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    configurator::{build::event_id, id_utils},
+    db::{events::Events, years::Years},
+    AppState,
+};
+
+#[derive(Deserialize)]
+pub struct NewEvent {
+    pub year_id: String,
+    pub name: String,
+    pub gender_id: String,
+    pub filter_key: String,
+}
+
+/// Add one-off events without editing the config. They are marked as manual so the next
+/// rebuild keeps them.
+#[post("")]
+pub async fn create(state: web::Data<AppState>, body: web::Json<Vec<NewEvent>>) -> HttpResponse {
+    let years = Years::all(&state.pool).await.unwrap();
+    let empty_scores = state.config.get().empty_scores();
+
+    let mut events = Vec::with_capacity(body.len());
+    for new_event in body.into_inner() {
+        let canonical = [
+            &new_event.year_id,
+            &new_event.gender_id,
+            &new_event.filter_key,
+        ]
+        .map(|id| id_utils::canonicalize(id));
+        let [year_id, gender_id, filter_key] = match canonical {
+            [Ok(year_id), Ok(gender_id), Ok(filter_key)] => [year_id, gender_id, filter_key],
+            [Err(e), _, _] | [_, Err(e), _] | [_, _, Err(e)] => {
+                return HttpResponse::BadRequest().json(json!({"error": e.to_string()}));
+            }
+        };
+        if !years.iter().any(|year| year.id == year_id) {
+            return HttpResponse::BadRequest()
+                .json(json!({"error": format!("Unknown year {}", new_event.year_id)}));
+        }
+
+        let id = event_id(&year_id, &gender_id, &filter_key).expect("parts are already canonical");
+        if events.iter().any(|event: &Events| event.id == id)
+            || Events::find_by_id(&state.pool, id.clone())
+                .await
+                .unwrap()
+                .is_some()
+        {
+            return HttpResponse::Conflict()
+                .json(json!({"error": format!("Event {} already exists", id)}));
+        }
+
+        events.push(
+            Events::new(
+                id,
+                new_event.name,
+                year_id,
+                gender_id,
+                filter_key,
+                empty_scores.clone(),
+            )
+            .with_manual(true),
+        );
+    }
+
+    let ids: Vec<String> = events.iter().map(|event| event.id.clone()).collect();
+    Events::insert_many(&state.pool, events).await.unwrap();
+    log::info!("Added manual events {:?}", ids);
+
+    HttpResponse::Created().json(ids)
+}
+
+/// Mark an event's results as final, so the score routes refuse further changes
+#[post("/{id}/lock")]
+pub async fn lock(state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    set_locked(state, path.into_inner(), true).await
+}
+
+/// Allow an event's scores to be changed again
+#[post("/{id}/unlock")]
+pub async fn unlock(state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    set_locked(state, path.into_inner(), false).await
+}
+
+async fn set_locked(state: web::Data<AppState>, id: String, locked: bool) -> HttpResponse {
+    if !Events::set_locked(&state.pool, id.clone(), locked)
+        .await
+        .unwrap()
+    {
+        return HttpResponse::NotFound().json(json!({"error": "Event not found"}));
+    }
+    log::info!(
+        "{} event {}",
+        if locked { "Locked" } else { "Unlocked" },
+        id
+    );
+    HttpResponse::NoContent().finish()
+}
+
+/// Every row of the events table as stored, with `scores` left as the raw string even when it
+/// isn't valid JSON, for checking what the database actually holds
+#[get("/events.json")]
+pub async fn raw(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(Events::all(&state.pool).await.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    use crate::{
+        configurator::{
+            parser::{Configuration, Form, Year},
+            run,
+        },
+        test_harness, utils,
+    };
+
+    fn test_config() -> Configuration {
+        Configuration {
+            genders: vec!["mixed".to_string()],
+            years: vec![Year {
+                id: "y8".to_string(),
+                name: "Y8".to_string(),
+            }],
+            forms: vec![Form {
+                id: "f1".to_string(),
+                name: "Form 1".to_string(),
+                colour: "red".to_string(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[actix_web::test]
+    async fn create_manual_events_test() {
+        let pool = test_harness::setup_db("admin_events_create").await;
+        let config = test_config();
+        run::rebuild(&config, &pool).await.unwrap();
+
+        let state = web::Data::new(test_harness::app_state(pool.clone(), config.clone()));
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/admin/events").service(create)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/events")
+            .set_json(json!([
+                {"year_id": "y8", "name": "Egg and Spoon", "gender_id": "mixed", "filter_key": "egg-spoon"},
+                {"year_id": "y8", "name": "Sack Race", "gender_id": "mixed", "filter_key": "sack"},
+            ]))
+            .to_request();
+        let ids: Vec<String> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(ids, vec!["y8-mixed-egg-spoon", "y8-mixed-sack"]);
+
+        Events::set_scores(&pool, "y8-mixed-sack".to_string(), json!({"f1": "7"}))
+            .await
+            .unwrap();
+
+        // Manual events survive a rebuild, scores included
+        run::rebuild(&config, &pool).await.unwrap();
+        let manual = Events::manual(&pool).await.unwrap();
+        assert_eq!(manual.len(), 2);
+        assert_eq!(manual[0].name, "Egg and Spoon");
+        assert_eq!(manual[1].parsed_decimal_scores()["f1"], 7.0);
+
+        let scoreboard: String = utils::render_year_scoreboard(state, "y8")
+            .await
+            .unwrap()
+            .split_whitespace()
+            .collect();
+        assert!(scoreboard.contains(r#"id="total-f1">7<"#));
+    }
+
+    #[actix_web::test]
+    async fn create_rejects_unknown_year_test() {
+        let pool = test_harness::setup_db("admin_events_unknown_year").await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(
+                    pool.clone(),
+                    test_config(),
+                )))
+                .service(web::scope("/admin/events").service(create)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/events")
+            .set_json(json!([
+                {"year_id": "y13", "name": "Sack Race", "gender_id": "mixed", "filter_key": "sack"},
+            ]))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        assert_eq!(Events::count(&pool).await.unwrap(), 0);
+    }
+
+    #[actix_web::test]
+    async fn lock_test() {
+        let pool = test_harness::setup_db("admin_events_lock").await;
+        let config = test_config();
+        run::rebuild(&config, &pool).await.unwrap();
+        Events::new(
+            "y8-mixed-sack".to_string(),
+            "Sack Race".to_string(),
+            "y8".to_string(),
+            "mixed".to_string(),
+            "sack".to_string(),
+            config.empty_scores(),
+        )
+        .insert(&pool)
+        .await
+        .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(
+                    pool.clone(),
+                    config,
+                )))
+                .service(web::scope("/admin/events").service(lock).service(unlock)),
+        )
+        .await;
+        let is_locked = || async {
+            Events::find_by_id(&pool, "y8-mixed-sack".to_string())
+                .await
+                .unwrap()
+                .unwrap()
+                .locked
+        };
+
+        let req = test::TestRequest::post()
+            .uri("/admin/events/y8-mixed-sack/lock")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NO_CONTENT);
+        assert!(is_locked().await);
+
+        let req = test::TestRequest::post()
+            .uri("/admin/events/y8-mixed-sack/unlock")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NO_CONTENT);
+        assert!(!is_locked().await);
+
+        let req = test::TestRequest::post()
+            .uri("/admin/events/missing/lock")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn raw_test() {
+        let pool = test_harness::setup_db("admin_events_raw").await;
+        Years::new("y8".to_string(), "Y8".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        let scores = r#"{"f1": "3", "f2": oops"#;
+        Events::new(
+            "y8-mixed-sack".to_string(),
+            "Sack Race".to_string(),
+            "y8".to_string(),
+            "mixed".to_string(),
+            "sack".to_string(),
+            scores.to_string(),
+        )
+        .insert(&pool)
+        .await
+        .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(pool, test_config())))
+                .service(web::scope("/admin").service(raw)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/events.json")
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body[0]["id"], "y8-mixed-sack");
+        assert_eq!(body[0]["scores"], scores);
+    }
+}
@@ -0,0 +1,23 @@
+use std::sync::atomic::Ordering;
+
+use actix_web::{post, web, HttpResponse};
+use serde_json::json;
+
+use crate::AppState;
+
+/// Turn maintenance mode on or off for every worker
+#[post("")]
+pub async fn set(state: web::Data<AppState>, body: web::Json<MaintenanceBody>) -> HttpResponse {
+    state.maintenance.store(body.enabled, Ordering::Relaxed);
+    log::info!(
+        "Maintenance mode {}",
+        if body.enabled { "enabled" } else { "disabled" }
+    );
+
+    HttpResponse::Ok().json(json!({"enabled": body.enabled}))
+}
+
+#[derive(serde::Deserialize)]
+pub struct MaintenanceBody {
+    enabled: bool,
+}
@@ -1,5 +1,11 @@
+pub mod config;
 pub mod console;
+pub mod diagnostics;
+pub mod events;
+pub mod maintenance;
+pub mod repair;
 pub mod sqlite;
+pub mod stats;
 pub mod users;
 
 use actix_web::{get, HttpResponse};
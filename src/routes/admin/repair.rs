@@ -0,0 +1,97 @@
+use actix_web::{post, web, HttpResponse};
+
+use crate::{db::repair, AppState};
+
+#[derive(serde::Deserialize)]
+pub struct RepairQuery {
+    dry_run: Option<u8>,
+}
+
+/// Delete orphaned events and sessions, reset corrupt scores and drop unconfigured forms from
+/// scores, all in one go. Locked events keep their scores and are listed in `locked_events`.
+/// With `?dry_run=1` nothing is changed, only reported.
+#[post("/repair")]
+pub async fn post(state: web::Data<AppState>, query: web::Query<RepairQuery>) -> HttpResponse {
+    let config = state.config.get();
+    let dry_run = query.dry_run.unwrap_or(0) != 0;
+    let form_ids = config.forms.iter().map(|form| form.id.clone()).collect();
+    match repair::repair(&state.pool, form_ids, config.empty_scores(), dry_run).await {
+        Ok(report) => {
+            if !dry_run && !report.is_empty() {
+                log::info!("Repaired database {:?}", report);
+            }
+            HttpResponse::Ok().json(report)
+        }
+        Err(e) => {
+            log::error!("Repair failed, nothing was changed: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({"error": e.to_string()}))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
+
+    use crate::{
+        configurator::parser::{Configuration, Form},
+        db::{events::Events, years::Years},
+        test_harness,
+    };
+
+    #[actix_web::test]
+    async fn repair_route_test() {
+        let pool = test_harness::setup_db("admin_repair_route").await;
+        Years::new("y7".to_string(), "Year 7".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        Events::new(
+            "y7-mixed-100m".to_string(),
+            "100m".to_string(),
+            "y7".to_string(),
+            "mixed".to_string(),
+            "100m".to_string(),
+            "{broken".to_string(),
+        )
+        .insert(&pool)
+        .await
+        .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(
+                    pool.clone(),
+                    Configuration {
+                        forms: vec![Form {
+                            id: "f1".to_string(),
+                            name: "Form 1".to_string(),
+                            colour: "red".to_string(),
+                        }],
+                        ..Default::default()
+                    },
+                )))
+                .service(web::scope("/admin").service(post)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/admin/repair?dry_run=1")
+            .to_request();
+        let report: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report["dry_run"], true);
+        assert_eq!(report["corrupt_scores"], json!(["y7-mixed-100m"]));
+        assert_eq!(
+            Events::find_corrupt_scores(&pool).await.unwrap(),
+            vec!["y7-mixed-100m"]
+        );
+
+        let req = test::TestRequest::post().uri("/admin/repair").to_request();
+        let report: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(report["dry_run"], false);
+        assert_eq!(report["corrupt_scores"], json!(["y7-mixed-100m"]));
+        assert!(Events::find_corrupt_scores(&pool).await.unwrap().is_empty());
+    }
+}
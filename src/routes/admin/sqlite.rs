@@ -1,20 +1,50 @@
 use actix_web::{get, post, web, HttpResponse};
 use askama::Template;
+use async_sqlite::rusqlite::{types::ValueRef, Connection};
 use serde::{Deserialize, Serialize};
-use std::process::Command;
+use serde_json::Value;
 
-use crate::templates::AdminSqliteTemplate;
+use crate::{db::timed_conn, templates::AdminSqliteTemplate};
+
+/// Rows returned by a single query, the rest are dropped and `truncated` is set
+const MAX_ROWS: usize = 1000;
+
+/// Statements allowed without `allow_writes`
+const READ_ONLY_KEYWORDS: [&str; 2] = ["SELECT", "EXPLAIN"];
+
+/// Statements blocked even with `allow_writes`, they can destroy data the app relies on or
+/// reach outside the database
+const DANGEROUS_KEYWORDS: [&str; 4] = ["ATTACH", "DETACH", "DROP", "VACUUM"];
 
 #[derive(Serialize, Deserialize)]
 pub struct SqliteCommand {
     query: String,
+    /// Run statements that change the database, otherwise only `SELECT` and `EXPLAIN` are
+    /// accepted
+    #[serde(default)]
+    allow_writes: bool,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct SqliteResult {
     success: bool,
+    /// Rows as `|` separated text for the console
     output: String,
     error: Option<String>,
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+    /// Set for statements that don't return rows
+    rows_affected: Option<usize>,
+    truncated: bool,
+}
+
+impl SqliteResult {
+    fn error(error: impl Into<String>) -> Self {
+        Self {
+            error: Some(error.into()),
+            ..Default::default()
+        }
+    }
 }
 
 #[get("")]
@@ -28,56 +58,164 @@ pub async fn get() -> HttpResponse {
     )
 }
 
+/// Run one statement against the app's database pool
 #[post("/execute")]
 pub async fn execute(
-    _app_state: web::Data<crate::AppState>,
+    app_state: web::Data<crate::AppState>,
     cmd: web::Json<SqliteCommand>,
 ) -> HttpResponse {
-    // Get the database path from the environment or use default
-    let db_path = std::env::var("DB_URL").unwrap_or_else(|_| "./db.sqlite".to_string());
-
-    // Validate the command to prevent dangerous operations
-    let query = cmd.query.trim();
+    let cmd = cmd.into_inner();
 
     // Block potentially dangerous commands
-    if is_dangerous_command(query) {
-        return HttpResponse::BadRequest().json(SqliteResult {
-            success: false,
-            output: String::new(),
-            error: Some("Dangerous command blocked for security reasons".to_string()),
-        });
-    }
-
-    // Execute the SQLite command
-    let output = Command::new("sqlite3").arg(&db_path).arg(query).output();
-
-    match output {
-        Ok(result) => {
-            let stdout = String::from_utf8_lossy(&result.stdout);
-            let stderr = String::from_utf8_lossy(&result.stderr);
-
-            if result.status.success() {
-                HttpResponse::Ok().json(SqliteResult {
-                    success: true,
-                    output: stdout.to_string(),
-                    error: None,
-                })
-            } else {
-                HttpResponse::Ok().json(SqliteResult {
-                    success: false,
-                    output: stdout.to_string(),
-                    error: Some(stderr.to_string()),
+    if is_dangerous_command(&cmd.query) {
+        return HttpResponse::BadRequest().json(SqliteResult::error(
+            "Dangerous command blocked for security reasons",
+        ));
+    }
+
+    let query = match expand_dot_command(cmd.query.trim()) {
+        Ok(query) => query,
+        Err(e) => return HttpResponse::BadRequest().json(SqliteResult::error(e)),
+    };
+    let keyword = leading_keyword(&query);
+    if !cmd.allow_writes && !READ_ONLY_KEYWORDS.contains(&keyword.as_str()) {
+        return HttpResponse::BadRequest().json(SqliteResult::error(
+            "Only SELECT and EXPLAIN are allowed without allow_writes",
+        ));
+    }
+
+    let allow_writes = cmd.allow_writes;
+    match timed_conn(&app_state.pool, move |conn| {
+        Ok(run_query(conn, &query, allow_writes))
+    })
+    .await
+    {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(e) => HttpResponse::InternalServerError().json(SqliteResult::error(format!(
+            "Failed to execute command: {}",
+            e
+        ))),
+    }
+}
+
+fn run_query(conn: &Connection, query: &str, allow_writes: bool) -> SqliteResult {
+    // `prepare` refuses more than one statement, so nothing can hide behind a `;`
+    let mut stmt = match conn.prepare(query) {
+        Ok(stmt) => stmt,
+        Err(e) => return SqliteResult::error(e.to_string()),
+    };
+    // Ask SQLite as well, in case a write hides behind a read-only leading keyword
+    if !allow_writes && !stmt.readonly() {
+        return SqliteResult::error("Statement would change the database");
+    }
+
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    if columns.is_empty() {
+        return match stmt.execute([]) {
+            Ok(rows_affected) => SqliteResult {
+                success: true,
+                rows_affected: Some(rows_affected),
+                ..Default::default()
+            },
+            Err(e) => SqliteResult::error(e.to_string()),
+        };
+    }
+
+    let mut result = SqliteResult {
+        success: true,
+        columns,
+        ..Default::default()
+    };
+    let mut rows = match stmt.query([]) {
+        Ok(rows) => rows,
+        Err(e) => return SqliteResult::error(e.to_string()),
+    };
+    loop {
+        let row = match rows.next() {
+            Ok(Some(row)) => row,
+            Ok(None) => break,
+            Err(e) => return SqliteResult::error(e.to_string()),
+        };
+        if result.rows.len() == MAX_ROWS {
+            result.truncated = true;
+            break;
+        }
+        let values = (0..result.columns.len())
+            .map(|i| match row.get_ref(i) {
+                Ok(value) => to_json(value),
+                Err(_) => Value::Null,
+            })
+            .collect();
+        result.rows.push(values);
+    }
+
+    result.output = std::iter::once(result.columns.join("|"))
+        .chain(result.rows.iter().map(|row| {
+            row.iter()
+                .map(|value| match value {
+                    Value::String(text) => text.clone(),
+                    Value::Null => String::new(),
+                    other => other.to_string(),
                 })
-            }
+                .collect::<Vec<_>>()
+                .join("|")
+        }))
+        .collect::<Vec<_>>()
+        .join("\n");
+    result
+}
+
+fn to_json(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => i.into(),
+        ValueRef::Real(f) => f.into(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into(),
+        ValueRef::Blob(blob) => format!("<blob {} bytes>", blob.len()).into(),
+    }
+}
+
+/// The `.tables` and `.schema` shortcuts from the sqlite3 shell as plain queries
+fn expand_dot_command(query: &str) -> Result<String, String> {
+    let Some(command) = query.strip_prefix('.') else {
+        return Ok(query.to_string());
+    };
+    let mut parts = command.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("tables"), None) => Ok(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
+                .to_string(),
+        ),
+        (Some("schema"), None) => {
+            Ok("SELECT sql FROM sqlite_master WHERE sql IS NOT NULL ORDER BY name".to_string())
         }
-        Err(e) => HttpResponse::InternalServerError().json(SqliteResult {
-            success: false,
-            output: String::new(),
-            error: Some(format!("Failed to execute command: {}", e)),
-        }),
+        (Some("schema"), Some(table)) => Ok(format!(
+            "SELECT sql FROM sqlite_master WHERE sql IS NOT NULL AND tbl_name = '{}'",
+            table.replace('\'', "''")
+        )),
+        _ => Err(format!("Unsupported command .{}", command)),
     }
 }
 
+/// First keyword of a statement in upper case, skipping whitespace and comments
+fn leading_keyword(query: &str) -> String {
+    let mut rest = query.trim_start();
+    loop {
+        if let Some(comment) = rest.strip_prefix("--") {
+            rest = comment.split_once('\n').map_or("", |(_, after)| after);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.split_once("*/").map_or("", |(_, after)| after);
+        } else {
+            break;
+        }
+        rest = rest.trim_start();
+    }
+    rest.chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
 fn is_dangerous_command(query: &str) -> bool {
     let query_lower = query.to_lowercase();
 
@@ -102,5 +240,121 @@ fn is_dangerous_command(query: &str) -> bool {
         }
     }
 
-    false
+    DANGEROUS_KEYWORDS.contains(&leading_keyword(query).as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::json;
+
+    use crate::{db::years::Years, test_harness};
+
+    async fn execute_json(
+        db_name: &str,
+        requests: Vec<Value>,
+    ) -> (async_sqlite::Pool, Vec<(StatusCode, Value)>) {
+        let pool = test_harness::setup_db(db_name).await;
+        Years::new("y7".to_string(), "Year 7".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(
+                    pool.clone(),
+                    Default::default(),
+                )))
+                .service(web::scope("/admin/sqlite").service(execute)),
+        )
+        .await;
+
+        let mut responses = vec![];
+        for body in requests {
+            let req = test::TestRequest::post()
+                .uri("/admin/sqlite/execute")
+                .set_json(body)
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            let status = resp.status();
+            responses.push((status, test::read_body_json(resp).await));
+        }
+        (pool, responses)
+    }
+
+    #[actix_web::test]
+    async fn test_leading_keyword() {
+        assert_eq!(leading_keyword("  select 1"), "SELECT");
+        assert_eq!(leading_keyword("-- note\n/* x */ Drop table years"), "DROP");
+        assert_eq!(leading_keyword(""), "");
+    }
+
+    #[actix_web::test]
+    async fn test_is_dangerous_command() {
+        assert!(is_dangerous_command("DROP TABLE years"));
+        assert!(is_dangerous_command("/* hidden */ drop table years"));
+        assert!(is_dangerous_command("ATTACH DATABASE 'x.db' AS x"));
+        assert!(is_dangerous_command(".shell ls"));
+        assert!(!is_dangerous_command("SELECT * FROM years"));
+    }
+
+    // Runs against the test pool rather than DB_URL, so no sqlite3 binary is involved
+    #[actix_web::test]
+    async fn test_select_is_allowed() {
+        let (_, responses) = execute_json(
+            "admin_sqlite_select",
+            vec![json!({"query": "SELECT id, name FROM years"})],
+        )
+        .await;
+
+        let (status, body) = &responses[0];
+        assert_eq!(*status, StatusCode::OK);
+        assert_eq!(body["success"], true);
+        assert_eq!(body["columns"], json!(["id", "name"]));
+        assert_eq!(body["rows"], json!([["y7", "Year 7"]]));
+        assert_eq!(body["output"], "id|name\ny7|Year 7");
+    }
+
+    #[actix_web::test]
+    async fn test_drop_is_blocked() {
+        let (pool, responses) = execute_json(
+            "admin_sqlite_drop",
+            vec![json!({"query": "DROP TABLE years", "allow_writes": true})],
+        )
+        .await;
+
+        assert_eq!(responses[0].0, StatusCode::BAD_REQUEST);
+        assert_eq!(Years::all(&pool).await.unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_writes_need_allow_writes() {
+        let (pool, responses) = execute_json(
+            "admin_sqlite_writes",
+            vec![
+                json!({"query": "DELETE FROM years"}),
+                json!({"query": "SELECT 1; DELETE FROM years"}),
+                json!({"query": "UPDATE years SET name = 'Y7'", "allow_writes": true}),
+            ],
+        )
+        .await;
+
+        assert_eq!(responses[0].0, StatusCode::BAD_REQUEST);
+        // Only one statement is run at a time
+        assert_eq!(responses[1].1["success"], false);
+        assert_eq!(responses[2].0, StatusCode::OK);
+        assert_eq!(responses[2].1["rows_affected"], 1);
+        assert_eq!(Years::all(&pool).await.unwrap()[0].name, "Y7");
+    }
+
+    #[actix_web::test]
+    async fn test_tables_shortcut() {
+        let (_, responses) =
+            execute_json("admin_sqlite_tables", vec![json!({"query": ".tables"})]).await;
+
+        let (status, body) = &responses[0];
+        assert_eq!(*status, StatusCode::OK);
+        assert!(body["rows"].as_array().unwrap().contains(&json!(["years"])));
+    }
 }
@@ -0,0 +1,113 @@
+use actix_web::{get, web, HttpResponse};
+use serde::Serialize;
+
+use crate::{
+    db::{events::Events, user_sessions::UserSessions, users::Users, years::Years},
+    AppState,
+};
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Stats {
+    pub years: i64,
+    pub forms: usize,
+    pub genders: usize,
+    pub configured_events: usize,
+    pub generated_events: i64,
+    pub users: i64,
+    pub sessions: i64,
+}
+
+/// Headline numbers for the admin dashboard, counted in the DB rather than loading rows
+#[get("/stats.json")]
+pub async fn get(state: web::Data<AppState>) -> HttpResponse {
+    let config = state.config.get();
+    let pool = &state.pool;
+    HttpResponse::Ok().json(Stats {
+        years: Years::count(pool).await.unwrap(),
+        forms: config.forms.len(),
+        genders: config.genders.len(),
+        configured_events: config.events.len(),
+        generated_events: Events::count(pool).await.unwrap(),
+        users: Users::count(pool).await.unwrap(),
+        sessions: UserSessions::count(pool).await.unwrap(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use serde_json::json;
+
+    use crate::{
+        configurator::{
+            parser::{ApplicabilityRules, Configuration, Event, Form, Year},
+            run,
+        },
+        test_harness,
+    };
+
+    #[actix_web::test]
+    async fn stats_test() {
+        let pool = test_harness::setup_db("admin_stats").await;
+        let config = Configuration {
+            genders: vec!["boys".to_string(), "girls".to_string()],
+            years: vec![
+                Year {
+                    id: "y7".to_string(),
+                    name: "Y7".to_string(),
+                },
+                Year {
+                    id: "y8".to_string(),
+                    name: "Y8".to_string(),
+                },
+            ],
+            forms: vec![Form {
+                id: "f1".to_string(),
+                name: "Form 1".to_string(),
+                colour: "red".to_string(),
+            }],
+            events: vec![Event {
+                id: "100m".to_string(),
+                name: "100m".to_string(),
+                applicable_years: ApplicabilityRules::All,
+                applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
+            }],
+            ..Default::default()
+        };
+        run::rebuild(&config, &pool).await.unwrap();
+        Users::new("example@example.com".to_string(), true, true)
+            .insert(&pool)
+            .await
+            .unwrap();
+        UserSessions::new(1, true, true)
+            .insert(&pool)
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(pool, config)))
+                .service(web::scope("/admin").service(get)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/stats.json")
+            .to_request();
+        let stats: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            stats,
+            json!({
+                "years": 2,
+                "forms": 1,
+                "genders": 2,
+                "configured_events": 1,
+                "generated_events": 4,
+                "users": 1,
+                "sessions": 1,
+            })
+        );
+    }
+}
@@ -1,8 +1,9 @@
-use actix_web::{get, post, web, HttpResponse};
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
 use askama::Template;
 
 use crate::{
     db,
+    routes::oauth::end_user_sessions,
     templates::{AdminUsersEditTemplate, AdminUsersListTemplate, AdminUsersNewTemplate},
     ternary, AppState,
 };
@@ -38,7 +39,7 @@ pub async fn create(state: web::Data<AppState>, params: web::Form<UpdateProps>)
     .await
     .unwrap();
     HttpResponse::Found()
-        .append_header(("Location", "/admin/users"))
+        .append_header(("Location", state.settings.url("/admin/users")))
         .finish()
 }
 
@@ -59,21 +60,72 @@ pub async fn edit(state: web::Data<AppState>, params: web::Path<PathProps>) -> H
 #[post("/edit/{id}")]
 pub async fn update(
     state: web::Data<AppState>,
+    req: HttpRequest,
     path: web::Path<PathProps>,
     body: web::Form<UpdateProps>,
 ) -> HttpResponse {
+    let has_admin = ternary!(body.has_admin == Some("on".to_string()) => true, false);
+    let has_set_score = ternary!(body.has_set_score  == Some("on".to_string()) => true, false);
+    if !has_admin
+        && db::users::Users::is_last_admin(&state.pool, path.id)
+            .await
+            .unwrap()
+    {
+        return HttpResponse::Conflict().body("Cannot remove admin from the last remaining admin");
+    }
+
+    let before = db::users::Users::find_by_id(path.id, &state.pool)
+        .await
+        .unwrap();
     db::users::Users::update(
         &state.pool,
         path.id,
         body.email.clone(),
-        ternary!(body.has_admin == Some("on".to_string()) => true, false),
-        ternary!(body.has_set_score  == Some("on".to_string()) => true, false),
+        has_admin,
+        has_set_score,
     )
     .await
     .unwrap();
 
+    let mut resp = HttpResponse::Found();
+    resp.append_header(("Location", state.settings.url("/admin/users")));
+    if let Some(before) = before
+        .filter(|before| (before.has_admin, before.has_set_score) != (has_admin, has_set_score))
+    {
+        let user = db::users::Users {
+            email: body.email.clone(),
+            has_admin,
+            has_set_score,
+            ..before
+        };
+        if let Some(cookie) = end_user_sessions(&state, &req, &user).await {
+            resp.cookie(cookie);
+        }
+    }
+    resp.finish()
+}
+
+/// Remove a user and their sessions, unless they are the last admin
+#[post("/delete/{id}")]
+pub async fn delete(state: web::Data<AppState>, path: web::Path<PathProps>) -> HttpResponse {
+    if db::users::Users::is_last_admin(&state.pool, path.id)
+        .await
+        .unwrap()
+    {
+        return HttpResponse::Conflict().body("Cannot delete the last remaining admin");
+    }
+
+    if !db::users::Users::delete(&state.pool, path.id)
+        .await
+        .unwrap()
+    {
+        return HttpResponse::NotFound().body("User not found");
+    }
+    // The store may keep sessions outside the users table
+    state.sessions.delete_for_user(path.id).await.unwrap();
+    log::info!("Deleted user {}", path.id);
     HttpResponse::Found()
-        .append_header(("Location", "/admin/users"))
+        .append_header(("Location", state.settings.url("/admin/users")))
         .finish()
 }
 
@@ -88,3 +140,79 @@ struct UpdateProps {
 struct PathProps {
     id: i64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    use crate::{db::users::Users, session_store::SessionStore, test_harness};
+
+    #[actix_web::test]
+    async fn delete_test() {
+        let pool = test_harness::setup_db("admin_users_delete").await;
+        let admin = Users::ensure_admin("admin@example.com".to_string(), &pool)
+            .await
+            .unwrap();
+        let scorer = Users::get_or_create("scorer@example.com".to_string(), &pool)
+            .await
+            .unwrap();
+        let sessions = crate::session_store::MemorySessionStore::default();
+        let old_session = sessions.insert(scorer.clone().new_session()).await.unwrap();
+        let state = web::Data::new(AppState {
+            sessions: Box::new(sessions),
+            ..test_harness::app_state(pool.clone(), Default::default())
+        });
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/admin/users").service(delete)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/admin/users/delete/{}", scorer.id.unwrap()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(Users::all(&pool).await.unwrap(), vec![admin]);
+        assert!(
+            !state
+                .sessions
+                .verify(old_session.id)
+                .await
+                .unwrap()
+                .verified
+        );
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/admin/users/delete/{}", scorer.id.unwrap()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn delete_last_admin_test() {
+        let pool = test_harness::setup_db("admin_users_delete_last_admin").await;
+        let admin = Users::ensure_admin("admin@example.com".to_string(), &pool)
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(
+                    pool.clone(),
+                    Default::default(),
+                )))
+                .service(web::scope("/admin/users").service(delete)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/admin/users/delete/{}", admin.id.unwrap()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        assert_eq!(Users::admin_count(&pool).await.unwrap(), 1);
+    }
+}
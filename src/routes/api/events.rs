@@ -0,0 +1,309 @@
+use actix_web::{get, http::header, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+
+use crate::{db::events::Events, utils, AppState};
+
+/// A page of events in display order, optionally only those with `tag`, where some form scored
+/// at least `min_score`, or with `unscored=1` those nobody has scored yet, answering `If-Modified-Since` with a 304 when nothing has changed since
+#[get("")]
+pub async fn index(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<IndexQuery>,
+    page: web::Query<utils::PageQuery>,
+) -> HttpResponse {
+    let pagination = match utils::Pagination::from_query(
+        &page,
+        state.settings.default_per_page,
+        state.settings.max_per_page,
+    ) {
+        Ok(pagination) => pagination,
+        Err(res) => return res,
+    };
+
+    let last_modified = Events::last_modified(&state.pool).await.unwrap();
+    if let Some(last_modified) = last_modified {
+        if utils::is_not_modified(&req, last_modified) {
+            return HttpResponse::NotModified().finish();
+        }
+    }
+
+    let mut events = Events::r#where(&state.pool, None, None, None, query.tag.clone())
+        .await
+        .unwrap();
+    if let Some(min_score) = query.min_score {
+        events.retain(|event| {
+            event
+                .parsed_decimal_scores()
+                .values()
+                .any(|score| *score >= min_score)
+        });
+    }
+    if query.unscored.unwrap_or(0) != 0 {
+        events.retain(|event| !event.is_scored());
+    }
+    utils::sort_events(&state.config.get(), &mut events);
+
+    let mut res = HttpResponse::Ok();
+    if let Some(last_modified) = last_modified {
+        res.insert_header(header::LastModified(utils::last_modified_header(
+            last_modified,
+        )));
+    }
+    res.json(pagination.apply(events))
+}
+
+/// Events changed after `since` (RFC 3339), so displays can poll for deltas
+#[get("/changes")]
+pub async fn changes(state: web::Data<AppState>, query: web::Query<ChangesQuery>) -> HttpResponse {
+    let since = match DateTime::parse_from_rfc3339(&query.since) {
+        Ok(since) => since.with_timezone(&Utc),
+        Err(_) => {
+            return HttpResponse::BadRequest()
+                .json(json!({"error": "since must be an RFC 3339 timestamp"}));
+        }
+    };
+
+    HttpResponse::Ok().json(Events::changed_since(&state.pool, since).await.unwrap())
+}
+
+#[derive(serde::Deserialize)]
+pub struct IndexQuery {
+    tag: Option<String>,
+    min_score: Option<f64>,
+    unscored: Option<u8>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ChangesQuery {
+    since: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::Value;
+
+    use crate::{db::years::Years, test_harness};
+
+    #[actix_web::test]
+    async fn changes_test() {
+        let pool = test_harness::setup_db("api_events_changes").await;
+        Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        for id in ["old", "new"] {
+            Events::new(
+                id.to_string(),
+                "60m".to_string(),
+                "y9".to_string(),
+                "boys".to_string(),
+                "60m".to_string(),
+                "{}".to_string(),
+            )
+            .insert(&pool)
+            .await
+            .unwrap();
+        }
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(5)).await;
+        let since = Utc::now();
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(5)).await;
+        Events::set_scores(&pool, "new".to_string(), json!({"a": "3"}))
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(
+                    pool,
+                    Default::default(),
+                )))
+                .service(web::scope("/api/events").service(changes)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/api/events/changes?since={}",
+                since.to_rfc3339().replace('+', "%2B")
+            ))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        let events = body.as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["id"], "new");
+
+        let req = test::TestRequest::get()
+            .uri("/api/events/changes?since=yesterday")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn index_min_score_test() {
+        let pool = test_harness::setup_db("api_events_index_min_score").await;
+        Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        for (id, scores) in [
+            ("y9-boys-60m", json!({"a": "3", "b": "8"})),
+            ("y9-boys-relay", json!({"a": "7.5", "b": ""})),
+            ("y9-boys-shot", json!({"a": "2", "b": "not a score"})),
+            ("y9-boys-javelin", json!({"a": 0, "b": 0})),
+        ] {
+            Events::new(
+                id.to_string(),
+                id.to_string(),
+                "y9".to_string(),
+                "boys".to_string(),
+                id.to_string(),
+                scores.to_string(),
+            )
+            .insert(&pool)
+            .await
+            .unwrap();
+        }
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(
+                    pool,
+                    Default::default(),
+                )))
+                .service(web::scope("/api/events").service(index)),
+        )
+        .await;
+
+        let ids = |body: Value| -> Vec<String> {
+            body.as_array()
+                .unwrap()
+                .iter()
+                .map(|event| event["id"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        let req = test::TestRequest::get()
+            .uri("/api/events?min_score=7.5")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(ids(body), vec!["y9-boys-60m", "y9-boys-relay"]);
+
+        let req = test::TestRequest::get()
+            .uri("/api/events?min_score=9")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(ids(body).is_empty());
+
+        let req = test::TestRequest::get()
+            .uri("/api/events?unscored=1")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(ids(body), vec!["y9-boys-javelin"]);
+
+        let req = test::TestRequest::get()
+            .uri("/api/events?min_score=lots")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn index_last_modified_test() {
+        let pool = test_harness::setup_db("api_events_index_last_modified").await;
+        Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        Events::new(
+            "y9-boys-60m".to_string(),
+            "60m".to_string(),
+            "y9".to_string(),
+            "boys".to_string(),
+            "60m".to_string(),
+            "{}".to_string(),
+        )
+        .insert(&pool)
+        .await
+        .unwrap();
+        // As if the event was saved part way through a second, long enough ago to have passed
+        let saved_at = "2024-06-01T10:00:00.400Z";
+        let set_modified = |at: &'static str| {
+            let pool = pool.clone();
+            async move {
+                pool.conn(move |conn| conn.execute("UPDATE modified SET at = ?1", [at]))
+                    .await
+                    .unwrap();
+            }
+        };
+        set_modified(saved_at).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(
+                    pool.clone(),
+                    Default::default(),
+                )))
+                .service(web::scope("/api/events").service(index)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/events").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let last_modified = resp
+            .headers()
+            .get(header::LAST_MODIFIED)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(last_modified, "Sat, 01 Jun 2024 10:00:01 GMT");
+
+        // At the latest change
+        let req = test::TestRequest::get()
+            .uri("/api/events")
+            .insert_header((header::IF_MODIFIED_SINCE, last_modified.clone()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+
+        // Saved again once that second had started, after the response was sent
+        set_modified("2024-06-01T10:00:01.000Z").await;
+        let req = test::TestRequest::get()
+            .uri("/api/events")
+            .insert_header((header::IF_MODIFIED_SINCE, last_modified.clone()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Deleting an event changes the list too
+        set_modified(saved_at).await;
+        Events::delete(&pool, "y9-boys-60m".to_string())
+            .await
+            .unwrap();
+        let req = test::TestRequest::get()
+            .uri("/api/events")
+            .insert_header((header::IF_MODIFIED_SINCE, last_modified))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Changed within this second, so the next second would be in the future
+        let req = test::TestRequest::get().uri("/api/events").to_request();
+        let resp = test::call_service(&app, req).await;
+        let last_modified: header::HttpDate = resp
+            .headers()
+            .get(header::LAST_MODIFIED)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(std::time::SystemTime::from(last_modified) <= std::time::SystemTime::now());
+    }
+}
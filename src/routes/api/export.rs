@@ -0,0 +1,163 @@
+use actix_web::{get, web, HttpResponse};
+use serde::Serialize;
+
+use crate::{db::events::Events, utils, AppState};
+
+/// Version of the county export schema, bumped whenever its shape changes
+pub const COUNTY_SCHEMA_VERSION: u32 = 2;
+
+/// Results in the format expected by the county database
+///
+/// ```json
+/// {
+///   "schema_version": 2,
+///   "school_id": "example-school",
+///   "events": [
+///     {
+///       "event_id": "y9-boys-60m",
+///       "event_name": "60m",
+///       "year_id": "y9",
+///       "gender_id": "boys",
+///       "placements": [{ "position": 1, "form_id": "w", "score": 8.0 }]
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CountyExport {
+    pub schema_version: u32,
+    pub school_id: String,
+    pub events: Vec<CountyEvent>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CountyEvent {
+    pub event_id: String,
+    pub event_name: String,
+    pub year_id: String,
+    pub gender_id: String,
+    /// Highest score first, forms on equal scores share a position
+    pub placements: Vec<CountyPlacement>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CountyPlacement {
+    pub position: usize,
+    pub form_id: String,
+    pub score: f64,
+}
+
+impl CountyEvent {
+    fn from_event(event: Events) -> Self {
+        let placements = utils::rank_totals(&event.parsed_decimal_scores())
+            .into_iter()
+            .map(|(form_id, score, position)| CountyPlacement {
+                position,
+                form_id,
+                score,
+            })
+            .collect();
+
+        Self {
+            event_id: event.id,
+            event_name: event.name,
+            year_id: event.year_id,
+            gender_id: event.gender_id,
+            placements,
+        }
+    }
+}
+
+#[get("/county")]
+pub async fn county(state: web::Data<AppState>) -> HttpResponse {
+    let mut events = Events::all(&state.pool).await.unwrap();
+    events.sort_by(|a, b| a.id.cmp(&b.id));
+
+    HttpResponse::Ok().json(CountyExport {
+        schema_version: COUNTY_SCHEMA_VERSION,
+        school_id: state.config.get().school_id.clone(),
+        events: events.into_iter().map(CountyEvent::from_event).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
+
+    use crate::{db::years::Years, test_harness};
+
+    #[actix_web::test]
+    async fn county_export_schema_test() {
+        let pool = test_harness::setup_db("api_export_county").await;
+        Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        Events::new(
+            "y9-boys-60m".to_string(),
+            "60m".to_string(),
+            "y9".to_string(),
+            "boys".to_string(),
+            "60m".to_string(),
+            json!({"w": "8", "e": "4", "s": "8", "t": ""}).to_string(),
+        )
+        .insert(&pool)
+        .await
+        .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(
+                    pool,
+                    crate::configurator::parser::Configuration {
+                        version: "1.0.0".to_string(),
+                        genders: vec![],
+                        scores: vec![],
+                        years: vec![],
+                        forms: vec![],
+                        events: vec![],
+                        school_id: "example-school".to_string(),
+                        ..Default::default()
+                    },
+                )))
+                .service(web::scope("/api/export").service(county)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/export/county")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+
+        // Every documented key is present with the documented type
+        assert_eq!(body["schema_version"], json!(COUNTY_SCHEMA_VERSION));
+        assert_eq!(body["school_id"], json!("example-school"));
+        let events = body["events"].as_array().unwrap();
+        assert_eq!(events.len(), 1);
+
+        let event = events[0].as_object().unwrap();
+        let mut keys: Vec<&String> = event.keys().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![
+                "event_id",
+                "event_name",
+                "gender_id",
+                "placements",
+                "year_id"
+            ]
+        );
+        assert_eq!(event["event_id"], json!("y9-boys-60m"));
+        assert_eq!(
+            event["placements"],
+            json!([
+                {"position": 1, "form_id": "s", "score": 8.0},
+                {"position": 1, "form_id": "w", "score": 8.0},
+                {"position": 3, "form_id": "e", "score": 4.0},
+            ])
+        );
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::{BTreeMap, HashMap};
+
+use actix_web::{get, web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    db::events::{format_timestamp, parse_decimal_scores, Events, ScoreHistoryEntry},
+    utils, AppState,
+};
+
+/// Everything the scoreboard knows about one form
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FormDetail {
+    pub id: String,
+    pub name: String,
+    pub colour: String,
+    /// Year id to the form's total in that year, for every year with events
+    pub year_totals: BTreeMap<String, f64>,
+    pub total: f64,
+    /// Overall position, ties share a position
+    pub position: usize,
+}
+
+/// A form's colour, totals and position, for drill-down views
+#[get("/{id}")]
+pub async fn get(state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    let form_id = path.into_inner();
+    let config = state.config.get();
+    let Some(form) = config.forms.iter().find(|form| form.id == form_id) else {
+        return HttpResponse::NotFound().body("Form not found");
+    };
+
+    let data = utils::compute_scoreboard(&state).await;
+    let year_totals = data
+        .year_totals
+        .keys()
+        .map(|year_id| {
+            let total = data
+                .year_form_scores
+                .get(year_id)
+                .and_then(|scores| scores.get(&form.id))
+                .copied()
+                .unwrap_or(0.0);
+            (year_id.clone(), total)
+        })
+        .collect();
+    let (total, position) = data
+        .form_rankings
+        .iter()
+        .find(|(id, _, _)| *id == form.id)
+        .map(|(_, total, position)| (*total, *position))
+        .unwrap_or_default();
+
+    HttpResponse::Ok().json(FormDetail {
+        id: form.id.clone(),
+        name: form.name.clone(),
+        colour: form.colour.clone(),
+        year_totals,
+        total,
+        position,
+    })
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TimelinePoint {
+    /// `None` for the single point returned when no score has a timestamp
+    pub at: Option<String>,
+    pub total: f64,
+}
+
+/// Cumulative totals for a form, one point per time its score changed.
+///
+/// `history` is `Events::all_score_history`, oldest first. Each recorded change moves the total
+/// by the difference it made, so corrections show as a step down rather than being lost. Scores
+/// from before an event's first recorded change have no time and are folded into the first
+/// point, as are changes without a timestamp. An event whose scores don't match its last recorded
+/// change, such as one inserted with scores, makes up the difference at its `updated_at`. If no
+/// change has a time, the current total is returned as a single point.
+pub fn cumulative_timeline(
+    form_id: &str,
+    events: &[Events],
+    history: &[ScoreHistoryEntry],
+) -> Vec<TimelinePoint> {
+    let score_in = |scores: &str| {
+        parse_decimal_scores(scores)
+            .get(form_id)
+            .copied()
+            .unwrap_or(0.0)
+    };
+    let mut changes: HashMap<&str, Vec<&ScoreHistoryEntry>> = HashMap::new();
+    for entry in history {
+        changes.entry(&entry.event_id).or_default().push(entry);
+    }
+
+    let mut untimed = 0.0;
+    let mut timed: Vec<(DateTime<Utc>, f64)> = vec![];
+    let mut change = |at: Option<DateTime<Utc>>, by: f64| match at {
+        _ if by == 0.0 => {}
+        Some(at) => timed.push((at, by)),
+        None => untimed += by,
+    };
+    for event in events {
+        let mut score = 0.0;
+        if let Some(entries) = changes.get(event.id.as_str()) {
+            score = score_in(&entries[0].old_scores);
+            change(None, score);
+            for entry in entries {
+                let new_score = score_in(&entry.new_scores);
+                change(entry.changed_at, new_score - score);
+                score = new_score;
+            }
+        }
+        change(event.updated_at, score_in(&event.scores) - score);
+    }
+
+    if timed.is_empty() {
+        return vec![TimelinePoint {
+            at: None,
+            total: untimed,
+        }];
+    }
+
+    timed.sort_by_key(|(at, _)| *at);
+    let mut total = untimed;
+    let mut points: Vec<TimelinePoint> = vec![];
+    for (at, score) in timed {
+        total += score;
+        let at = Some(format_timestamp(at));
+        match points.last_mut() {
+            Some(last) if last.at == at => last.total = total,
+            _ => points.push(TimelinePoint { at, total }),
+        }
+    }
+    points
+}
+
+/// How a form's total grew over the day, for sparklines
+#[get("/{id}/timeline")]
+pub async fn timeline(state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    let form_id = path.into_inner();
+    if !state
+        .config
+        .get()
+        .forms
+        .iter()
+        .any(|form| form.id == form_id)
+    {
+        return HttpResponse::NotFound().body("Form not found");
+    }
+
+    let events = Events::all(&state.pool).await.unwrap();
+    let history = Events::all_score_history(&state.pool).await.unwrap();
+    HttpResponse::Ok().json(cumulative_timeline(&form_id, &events, &history))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use crate::{
+        configurator::parser::{Configuration, Form, Year},
+        test_harness,
+    };
+
+    fn event(id: &str, scores: serde_json::Value, updated_at: Option<DateTime<Utc>>) -> Events {
+        let mut event = Events::new(
+            id.to_string(),
+            id.to_string(),
+            "y8".to_string(),
+            "boys".to_string(),
+            id.to_string(),
+            scores.to_string(),
+        );
+        event.updated_at = updated_at;
+        event
+    }
+
+    #[actix_web::test]
+    async fn cumulative_timeline_test() {
+        let at = |hour| Some(Utc.with_ymd_and_hms(2025, 7, 1, hour, 0, 0).unwrap());
+        let events = vec![
+            event("relay", json!({"f1": "5", "f2": "3"}), at(14)),
+            event("100m", json!({"f1": "3", "f2": "5"}), at(10)),
+            event("javelin", json!({"f1": "0", "f2": "1"}), at(11)),
+            event("shot", json!({"f1": "2"}), at(12)),
+            event("legacy", json!({"f1": "1"}), None),
+        ];
+
+        assert_eq!(
+            cumulative_timeline("f1", &events, &[]),
+            vec![
+                TimelinePoint {
+                    at: Some("2025-07-01T10:00:00.000Z".to_string()),
+                    total: 4.0,
+                },
+                TimelinePoint {
+                    at: Some("2025-07-01T12:00:00.000Z".to_string()),
+                    total: 6.0,
+                },
+                TimelinePoint {
+                    at: Some("2025-07-01T14:00:00.000Z".to_string()),
+                    total: 11.0,
+                },
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn cumulative_timeline_replays_history_test() {
+        let at = |hour| Some(Utc.with_ymd_and_hms(2025, 7, 1, hour, 0, 0).unwrap());
+        let change =
+            |id: &str, old: serde_json::Value, new: serde_json::Value, hour| ScoreHistoryEntry {
+                event_id: id.to_string(),
+                old_scores: old.to_string(),
+                new_scores: new.to_string(),
+                changed_at: at(hour),
+            };
+        let events = vec![
+            event("100m", json!({"f1": "1"}), at(11)),
+            event("relay", json!({"f1": "5"}), at(12)),
+            event("shot", json!({"f1": "2"}), at(13)),
+        ];
+        // 100m was scored 3 then corrected to 1, relay had 1 before history was kept and shot
+        // has no history at all
+        let history = vec![
+            change("100m", json!({}), json!({"f1": "3"}), 10),
+            change("100m", json!({"f1": "3"}), json!({"f1": "1"}), 11),
+            change("relay", json!({"f1": "1"}), json!({"f1": "5"}), 12),
+            change("deleted", json!({}), json!({"f1": "9"}), 12),
+        ];
+
+        let totals: Vec<(String, f64)> = cumulative_timeline("f1", &events, &history)
+            .into_iter()
+            .map(|point| (point.at.unwrap()[11..13].to_string(), point.total))
+            .collect();
+        assert_eq!(
+            totals,
+            vec![
+                ("10".to_string(), 4.0),
+                ("11".to_string(), 2.0),
+                ("12".to_string(), 6.0),
+                ("13".to_string(), 8.0),
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn cumulative_timeline_without_timestamps_test() {
+        let events = vec![
+            event("100m", json!({"f1": "3"}), None),
+            event("relay", json!({"f1": "5"}), None),
+        ];
+        assert_eq!(
+            cumulative_timeline("f1", &events, &[]),
+            vec![TimelinePoint {
+                at: None,
+                total: 8.0
+            }]
+        );
+        assert_eq!(
+            cumulative_timeline("f2", &events, &[]),
+            vec![TimelinePoint {
+                at: None,
+                total: 0.0
+            }]
+        );
+    }
+
+    #[actix_web::test]
+    async fn get_test() {
+        let pool = test_harness::setup_db("api_forms_get").await;
+        let form = |id: &str, colour: &str| Form {
+            id: id.to_string(),
+            name: format!("Form {}", id),
+            colour: colour.to_string(),
+        };
+        let config = Configuration {
+            genders: vec!["mixed".to_string()],
+            years: vec![
+                Year {
+                    id: "y7".to_string(),
+                    name: "Y7".to_string(),
+                },
+                Year {
+                    id: "y8".to_string(),
+                    name: "Y8".to_string(),
+                },
+            ],
+            forms: vec![form("f1", "red"), form("f2", "#fdfd80")],
+            ..Default::default()
+        };
+        crate::configurator::run::rebuild(&config, &pool)
+            .await
+            .unwrap();
+        for (id, year, scores) in [
+            ("y7-mixed-100m", "y7", json!({"f1": "5", "f2": "8"})),
+            ("y8-mixed-100m", "y8", json!({"f1": "2.5", "f2": "1"})),
+        ] {
+            Events::new(
+                id.to_string(),
+                "100m".to_string(),
+                year.to_string(),
+                "mixed".to_string(),
+                "100m".to_string(),
+                scores.to_string(),
+            )
+            .insert(&pool)
+            .await
+            .unwrap();
+        }
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(pool, config)))
+                .service(web::scope("/api/forms").service(get)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/forms/f1").to_request();
+        let detail: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            detail,
+            json!({
+                "id": "f1",
+                "name": "Form f1",
+                "colour": "red",
+                "year_totals": {"y7": 5.0, "y8": 2.5},
+                "total": 7.5,
+                "position": 2,
+            })
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/api/forms/missing")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn timeline_test() {
+        let pool = test_harness::setup_db("api_forms_timeline").await;
+        let config = Configuration {
+            forms: vec![Form {
+                id: "f1".to_string(),
+                name: "Form 1".to_string(),
+                colour: "red".to_string(),
+            }],
+            ..Default::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(
+                    pool.clone(),
+                    config,
+                )))
+                .service(web::scope("/api/forms").service(timeline)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/forms/f1/timeline")
+            .to_request();
+        let points: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(points, json!([{"at": null, "total": 0.0}]));
+
+        // A correction is a step in the timeline, not lost under the latest score
+        crate::db::years::Years::new("y8".to_string(), "Year 8".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        event("100m", json!({}), None).insert(&pool).await.unwrap();
+        for score in ["3", "1"] {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            Events::set_scores(&pool, "100m".to_string(), json!({"f1": score}))
+                .await
+                .unwrap();
+        }
+        let req = test::TestRequest::get()
+            .uri("/api/forms/f1/timeline")
+            .to_request();
+        let points: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+        let totals: Vec<&serde_json::Value> = points.iter().map(|point| &point["total"]).collect();
+        assert_eq!(totals, vec![&json!(3.0), &json!(1.0)]);
+
+        let req = test::TestRequest::get()
+            .uri("/api/forms/missing/timeline")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,9 @@
+pub mod events;
+pub mod export;
+pub mod forms;
+pub mod podium;
+pub mod scoreboard;
+pub mod session;
+pub mod ticker;
+pub mod users;
+pub mod version;
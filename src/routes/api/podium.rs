@@ -0,0 +1,197 @@
+use actix_web::{get, web, HttpResponse};
+use serde::Serialize;
+
+use crate::{utils, AppState};
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct PodiumPlace {
+    pub position: usize,
+    pub form_id: String,
+    pub form_name: String,
+    pub total: f64,
+}
+
+/// Every form's overall total and position as ranked on the scoreboard, first place first. With
+/// `?reveal=1` the order is reversed so a display can count down to the winner, the positions
+/// stay the same.
+#[get("/podium")]
+pub async fn podium(state: web::Data<AppState>, query: web::Query<PodiumQuery>) -> HttpResponse {
+    let config = state.config.get();
+    let mut places: Vec<PodiumPlace> = utils::compute_scoreboard(&state)
+        .await
+        .form_rankings
+        .into_iter()
+        .map(|(form_id, total, position)| PodiumPlace {
+            position,
+            form_name: config
+                .forms
+                .iter()
+                .find(|form| form.id == form_id)
+                .map(|form| form.name.clone())
+                .unwrap_or_default(),
+            form_id,
+            total,
+        })
+        .collect();
+    if query.reveal.unwrap_or(0) != 0 {
+        places.reverse();
+    }
+
+    HttpResponse::Ok().json(places)
+}
+
+#[derive(serde::Deserialize)]
+pub struct PodiumQuery {
+    reveal: Option<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use serde_json::{json, Value};
+
+    use crate::{
+        configurator::parser::{Configuration, Form},
+        db::{events::Events, years::Years},
+        test_harness,
+    };
+
+    #[actix_web::test]
+    async fn reveal_order_test() {
+        let pool = test_harness::setup_db("api_podium_reveal").await;
+        Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        Events::new(
+            "y9-boys-60m".to_string(),
+            "60m".to_string(),
+            "y9".to_string(),
+            "boys".to_string(),
+            "60m".to_string(),
+            json!({"a": "3", "b": "8", "c": "3", "d": "1"}).to_string(),
+        )
+        .insert(&pool)
+        .await
+        .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(
+                    pool,
+                    Configuration {
+                        forms: ["a", "b", "c", "d"]
+                            .iter()
+                            .map(|id| Form {
+                                id: id.to_string(),
+                                name: id.to_uppercase(),
+                                colour: "red".to_string(),
+                            })
+                            .collect(),
+                        ..Default::default()
+                    },
+                )))
+                .service(web::scope("/api").service(podium)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/podium").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        let order: Vec<(&str, u64)> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|place| {
+                (
+                    place["form_id"].as_str().unwrap(),
+                    place["position"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(order, vec![("b", 1), ("a", 2), ("c", 2), ("d", 4)]);
+
+        let req = test::TestRequest::get()
+            .uri("/api/podium?reveal=1")
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        let places = body.as_array().unwrap();
+        let totals: Vec<f64> = places
+            .iter()
+            .map(|place| place["total"].as_f64().unwrap())
+            .collect();
+        assert_eq!(totals, vec![1.0, 3.0, 3.0, 8.0]);
+        assert_eq!(places[0]["position"], 4);
+        assert_eq!(places[3]["position"], 1);
+        assert_eq!(places[3]["form_name"], "B");
+    }
+
+    #[actix_web::test]
+    async fn matches_scoreboard_test() {
+        let pool = test_harness::setup_db("api_podium_scoreboard").await;
+        Years::new("y9".to_string(), "Year 9".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        for (gender, scores) in [
+            ("boys", json!({"a": "5", "b": "2"})),
+            ("girls", json!({"a": "0.75", "b": "4"})),
+        ] {
+            Events::new(
+                format!("y9-{}-60m", gender),
+                "60m".to_string(),
+                "y9".to_string(),
+                gender.to_string(),
+                "60m".to_string(),
+                scores.to_string(),
+            )
+            .insert(&pool)
+            .await
+            .unwrap();
+        }
+        let state = web::Data::new(test_harness::app_state(
+            pool,
+            Configuration {
+                forms: ["a", "b"]
+                    .iter()
+                    .map(|id| Form {
+                        id: id.to_string(),
+                        name: id.to_uppercase(),
+                        colour: "red".to_string(),
+                    })
+                    .collect(),
+                gender_multipliers: [("girls".to_string(), 1.5)].into_iter().collect(),
+                ..Default::default()
+            },
+        ));
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/api").service(podium)),
+        )
+        .await;
+
+        // b wins on the girls' multiplier, 2 + 4 * 1.5, and a keeps its fraction, 5 + 0.75 * 1.5
+        let req = test::TestRequest::get().uri("/api/podium").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body[0]["form_id"], "b");
+        assert_eq!(body[0]["total"], 8.0);
+        assert_eq!(body[1]["total"], 6.125);
+        let rankings: Vec<(String, f64, usize)> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|place| {
+                (
+                    place["form_id"].as_str().unwrap().to_string(),
+                    place["total"].as_f64().unwrap(),
+                    place["position"].as_u64().unwrap() as usize,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rankings,
+            utils::compute_scoreboard(&state).await.form_rankings
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use actix_web::{get, http::header, web, HttpRequest, HttpResponse};
+
+use crate::{db::events::Events, utils, AppState};
+
+/// The totals behind `/scoreboard`, for external displays and apps
+#[get("/scoreboard")]
+pub async fn scoreboard(req: HttpRequest, state: web::Data<AppState>) -> HttpResponse {
+    let last_modified = Events::last_modified(&state.pool).await.unwrap();
+    if let Some(last_modified) = last_modified {
+        if utils::is_not_modified(&req, last_modified) {
+            return HttpResponse::NotModified().finish();
+        }
+    }
+
+    let mut res = HttpResponse::Ok();
+    if let Some(last_modified) = last_modified {
+        res.insert_header(header::LastModified(utils::last_modified_header(
+            last_modified,
+        )));
+    }
+    res.json(utils::compute_scoreboard(&state).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use serde_json::json;
+
+    use crate::{
+        configurator::parser::{Configuration, Form, Year},
+        db::years::Years,
+        test_harness,
+        utils::ScoreboardData,
+    };
+
+    #[actix_web::test]
+    async fn scoreboard_test() {
+        let pool = test_harness::setup_db("api_scoreboard").await;
+        for year in ["y8", "y9"] {
+            Years::new(year.to_string(), year.to_string())
+                .insert(&pool)
+                .await
+                .unwrap();
+        }
+        for (id, year, scores) in [
+            ("y8-boys-100m", "y8", json!({"f1": "3", "f2": "5"})),
+            ("y8-girls-100m", "y8", json!({"f1": "5", "f2": "1"})),
+            ("y9-boys-100m", "y9", json!({"f1": "2", "f2": "4"})),
+        ] {
+            Events::new(
+                id.to_string(),
+                "100m".to_string(),
+                year.to_string(),
+                "boys".to_string(),
+                "100m".to_string(),
+                scores.to_string(),
+            )
+            .insert(&pool)
+            .await
+            .unwrap();
+        }
+
+        let config = Configuration {
+            years: vec![
+                Year {
+                    id: "y8".to_string(),
+                    name: "Y8".to_string(),
+                },
+                Year {
+                    id: "y9".to_string(),
+                    name: "Y9".to_string(),
+                },
+            ],
+            forms: vec![
+                Form {
+                    id: "f1".to_string(),
+                    name: "Form 1".to_string(),
+                    colour: "red".to_string(),
+                },
+                Form {
+                    id: "f2".to_string(),
+                    name: "Form 2".to_string(),
+                    colour: "blue".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(pool, config)))
+                .service(web::scope("/api").service(scoreboard)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/scoreboard").to_request();
+        let data: ScoreboardData = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(data.year_form_scores["y8"]["f1"], 8.0);
+        assert_eq!(data.year_form_scores["y8"]["f2"], 6.0);
+        assert_eq!(data.year_form_scores["y9"]["f2"], 4.0);
+        assert_eq!(data.year_totals["y8"], 14.0);
+        assert_eq!(data.year_totals["y9"], 6.0);
+        assert_eq!(data.form_totals["f1"], 10.0);
+        assert_eq!(data.form_totals["f2"], 10.0);
+        assert_eq!(data.grand_total, 20.0);
+    }
+}
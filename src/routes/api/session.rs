@@ -0,0 +1,111 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use serde_json::json;
+
+use crate::AppState;
+
+/// Whether the caller's session runs out within `within` seconds (default 300), so the page can
+/// warn before they are logged out
+#[get("/session/expiry")]
+pub async fn expiry(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<ExpiryQuery>,
+) -> HttpResponse {
+    let Some(session_id) = req.cookie("session_data").map(|c| c.value().to_string()) else {
+        return HttpResponse::Unauthorized().json(json!({"error": "Not logged in"}));
+    };
+    let within = match query.within.unwrap_or(300) {
+        within if within < 0 => None,
+        within => Duration::try_seconds(within),
+    };
+    let Some(within) = within else {
+        return HttpResponse::BadRequest()
+            .json(json!({"error": "within should be a number of seconds from 0"}));
+    };
+
+    let expires_at = state.sessions.expires_at(session_id).await.unwrap();
+    // A window reaching past the last representable time covers every expiry
+    let expiring = expires_at.is_some_and(|expires_at| {
+        Utc::now()
+            .checked_add_signed(within)
+            .is_none_or(|limit| expires_at <= limit)
+    });
+
+    HttpResponse::Ok().json(json!({
+        "expiring": expiring,
+        "expires_at": expires_at.filter(|_| expiring),
+    }))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ExpiryQuery {
+    within: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{cookie::Cookie, http::StatusCode, test, App};
+
+    use crate::{
+        db::user_sessions::UserSessions,
+        session_store::{MemorySessionStore, SessionStore},
+        test_harness,
+    };
+
+    #[actix_web::test]
+    async fn expiry_test() {
+        let sessions = MemorySessionStore::default().with_ttl(Some(Duration::hours(1)));
+        let mut old = UserSessions::new(1, true, true);
+        old.created_at = Some(Utc::now() - Duration::minutes(58));
+        let old = sessions.insert(old).await.unwrap();
+        let fresh = sessions
+            .insert(UserSessions::new(1, true, true))
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    sessions: Box::new(sessions),
+                    ..test_harness::app_state(
+                        test_harness::setup_db("api_session_expiry").await,
+                        Default::default(),
+                    )
+                }))
+                .service(web::scope("/api").service(expiry)),
+        )
+        .await;
+        let get = |uri: &str, session: &UserSessions| {
+            test::TestRequest::get()
+                .uri(uri)
+                .cookie(Cookie::new("session_data", session.id.clone()))
+                .to_request()
+        };
+
+        // Read from the memory store, not the database
+        let body: serde_json::Value =
+            test::call_and_read_body_json(&app, get("/api/session/expiry", &old)).await;
+        assert_eq!(body["expiring"], true);
+        assert!(body["expires_at"].is_string());
+
+        let body: serde_json::Value =
+            test::call_and_read_body_json(&app, get("/api/session/expiry", &fresh)).await;
+        assert_eq!(body["expiring"], false);
+
+        let body: serde_json::Value =
+            test::call_and_read_body_json(&app, get("/api/session/expiry?within=7200", &fresh))
+                .await;
+        assert_eq!(body["expiring"], true);
+
+        for within in ["-1", "9223372036854775807"] {
+            let resp = test::call_service(
+                &app,
+                get(&format!("/api/session/expiry?within={}", within), &fresh),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use actix_web::{get, web, HttpResponse};
+use serde::Serialize;
+
+use crate::{configurator::parser::Configuration, db::events::Events, utils, AppState};
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TickerLine {
+    pub event_id: String,
+    pub line: String,
+    /// The winning form's name, `None` when forms tied for first
+    pub winner: Option<String>,
+}
+
+/// Names of the forms in first place, empty if the event hasn't been scored
+pub fn leaders(config: &Configuration, event: &Events) -> Vec<String> {
+    let scores = event.parsed_decimal_scores();
+    if scores.values().all(|score| *score == 0.0) {
+        return vec![];
+    }
+
+    utils::rank_totals(&scores)
+        .into_iter()
+        .take_while(|(_, _, position)| *position == 1)
+        .map(|(form_id, _, _)| {
+            config
+                .forms
+                .iter()
+                .find(|form| form.id == form_id)
+                .map(|form| form.name.clone())
+                .unwrap_or(form_id)
+        })
+        .collect()
+}
+
+/// The top form's name, or `None` if the event is unscored or tied at the top
+pub fn event_winner(config: &Configuration, event: &Events) -> Option<String> {
+    match leaders(config, event).as_slice() {
+        [winner] => Some(winner.clone()),
+        _ => None,
+    }
+}
+
+fn ticker_line(config: &Configuration, event: &Events) -> Option<TickerLine> {
+    let leaders = leaders(config, event);
+    let result = match leaders.as_slice() {
+        [] => return None,
+        [winner] => format!("{} wins", winner),
+        [rest @ .., last] => format!("{} and {} tie", rest.join(", "), last),
+    };
+
+    let year = config
+        .years
+        .iter()
+        .find(|year| year.id == event.year_id)
+        .map(|year| year.name.clone())
+        .unwrap_or_else(|| event.year_id.clone());
+    let mut gender = event.gender_id.chars();
+    let gender = match gender.next() {
+        Some(first) => first.to_uppercase().chain(gender).collect(),
+        None => String::new(),
+    };
+
+    Some(TickerLine {
+        event_id: event.id.clone(),
+        line: format!("{} ({} {}): {}", event.name, year, gender, result),
+        winner: event_winner(config, event),
+    })
+}
+
+/// One line per scored event in display order, e.g. "100m (Year 8 Boys): Form 2 wins"
+#[get("/ticker")]
+pub async fn ticker(state: web::Data<AppState>) -> HttpResponse {
+    let config = state.config.get();
+    let mut events = Events::all(&state.pool).await.unwrap();
+    utils::sort_events(&config, &mut events);
+
+    let lines: Vec<TickerLine> = events
+        .iter()
+        .filter_map(|event| ticker_line(&config, event))
+        .collect();
+    HttpResponse::Ok().json(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+    use serde_json::json;
+
+    use crate::{
+        configurator::parser::{Form, Year},
+        db::years::Years,
+        test_harness,
+    };
+
+    #[actix_web::test]
+    async fn ticker_test() {
+        let pool = test_harness::setup_db("api_ticker").await;
+        Years::new("y8".to_string(), "Y8".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        for (id, name, scores) in [
+            ("y8-boys-100m", "100m Sprint", json!({"f1": "3", "f2": "5"})),
+            ("y8-boys-relay", "Relay", json!({"f1": "5", "f2": "5"})),
+            ("y8-boys-javelin", "Javelin", json!({"f1": "0", "f2": "0"})),
+        ] {
+            Events::new(
+                id.to_string(),
+                name.to_string(),
+                "y8".to_string(),
+                "boys".to_string(),
+                name.to_string(),
+                scores.to_string(),
+            )
+            .insert(&pool)
+            .await
+            .unwrap();
+        }
+
+        let config = Configuration {
+            years: vec![Year {
+                id: "y8".to_string(),
+                name: "Y8".to_string(),
+            }],
+            forms: vec![
+                Form {
+                    id: "f1".to_string(),
+                    name: "Form 1".to_string(),
+                    colour: "red".to_string(),
+                },
+                Form {
+                    id: "f2".to_string(),
+                    name: "Form 2".to_string(),
+                    colour: "blue".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(pool, config)))
+                .service(web::scope("/api").service(ticker)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/ticker").to_request();
+        let mut lines: Vec<serde_json::Value> = test::call_and_read_body_json(&app, req).await;
+        lines.sort_by_key(|line| line["event_id"].as_str().unwrap().to_string());
+
+        assert_eq!(
+            lines,
+            vec![
+                json!({
+                    "event_id": "y8-boys-100m",
+                    "line": "100m Sprint (Y8 Boys): Form 2 wins",
+                    "winner": "Form 2",
+                }),
+                json!({
+                    "event_id": "y8-boys-relay",
+                    "line": "Relay (Y8 Boys): Form 1 and Form 2 tie",
+                    "winner": null,
+                }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,515 @@
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use serde_json::json;
+
+use crate::{
+    db::users::Users,
+    routes::oauth::end_user_sessions,
+    utils::{PageQuery, Pagination},
+    AppState,
+};
+
+/// A page of users, ordered by id
+#[get("")]
+pub async fn list(state: web::Data<AppState>, query: web::Query<PageQuery>) -> HttpResponse {
+    let pagination = match Pagination::from_query(
+        &query,
+        state.settings.default_per_page,
+        state.settings.max_per_page,
+    ) {
+        Ok(pagination) => pagination,
+        Err(res) => return res,
+    };
+
+    let mut users = Users::all(&state.pool).await.unwrap();
+    users.sort_by_key(|user| user.id);
+    HttpResponse::Ok().json(pagination.apply(users))
+}
+
+#[put("/{id}")]
+pub async fn update(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<PathProps>,
+    body: web::Json<UpdateBody>,
+) -> HttpResponse {
+    let email = body.email.trim().to_string();
+    if !is_valid_email(&email) {
+        return HttpResponse::BadRequest().json(json!({"error": "Invalid email address"}));
+    }
+
+    let Some(before) = Users::find_by_id(path.id, &state.pool).await.unwrap() else {
+        return HttpResponse::NotFound().json(json!({"error": "User not found"}));
+    };
+
+    // Don't let the last admin demote themselves, otherwise nobody can get back into /admin
+    if !body.has_admin && Users::is_last_admin(&state.pool, path.id).await.unwrap() {
+        return HttpResponse::Conflict()
+            .json(json!({"error": "Cannot remove admin from the last remaining admin"}));
+    }
+
+    Users::update(
+        &state.pool,
+        path.id,
+        email,
+        body.has_admin,
+        body.has_set_score,
+    )
+    .await
+    .unwrap();
+
+    let user = Users::find_by_id(path.id, &state.pool)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut resp = HttpResponse::Ok();
+    if (before.has_admin, before.has_set_score) != (user.has_admin, user.has_set_score) {
+        if let Some(cookie) = end_user_sessions(&state, &req, &user).await {
+            resp.cookie(cookie);
+        }
+    }
+    resp.json(user)
+}
+
+#[delete("/{id}")]
+pub async fn delete(state: web::Data<AppState>, path: web::Path<PathProps>) -> HttpResponse {
+    if Users::is_last_admin(&state.pool, path.id).await.unwrap() {
+        return HttpResponse::Conflict()
+            .json(json!({"error": "Cannot delete the last remaining admin"}));
+    }
+
+    if !Users::delete(&state.pool, path.id).await.unwrap() {
+        return HttpResponse::NotFound().json(json!({"error": "User not found"}));
+    }
+    // The store may keep sessions outside the users table
+    state.sessions.delete_for_user(path.id).await.unwrap();
+    HttpResponse::NoContent().finish()
+}
+
+/// Grant permissions to every user with an email at a domain, e.g. all staff accounts
+#[post("/grant-domain")]
+pub async fn grant_domain(
+    state: web::Data<AppState>,
+    body: web::Json<GrantDomainBody>,
+) -> HttpResponse {
+    let domain = body.domain.trim().trim_start_matches('@').to_lowercase();
+    if !is_valid_domain(&domain) {
+        return HttpResponse::BadRequest().json(json!({"error": "Invalid domain"}));
+    }
+
+    let updated = Users::grant_by_domain(&state.pool, domain, body.has_admin, body.has_set_score)
+        .await
+        .unwrap();
+    HttpResponse::Ok().json(json!({"updated": updated}))
+}
+
+/// A plain domain name, so nothing like `*` or an empty string can match every account
+fn is_valid_domain(domain: &str) -> bool {
+    domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
+
+fn is_valid_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !domain.contains('@')
+                && !email.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct UpdateBody {
+    email: String,
+    has_admin: bool,
+    has_set_score: bool,
+}
+
+#[derive(serde::Deserialize)]
+pub struct GrantDomainBody {
+    domain: String,
+    #[serde(default)]
+    has_admin: bool,
+    #[serde(default)]
+    has_set_score: bool,
+}
+
+#[derive(serde::Deserialize)]
+struct PathProps {
+    id: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    use crate::test_harness;
+
+    async fn app_state(db_name: &str) -> web::Data<AppState> {
+        let pool = test_harness::setup_db(db_name).await;
+
+        web::Data::new(test_harness::app_state(
+            pool,
+            crate::configurator::parser::Configuration {
+                version: "1.0.0".to_string(),
+                genders: vec![],
+                scores: vec![],
+                years: vec![],
+                forms: vec![],
+                events: vec![],
+                ..Default::default()
+            },
+        ))
+    }
+
+    #[actix_web::test]
+    async fn is_valid_email_test() {
+        assert!(is_valid_email("someone@example.com"));
+        assert!(!is_valid_email("someone"));
+        assert!(!is_valid_email("@example.com"));
+        assert!(!is_valid_email("someone@example"));
+        assert!(!is_valid_email("some one@example.com"));
+    }
+
+    #[actix_web::test]
+    async fn is_valid_domain_test() {
+        assert!(is_valid_domain("staff.school.edu"));
+        assert!(!is_valid_domain(""));
+        assert!(!is_valid_domain("*"));
+        assert!(!is_valid_domain("%.edu"));
+        assert!(!is_valid_domain("school"));
+        assert!(!is_valid_domain(".edu"));
+    }
+
+    #[actix_web::test]
+    async fn list_test() {
+        let state = app_state("api_users_list").await;
+        for i in 1..=3 {
+            Users::new(format!("user{}@example.com", i), false, false)
+                .insert(&state.pool)
+                .await
+                .unwrap();
+        }
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/api/users").service(list)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/api/users?page=2&per_page=2")
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let users = body.as_array().unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0]["email"], "user3@example.com");
+
+        let req = test::TestRequest::get()
+            .uri("/api/users?per_page=-1")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn grant_domain_test() {
+        let state = app_state("api_users_grant_domain").await;
+        Users::new("teacher@staff.school.edu".to_string(), false, false)
+            .insert(&state.pool)
+            .await
+            .unwrap();
+        Users::new("pupil@school.edu".to_string(), false, false)
+            .insert(&state.pool)
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/api/users").service(grant_domain)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/users/grant-domain")
+            .set_json(json!({"domain": "@staff.school.edu", "has_set_score": true}))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["updated"], 1);
+
+        let req = test::TestRequest::post()
+            .uri("/api/users/grant-domain")
+            .set_json(json!({"domain": "*", "has_admin": true}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn update_test() {
+        let state = app_state("api_users_update").await;
+        Users::new("admin@example.com".to_string(), true, true)
+            .insert(&state.pool)
+            .await
+            .unwrap();
+        Users::new("scorer@example.com".to_string(), false, false)
+            .insert(&state.pool)
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/api/users").service(update)),
+        )
+        .await;
+
+        let req = test::TestRequest::put()
+            .uri("/api/users/2")
+            .set_json(json!({
+                "email": "scorer@example.com",
+                "has_admin": false,
+                "has_set_score": true
+            }))
+            .to_request();
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(resp["id"], 2);
+        assert_eq!(resp["has_admin"], false);
+        assert_eq!(resp["has_set_score"], true);
+        assert!(
+            Users::find_by_id(2, &state.pool)
+                .await
+                .unwrap()
+                .unwrap()
+                .has_set_score
+        );
+    }
+
+    #[actix_web::test]
+    async fn update_rotates_own_session_test() {
+        let state = app_state("api_users_update_rotates_session").await;
+        for email in ["admin@example.com", "other@example.com"] {
+            Users::new(email.to_string(), true, true)
+                .insert(&state.pool)
+                .await
+                .unwrap();
+        }
+        let user = Users::find_by_id(2, &state.pool).await.unwrap().unwrap();
+        let session = state.sessions.insert(user.new_session()).await.unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/api/users").service(update)),
+        )
+        .await;
+
+        // Someone else's permissions leave the caller's session alone
+        let req = test::TestRequest::put()
+            .uri("/api/users/1")
+            .cookie(actix_web::cookie::Cookie::new(
+                "session_data",
+                session.id.clone(),
+            ))
+            .set_json(
+                json!({"email": "admin@example.com", "has_admin": true, "has_set_score": false}),
+            )
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.response().cookies().next().is_none());
+
+        let req = test::TestRequest::put()
+            .uri("/api/users/2")
+            .cookie(actix_web::cookie::Cookie::new(
+                "session_data",
+                session.id.clone(),
+            ))
+            .set_json(
+                json!({"email": "other@example.com", "has_admin": false, "has_set_score": true}),
+            )
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let new_id = resp
+            .response()
+            .cookies()
+            .find(|cookie| cookie.name() == "session_data")
+            .unwrap()
+            .value()
+            .to_string();
+
+        assert_ne!(new_id, session.id);
+        assert!(!state.sessions.verify(session.id).await.unwrap().verified);
+        let verified = state.sessions.verify(new_id).await.unwrap();
+        assert!(verified.verified);
+        assert!(!verified.has_admin);
+    }
+
+    #[actix_web::test]
+    async fn update_ends_demoted_sessions_test() {
+        let state = app_state("api_users_update_ends_sessions").await;
+        for email in ["admin@example.com", "other@example.com"] {
+            Users::new(email.to_string(), true, true)
+                .insert(&state.pool)
+                .await
+                .unwrap();
+        }
+        let other = Users::find_by_id(2, &state.pool).await.unwrap().unwrap();
+        let old_session = state.sessions.insert(other.new_session()).await.unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/api/users").service(update)),
+        )
+        .await;
+
+        let req = test::TestRequest::put()
+            .uri("/api/users/2")
+            .set_json(
+                json!({"email": "other@example.com", "has_admin": false, "has_set_score": true}),
+            )
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        let verified = state.sessions.verify(old_session.id).await.unwrap();
+        assert!(!verified.verified);
+        assert!(!verified.has_admin);
+    }
+
+    #[actix_web::test]
+    async fn update_last_admin_test() {
+        let state = app_state("api_users_update_last_admin").await;
+        Users::new("admin@example.com".to_string(), true, true)
+            .insert(&state.pool)
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/api/users").service(update)),
+        )
+        .await;
+
+        let req = test::TestRequest::put()
+            .uri("/api/users/1")
+            .set_json(json!({
+                "email": "admin@example.com",
+                "has_admin": false,
+                "has_set_score": true
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), actix_web::http::StatusCode::CONFLICT);
+        assert!(
+            Users::find_by_id(1, &state.pool)
+                .await
+                .unwrap()
+                .unwrap()
+                .has_admin
+        );
+    }
+
+    #[actix_web::test]
+    async fn update_one_of_two_admins_test() {
+        let state = app_state("api_users_update_one_of_two_admins").await;
+        Users::new("admin@example.com".to_string(), true, true)
+            .insert(&state.pool)
+            .await
+            .unwrap();
+        Users::new("admin2@example.com".to_string(), true, true)
+            .insert(&state.pool)
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/api/users").service(update)),
+        )
+        .await;
+
+        let req = test::TestRequest::put()
+            .uri("/api/users/1")
+            .set_json(json!({
+                "email": "admin@example.com",
+                "has_admin": false,
+                "has_set_score": true
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert!(resp.status().is_success());
+        assert_eq!(Users::admin_count(&state.pool).await.unwrap(), 1);
+    }
+
+    #[actix_web::test]
+    async fn delete_last_admin_test() {
+        let state = app_state("api_users_delete_last_admin").await;
+        Users::new("admin@example.com".to_string(), true, true)
+            .insert(&state.pool)
+            .await
+            .unwrap();
+        Users::new("scorer@example.com".to_string(), false, true)
+            .insert(&state.pool)
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/api/users").service(delete)),
+        )
+        .await;
+
+        let req = test::TestRequest::delete().uri("/api/users/1").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::CONFLICT);
+        assert!(Users::find_by_id(1, &state.pool).await.unwrap().is_some());
+
+        let req = test::TestRequest::delete().uri("/api/users/2").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NO_CONTENT);
+        assert!(Users::find_by_id(2, &state.pool).await.unwrap().is_none());
+    }
+
+    #[actix_web::test]
+    async fn update_invalid_email_test() {
+        let state = app_state("api_users_update_invalid_email").await;
+        Users::new("admin@example.com".to_string(), true, true)
+            .insert(&state.pool)
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(state.clone())
+                .service(web::scope("/api/users").service(update)),
+        )
+        .await;
+
+        let req = test::TestRequest::put()
+            .uri("/api/users/1")
+            .set_json(json!({
+                "email": "not an email",
+                "has_admin": true,
+                "has_set_score": true
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+}
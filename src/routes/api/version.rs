@@ -0,0 +1,45 @@
+use actix_web::{get, web, HttpResponse};
+use serde_json::json;
+
+use crate::AppState;
+
+/// Which build and config the server is running, to spot config edits that haven't been loaded
+#[get("/version")]
+pub async fn version(state: web::Data<AppState>) -> HttpResponse {
+    let config = state.config.get();
+    HttpResponse::Ok().json(json!({
+        "app_version": env!("CARGO_PKG_VERSION"),
+        "config_version": config.get_version(),
+        "config_checksum": config.checksum(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App};
+
+    use crate::{configurator::parser::Configuration, test_harness};
+
+    #[actix_web::test]
+    async fn version_test() {
+        let pool = test_harness::setup_db("api_version").await;
+        let config = Configuration {
+            version: "2.1.0".to_string(),
+            ..Default::default()
+        };
+        let checksum = config.checksum();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(pool, config)))
+                .service(web::scope("/api").service(version)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/version").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["config_version"], "2.1.0");
+        assert_eq!(body["config_checksum"], checksum);
+        assert_eq!(body["app_version"], env!("CARGO_PKG_VERSION"));
+    }
+}
@@ -0,0 +1,409 @@
+use actix_web::{post, web, HttpResponse};
+use serde_json::json;
+
+use crate::{
+    db::events::{Events, ScoreWrite, SwapScores},
+    websocket::ChannelsActor,
+    AppState,
+};
+
+/// Replace an event's scores and push the new scoreboard to WebSocket clients
+#[post("/{id}/scores")]
+pub async fn set_scores(
+    state: web::Data<AppState>,
+    path: web::Path<PathProps>,
+    body: web::Json<serde_json::Value>,
+    channels: web::Data<actix::Addr<ChannelsActor>>,
+) -> HttpResponse {
+    if !body.is_object() {
+        return HttpResponse::BadRequest()
+            .json(json!({"error": "Scores should be an object keyed by form id"}));
+    }
+    let out_of_range = state.config.get().score_range_errors(&body);
+    if !out_of_range.is_empty() {
+        return HttpResponse::BadRequest()
+            .json(json!({"error": "Scores out of range", "forms": out_of_range}));
+    }
+    match Events::set_scores(&state.pool, path.id.clone(), body.into_inner()).await {
+        Ok(ScoreWrite::Written(())) => {}
+        Ok(ScoreWrite::UnknownEvent) => {
+            return HttpResponse::NotFound().json(json!({"error": "Event not found"}));
+        }
+        Ok(ScoreWrite::Locked(id)) => return locked_conflict(&id),
+        Err(e) => {
+            log::error!("Failed to set scores for event {}: {}", path.id, e);
+            return HttpResponse::InternalServerError()
+                .json(json!({"error": "Failed to save scores"}));
+        }
+    }
+
+    // Only publish once the new scores are saved
+    crate::utils::publish_scoreboards(state, &channels).await;
+
+    HttpResponse::NoContent().finish()
+}
+
+/// Put a single event back to zero for every form, e.g. to re-run a heat
+#[post("/{id}/scores/reset")]
+pub async fn reset_scores(
+    state: web::Data<AppState>,
+    path: web::Path<PathProps>,
+    channels: web::Data<actix::Addr<ChannelsActor>>,
+) -> HttpResponse {
+    let reset = Events::reset_scores(
+        &state.pool,
+        path.id.clone(),
+        state.config.get().empty_scores(),
+    )
+    .await
+    .unwrap();
+    match reset {
+        ScoreWrite::Written(()) => {}
+        ScoreWrite::UnknownEvent => {
+            return HttpResponse::NotFound().json(json!({"error": "Event not found"}));
+        }
+        ScoreWrite::Locked(id) => return locked_conflict(&id),
+    }
+
+    crate::utils::publish_scoreboards(state, &channels).await;
+
+    HttpResponse::NoContent().finish()
+}
+
+/// Exchange two forms' scores for an event that was entered the wrong way round
+#[post("/{id}/scores/swap")]
+pub async fn swap_scores(
+    state: web::Data<AppState>,
+    path: web::Path<PathProps>,
+    body: web::Json<SwapBody>,
+    channels: web::Data<actix::Addr<ChannelsActor>>,
+) -> HttpResponse {
+    let outcome = Events::swap_scores(
+        &state.pool,
+        path.id.clone(),
+        body.form_a.clone(),
+        body.form_b.clone(),
+    )
+    .await
+    .unwrap();
+
+    match outcome {
+        SwapScores::Swapped => {}
+        SwapScores::UnknownEvent => {
+            return HttpResponse::NotFound().json(json!({"error": "Event not found"}));
+        }
+        SwapScores::Locked => return locked_conflict(&path.id),
+        SwapScores::UnknownForm(form) => {
+            return HttpResponse::BadRequest()
+                .json(json!({"error": format!("Form {} has no score in this event", form)}));
+        }
+    }
+    log::info!(
+        "Swapped scores of forms {} and {} in event {}",
+        body.form_a,
+        body.form_b,
+        path.id
+    );
+
+    crate::utils::publish_scoreboards(state, &channels).await;
+
+    HttpResponse::NoContent().finish()
+}
+
+/// The response for a score change to a locked event
+pub(crate) fn locked_conflict(id: &str) -> HttpResponse {
+    HttpResponse::Conflict().json(json!({"error": format!("Event {} is locked", id)}))
+}
+
+#[derive(serde::Deserialize)]
+pub struct SwapBody {
+    form_a: String,
+    form_b: String,
+}
+
+#[derive(serde::Deserialize)]
+struct PathProps {
+    id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix::Actor;
+    use actix_web::{http::StatusCode, test, App};
+
+    use crate::{
+        configurator::parser::{ApplicabilityRules, Configuration, Event, Form, Year},
+        test_harness,
+    };
+
+    /// Two events scored a=5, b=3 for forms a and b, accepting scores from 0 to 10
+    async fn app_state(db_name: &str) -> web::Data<AppState> {
+        let pool = test_harness::setup_db(db_name).await;
+        let config = Configuration {
+            version: "1.0.0".to_string(),
+            genders: vec!["mixed".to_string()],
+            scores: vec![],
+            years: vec![Year {
+                id: "y9".to_string(),
+                name: "Year 9".to_string(),
+            }],
+            forms: vec![
+                Form {
+                    id: "a".to_string(),
+                    name: "A".to_string(),
+                    colour: "red".to_string(),
+                },
+                Form {
+                    id: "b".to_string(),
+                    name: "B".to_string(),
+                    colour: "blue".to_string(),
+                },
+            ],
+            events: ["100m", "200m"]
+                .iter()
+                .map(|id| Event {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    applicable_years: ApplicabilityRules::All,
+                    applicable_genders: ApplicabilityRules::All,
+                    tags: vec![],
+                })
+                .collect(),
+            min_score: Some(0),
+            max_score: Some(10),
+            ..Default::default()
+        };
+        let plan = crate::configurator::build::build_plan(config.clone()).unwrap();
+        crate::configurator::run::run(plan, &pool).await.unwrap();
+        for id in ["y9-mixed-100m", "y9-mixed-200m"] {
+            Events::set_scores(&pool, id.to_string(), json!({"a": "5", "b": "3"}))
+                .await
+                .unwrap();
+        }
+
+        web::Data::new(test_harness::app_state(pool, config))
+    }
+
+    #[actix_web::test]
+    async fn set_scores_publishes_test() {
+        use futures::StreamExt;
+
+        let state = app_state("routes_events_set_scores").await;
+        let pool = state.pool.clone();
+        let channels = ChannelsActor::with_coalesce_window(std::time::Duration::ZERO).start();
+        let (tx, mut rx) = futures::channel::mpsc::unbounded();
+        channels
+            .send(crate::websocket::Subscribe {
+                channel: "scores".to_string(),
+                addr: crate::websocket::SseSession { tx }.start().recipient(),
+            })
+            .await
+            .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .app_data(web::Data::new(channels))
+                .service(web::scope("/events").service(set_scores)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/events/y9-mixed-100m/scores")
+            .set_json(json!({"a": "1", "b": "9"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let event = Events::find_by_id(&pool, "y9-mixed-100m".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.parsed_decimal_scores()["b"], 9.0);
+
+        let payload = tokio::time::timeout(std::time::Duration::from_secs(5), rx.next())
+            .await
+            .unwrap()
+            .unwrap();
+        let payload: String = payload.split_whitespace().collect();
+        // 9 from this event plus 3 from the other
+        assert!(payload.contains(r#"id="total-b">12<"#));
+
+        let req = test::TestRequest::post()
+            .uri("/events/missing/scores")
+            .set_json(json!({"a": "1"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn set_scores_range_test() {
+        let state = app_state("routes_events_set_scores_range").await;
+        let pool = state.pool.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .app_data(web::Data::new(ChannelsActor::new().start()))
+                .service(web::scope("/events").service(set_scores)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/events/y9-mixed-100m/scores")
+            .set_json(json!({"a": "10", "b": "0"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let req = test::TestRequest::post()
+            .uri("/events/y9-mixed-100m/scores")
+            .set_json(json!({"a": "100", "b": "1"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(
+            body["forms"],
+            json!({"a": "100 is above the maximum score of 10"})
+        );
+
+        // The rejected submission isn't saved
+        let event = Events::find_by_id(&pool, "y9-mixed-100m".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.parsed_decimal_scores()["a"], 10.0);
+        assert_eq!(event.parsed_decimal_scores()["b"], 0.0);
+    }
+
+    #[actix_web::test]
+    async fn reset_scores_test() {
+        let state = app_state("routes_events_reset_scores").await;
+        let pool = state.pool.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .app_data(web::Data::new(ChannelsActor::new().start()))
+                .service(web::scope("/events").service(reset_scores)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/events/y9-mixed-100m/scores/reset")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let mut events = Events::all(&pool).await.unwrap();
+        events.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(events[0].parsed_decimal_scores().values().sum::<f64>(), 0.0);
+        assert_eq!(events[0].parsed_decimal_scores().len(), 2);
+        assert_eq!(events[1].parsed_decimal_scores().values().sum::<f64>(), 8.0);
+
+        let req = test::TestRequest::post()
+            .uri("/events/missing/scores/reset")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn swap_scores_test() {
+        let state = app_state("routes_events_swap_scores").await;
+        let pool = state.pool.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .app_data(web::Data::new(ChannelsActor::new().start()))
+                .service(web::scope("/events").service(swap_scores)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/events/y9-mixed-100m/scores/swap")
+            .set_json(json!({"form_a": "a", "form_b": "b"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let mut events = Events::all(&pool).await.unwrap();
+        events.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(events[0].parsed_decimal_scores()["a"], 3.0);
+        assert_eq!(events[0].parsed_decimal_scores()["b"], 5.0);
+        assert_eq!(events[1].parsed_decimal_scores()["a"], 5.0);
+
+        let req = test::TestRequest::post()
+            .uri("/events/y9-mixed-100m/scores/swap")
+            .set_json(json!({"form_a": "a", "form_b": "nope"}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn lock_test() {
+        let state = app_state("routes_events_lock").await;
+        let pool = state.pool.clone();
+        let app = test::init_service(
+            App::new()
+                .app_data(state)
+                .app_data(web::Data::new(ChannelsActor::new().start()))
+                .service(
+                    web::scope("/events")
+                        .service(set_scores)
+                        .service(reset_scores)
+                        .service(swap_scores),
+                ),
+        )
+        .await;
+        let set = || {
+            test::TestRequest::post()
+                .uri("/events/y9-mixed-100m/scores")
+                .set_json(json!({"a": "1", "b": "2"}))
+                .to_request()
+        };
+
+        Events::set_locked(&pool, "y9-mixed-100m".to_string(), true)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            test::call_service(&app, set()).await.status(),
+            StatusCode::CONFLICT
+        );
+        let req = test::TestRequest::post()
+            .uri("/events/y9-mixed-100m/scores/reset")
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::CONFLICT
+        );
+        let req = test::TestRequest::post()
+            .uri("/events/y9-mixed-100m/scores/swap")
+            .set_json(json!({"form_a": "a", "form_b": "b"}))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            StatusCode::CONFLICT
+        );
+        let event = Events::find_by_id(&pool, "y9-mixed-100m".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.parsed_decimal_scores()["a"], 5.0);
+
+        Events::set_locked(&pool, "y9-mixed-100m".to_string(), false)
+            .await
+            .unwrap();
+        assert_eq!(
+            test::call_service(&app, set()).await.status(),
+            StatusCode::NO_CONTENT
+        );
+        let event = Events::find_by_id(&pool, "y9-mixed-100m".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.parsed_decimal_scores()["a"], 1.0);
+    }
+}
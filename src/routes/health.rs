@@ -0,0 +1,51 @@
+use actix_web::{get, web, HttpResponse};
+use serde_json::json;
+
+use crate::{db::timed_conn, AppState};
+
+/// Readiness check for load balancers, only healthy if the database answers a query.
+///
+/// Deliberately registered outside the authentication middleware and allowed through
+/// maintenance mode, so it reports whether the instance can serve rather than who is asking.
+#[get("/healthz")]
+pub async fn healthz(state: web::Data<AppState>) -> HttpResponse {
+    match timed_conn(&state.pool, |conn| {
+        conn.query_row("SELECT 1", [], |row| row.get::<_, i64>(0))
+    })
+    .await
+    {
+        Ok(_) => HttpResponse::Ok().json(json!({"status": "ok"})),
+        Err(e) => {
+            log::error!("Health check failed: {}", e);
+            HttpResponse::ServiceUnavailable().json(json!({"status": "degraded"}))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    use crate::test_harness;
+
+    #[actix_web::test]
+    async fn healthz_test() {
+        let pool = test_harness::setup_db("routes_healthz").await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_harness::app_state(
+                    pool,
+                    Default::default(),
+                )))
+                .service(healthz),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/healthz").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body, json!({"status": "ok"}));
+    }
+}
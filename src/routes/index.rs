@@ -4,8 +4,14 @@ use askama::Template;
 use crate::{templates::IndexTemplate, AppState};
 
 #[get("/")]
-pub async fn get(_state: web::Data<AppState>) -> HttpResponse {
-    HttpResponse::Ok().body(IndexTemplate {}.render().expect("Template should be valid"))
+pub async fn get(state: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().body(
+        IndexTemplate {
+            announcement: state.config.get().banner(),
+        }
+        .render()
+        .expect("Template should be valid"),
+    )
 }
 
 #[cfg(test)]
@@ -34,6 +40,7 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
         let client = reqwest::Client::builder()
@@ -55,13 +62,17 @@ mod tests {
             App::new()
                 .app_data(web::Data::new(crate::AppState {
                     client: client.clone(),
-                    config: config.clone(),
+                    config: config.clone().into(),
                     pool: pool.clone(),
                     log_collector: log_collector.clone(),
                     oauth_creds: crate::OauthCreds {
                         client_id: "test".to_string(),
                         client_secret: "test".to_string(),
                     },
+                    sessions: Box::new(crate::session_store::MemorySessionStore::default()),
+                    settings: crate::settings::Settings::default(),
+                    config_path: crate::configurator::parser::DEFAULT_CONFIG_PATH.to_string(),
+                    maintenance: Default::default(),
                 }))
                 .service(get),
         )
@@ -132,6 +143,7 @@ mod tests {
                     name: "100m Sprint".to_string(),
                     applicable_years: crate::configurator::parser::ApplicabilityRules::All,
                     applicable_genders: crate::configurator::parser::ApplicabilityRules::All,
+                    tags: vec![],
                 },
                 crate::configurator::parser::Event {
                     id: "relay".to_string(),
@@ -140,11 +152,13 @@ mod tests {
                         ids: vec!["year8".to_string()],
                     },
                     applicable_genders: crate::configurator::parser::ApplicabilityRules::All,
+                    tags: vec![],
                 },
             ],
+            ..Default::default()
         };
 
-        let plan = crate::configurator::build::build_plan(config.clone());
+        let plan = crate::configurator::build::build_plan(config.clone()).unwrap();
         crate::configurator::run::run(plan, &pool).await.unwrap();
 
         let client = reqwest::Client::builder()
@@ -160,13 +174,17 @@ mod tests {
                 .wrap(crate::middleware::headers::DefaultHtmlContentType)
                 .app_data(web::Data::new(crate::AppState {
                     client: client.clone(),
-                    config: config.clone(),
+                    config: config.clone().into(),
                     pool: pool.clone(),
                     log_collector: log_collector.clone(),
                     oauth_creds: crate::OauthCreds {
                         client_id: "test_client_id".to_string(),
                         client_secret: "test_client_secret".to_string(),
                     },
+                    sessions: Box::new(crate::session_store::MemorySessionStore::default()),
+                    settings: crate::settings::Settings::default(),
+                    config_path: crate::configurator::parser::DEFAULT_CONFIG_PATH.to_string(),
+                    maintenance: Default::default(),
                 }))
                 .app_data(web::Data::new(ws_channels.clone()))
                 .service(get)
@@ -1,7 +1,12 @@
 pub mod admin;
+pub mod api;
+pub mod events;
+pub mod health;
 pub mod index;
 pub mod oauth;
+pub mod public;
 pub mod results;
 pub mod scoreboard;
 pub mod set_scores;
+pub mod sse;
 pub mod ws;
@@ -105,33 +105,86 @@ pub async fn callback_get(
             .await
             .unwrap();
     }
-    let session = user.clone().new_session();
+    let session = match state.sessions.insert(user.clone().new_session()).await {
+        Ok(session) => session,
+        Err(e) => {
+            error!("Could not store session for {}: {}", user.email, e);
+            return HttpResponse::InternalServerError().body("Could not log in, please try again");
+        }
+    };
     debug!(
         "Created session for user {} with id {}",
         user.id.unwrap(),
         session.id
     );
 
-    session.clone().insert(&state.pool).await.unwrap();
-
-    let cookie = Cookie::build("session_data", session.clone().id)
-        .path("/") // Make cookie available for all paths
-        .max_age(Duration::days(10))
-        .http_only(true) // Prevent JavaScript access for security
-        .finish();
+    let cookie = session_cookie(&state, &req, session.clone().id);
 
     debug!("Setting cookie: session_data={}", session.id);
 
     let redirect_to = req
         .cookie("redirect-to")
         .map(|c| c.value().to_string())
-        .unwrap_or_else(|| "/".to_string());
+        .unwrap_or_else(|| state.settings.url("/"));
     HttpResponse::Found()
         .append_header(("Location", redirect_to))
         .cookie(cookie)
         .finish()
 }
 
+/// The `session_data` cookie for a session id
+fn session_cookie(state: &AppState, req: &HttpRequest, id: String) -> Cookie<'static> {
+    state
+        .settings
+        .cookie(req, "session_data", id) // Available across the whole app
+        .max_age(Duration::days(10))
+        .http_only(true) // Prevent JavaScript access for security
+        .finish()
+}
+
+/// Call after `user`'s permissions change. Every session `user` holds is ended so none of them
+/// keep the old permissions. If the caller was logged in as `user` and sessions rotate, they get
+/// a fresh session with the new permissions and the cookie for it is returned, otherwise they
+/// have to log in again.
+pub async fn end_user_sessions(
+    state: &AppState,
+    req: &HttpRequest,
+    user: &db::users::Users,
+) -> Option<Cookie<'static>> {
+    let user_id = user.id?;
+    // Check who the caller is before their session goes
+    let is_caller = match req.cookie("session_data") {
+        Some(cookie) => state
+            .sessions
+            .verify(cookie.value().to_string())
+            .await
+            .is_ok_and(|session| session.verified && session.user_id == Some(user_id)),
+        None => false,
+    };
+
+    match state.sessions.delete_for_user(user_id).await {
+        Ok(ended) => debug!("Ended {} sessions for user {}", ended, user.email),
+        Err(e) => {
+            error!("Could not end sessions for {}: {}", user.email, e);
+            return None;
+        }
+    }
+    if !is_caller || !state.settings.rotate_sessions {
+        return None;
+    }
+
+    match state.sessions.insert(user.clone().new_session()).await {
+        Ok(session) => {
+            debug!("Rotated session for user {} to {}", user.email, session.id);
+            Some(session_cookie(state, req, session.id))
+        }
+        Err(e) => {
+            error!("Could not rotate session for {}: {}", user.email, e);
+            None
+        }
+    }
+}
+
 #[derive(serde::Deserialize)]
 struct CallbackParams {
     code: String,
@@ -0,0 +1,67 @@
+use actix_web::{get, web, HttpResponse};
+use askama::Template;
+
+use crate::{templates::ScoreboardTemplate, utils, AppState};
+
+/// Read-only scoreboard for share links, served without logging in
+#[get("/{token}/scoreboard")]
+pub async fn scoreboard(state: web::Data<AppState>, path: web::Path<PathProps>) -> HttpResponse {
+    if !state.settings.is_share_token(&path.token) {
+        return HttpResponse::NotFound().body("Not Found");
+    }
+
+    let announcement = state.config.get().banner();
+    let scores = utils::render_scoreboard(state).await;
+    let html = ScoreboardTemplate {
+        scores,
+        channel: "scores".to_string(),
+        announcement,
+    }
+    .render()
+    .expect("template should be valid");
+
+    HttpResponse::Ok().body(html)
+}
+
+#[derive(serde::Deserialize)]
+struct PathProps {
+    token: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, App};
+
+    use crate::{settings::Settings, test_harness};
+
+    #[actix_web::test]
+    async fn share_token_test() {
+        let pool = test_harness::setup_db("public_scoreboard").await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(AppState {
+                    settings: Settings {
+                        share_tokens: vec!["letmein".to_string()],
+                        ..Default::default()
+                    },
+                    ..test_harness::app_state(pool, Default::default())
+                }))
+                .service(web::scope("/public").service(scoreboard)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/public/letmein/scoreboard")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get()
+            .uri("/public/guess/scoreboard")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
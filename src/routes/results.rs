@@ -1,4 +1,4 @@
-use actix_web::{get, web, HttpResponse};
+use actix_web::{get, http::header, web, HttpResponse};
 use askama::Template;
 use serde_json::Value;
 
@@ -6,14 +6,15 @@ use crate::{configurator::parser::Year, db, templates::ResultsTemplate, AppState
 
 #[get("/results")]
 pub async fn get(state: web::Data<AppState>) -> HttpResponse {
-    let events = db::events::Events::all(&state.pool).await.unwrap();
+    let config = state.config.get();
+    let mut events = db::events::Events::all(&state.pool).await.unwrap();
+    crate::utils::sort_events(&config, &mut events);
     let mut results_events: Vec<ResultsEvent> = Vec::new();
 
     for event in events.iter() {
         results_events.push(ResultsEvent {
             name: event.name.clone(),
-            year: state
-                .config
+            year: config
                 .years
                 .iter()
                 .filter(|year| year.id == event.year_id)
@@ -21,13 +22,19 @@ pub async fn get(state: web::Data<AppState>) -> HttpResponse {
                 .name
                 .clone(),
             group: event.gender_id.clone(),
-            scores: serde_json::from_str::<Value>(event.scores.as_str()).unwrap(),
+            scores: match serde_json::from_str::<Value>(event.scores.as_str()) {
+                Ok(scores) => scores,
+                Err(e) => {
+                    log::warn!("Event {} has corrupt scores: {}", event.id, e);
+                    Value::Object(serde_json::Map::new())
+                }
+            },
         });
     }
 
     HttpResponse::Ok().body(
         ResultsTemplate {
-            forms: state.config.forms.clone(),
+            forms: config.forms.clone(),
             events: results_events,
         }
         .render()
@@ -35,6 +42,64 @@ pub async fn get(state: web::Data<AppState>) -> HttpResponse {
     )
 }
 
+/// Every event's points per form as a spreadsheet, for printing and keeping after the day
+#[get("/results/export.csv")]
+pub async fn export_csv(state: web::Data<AppState>) -> HttpResponse {
+    let config = state.config.get();
+    let mut events = db::events::Events::all(&state.pool).await.unwrap();
+    crate::utils::sort_events(&config, &mut events);
+
+    let mut csv = String::new();
+    let header = ["Event", "Year", "Group"]
+        .into_iter()
+        .map(String::from)
+        .chain(config.forms.iter().map(|form| form.name.clone()));
+    push_csv_row(&mut csv, header);
+    for event in events.iter() {
+        let year = config
+            .years
+            .iter()
+            .find(|year| year.id == event.year_id)
+            .map_or(event.year_id.clone(), |year| year.name.clone());
+        let scores = serde_json::from_str::<serde_json::Map<String, Value>>(&event.scores)
+            .unwrap_or_default();
+        let points = config.forms.iter().map(|form| match scores.get(&form.id) {
+            Some(Value::String(score)) => score.clone(),
+            Some(Value::Number(score)) => score.to_string(),
+            _ => String::new(),
+        });
+        push_csv_row(
+            &mut csv,
+            [event.name.clone(), year, event.gender_id.clone()]
+                .into_iter()
+                .chain(points),
+        );
+    }
+
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"results.csv\"",
+        ))
+        .body(csv)
+}
+
+/// Append one CSV line, quoting fields that hold commas, quotes or line breaks
+fn push_csv_row(csv: &mut String, fields: impl Iterator<Item = String>) {
+    let fields: Vec<String> = fields
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect();
+    csv.push_str(&fields.join(","));
+    csv.push_str("\r\n");
+}
+
 pub struct ResultsEvent {
     pub name: String,
     pub year: String,
@@ -66,6 +131,7 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
         let client = reqwest::Client::builder()
@@ -87,13 +153,17 @@ mod tests {
             actix_web::App::new()
                 .app_data(web::Data::new(crate::AppState {
                     client: client.clone(),
-                    config: config.clone(),
+                    config: config.clone().into(),
                     pool: pool.clone(),
                     log_collector: log_collector.clone(),
                     oauth_creds: crate::OauthCreds {
                         client_id: "test".to_string(),
                         client_secret: "test".to_string(),
                     },
+                    sessions: Box::new(crate::session_store::MemorySessionStore::default()),
+                    settings: crate::settings::Settings::default(),
+                    config_path: crate::configurator::parser::DEFAULT_CONFIG_PATH.to_string(),
+                    maintenance: Default::default(),
                 }))
                 .service(get),
         )
@@ -104,4 +174,121 @@ mod tests {
 
         assert!(resp.status().is_success());
     }
+
+    #[actix_web::test]
+    async fn test_results_follow_gender_order() {
+        let config = crate::configurator::parser::Configuration {
+            version: "1.0.0".to_string(),
+            genders: vec!["boys".to_string(), "girls".to_string()],
+            scores: vec![],
+            years: vec![crate::configurator::parser::Year {
+                id: "y9".to_string(),
+                name: "Year 9".to_string(),
+            }],
+            forms: vec![],
+            events: vec![crate::configurator::parser::Event {
+                id: "60m".to_string(),
+                name: "60m".to_string(),
+                applicable_years: crate::configurator::parser::ApplicabilityRules::All,
+                applicable_genders: crate::configurator::parser::ApplicabilityRules::All,
+                tags: vec![],
+            }],
+            gender_order: vec!["girls".to_string(), "boys".to_string()],
+            ..Default::default()
+        };
+
+        let pool = crate::test_harness::setup_db("results_gender_order").await;
+        let plan = crate::configurator::build::build_plan(config.clone()).unwrap();
+        crate::configurator::run::run(plan, &pool).await.unwrap();
+
+        let app = test::init_service(
+            actix_web::App::new()
+                .app_data(web::Data::new(crate::test_harness::app_state(
+                    pool.clone(),
+                    config.clone(),
+                )))
+                .service(get),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/results").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        let girls = body.find("<td>girls</td>").unwrap();
+        let boys = body.find("<td>boys</td>").unwrap();
+        assert!(girls < boys);
+    }
+
+    #[actix_web::test]
+    async fn test_export_csv() {
+        let form = |id: &str, name: &str| crate::configurator::parser::Form {
+            id: id.to_string(),
+            name: name.to_string(),
+            colour: "red".to_string(),
+        };
+        let config = crate::configurator::parser::Configuration {
+            genders: vec!["boys".to_string(), "girls".to_string()],
+            years: vec![crate::configurator::parser::Year {
+                id: "y9".to_string(),
+                name: "Year 9".to_string(),
+            }],
+            forms: vec![form("f1", "Form 1"), form("f2", "Smith, J")],
+            events: vec![crate::configurator::parser::Event {
+                id: "60m".to_string(),
+                name: "60m".to_string(),
+                applicable_years: crate::configurator::parser::ApplicabilityRules::All,
+                applicable_genders: crate::configurator::parser::ApplicabilityRules::All,
+                tags: vec![],
+            }],
+            ..Default::default()
+        };
+
+        let pool = crate::test_harness::setup_db("results_export_csv").await;
+        let plan = crate::configurator::build::build_plan(config.clone()).unwrap();
+        crate::configurator::run::run(plan, &pool).await.unwrap();
+        db::events::Events::set_scores(
+            &pool,
+            "y9-girls-60m".to_string(),
+            serde_json::json!({"f1": "3", "f2": "5"}),
+        )
+        .await
+        .unwrap();
+
+        let app = test::init_service(
+            actix_web::App::new()
+                .app_data(web::Data::new(crate::test_harness::app_state(
+                    pool.clone(),
+                    config,
+                )))
+                .service(export_csv),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/results/export.csv")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/csv; charset=utf-8"
+        );
+        assert!(resp
+            .headers()
+            .get(header::CONTENT_DISPOSITION)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("attachment"));
+
+        let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        assert_eq!(
+            body.lines().collect::<Vec<_>>(),
+            vec![
+                "Event,Year,Group,Form 1,\"Smith, J\"",
+                "60m,Year 9,boys,0,0",
+                "60m,Year 9,girls,3,5",
+            ]
+        );
+    }
 }
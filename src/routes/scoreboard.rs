@@ -5,14 +5,42 @@ use crate::{templates::ScoreboardTemplate, utils, AppState};
 
 #[get("/scoreboard")]
 pub async fn get(state: web::Data<AppState>) -> HttpResponse {
+    let announcement = state.config.get().banner();
     let scores = utils::render_scoreboard(state).await;
-    let html = ScoreboardTemplate { scores }
-        .render()
-        .expect("template should be valid");
+    let html = ScoreboardTemplate {
+        scores,
+        channel: "scores".to_string(),
+        announcement,
+    }
+    .render()
+    .expect("template should be valid");
 
     HttpResponse::Ok().body(html)
 }
 
+/// One year group's forms and scores, for a screen dedicated to that year
+#[get("/scoreboard/{year_id}")]
+pub async fn year(state: web::Data<AppState>, path: web::Path<PathProps>) -> HttpResponse {
+    let announcement = state.config.get().banner();
+    let Some(scores) = utils::render_year_scoreboard(state, &path.year_id).await else {
+        return HttpResponse::NotFound().body("Not Found");
+    };
+    let html = ScoreboardTemplate {
+        scores,
+        channel: format!("year:{}", path.year_id),
+        announcement,
+    }
+    .render()
+    .expect("template should be valid");
+
+    HttpResponse::Ok().body(html)
+}
+
+#[derive(serde::Deserialize)]
+struct PathProps {
+    year_id: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,6 +65,7 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
         let client = reqwest::Client::builder()
@@ -58,13 +87,17 @@ mod tests {
             actix_web::App::new()
                 .app_data(web::Data::new(crate::AppState {
                     client: client.clone(),
-                    config: config.clone(),
+                    config: config.clone().into(),
                     pool: pool.clone(),
                     log_collector: log_collector.clone(),
                     oauth_creds: crate::OauthCreds {
                         client_id: "test".to_string(),
                         client_secret: "test".to_string(),
                     },
+                    sessions: Box::new(crate::session_store::MemorySessionStore::default()),
+                    settings: crate::settings::Settings::default(),
+                    config_path: crate::configurator::parser::DEFAULT_CONFIG_PATH.to_string(),
+                    maintenance: Default::default(),
                 }))
                 .service(get),
         )
@@ -75,4 +108,185 @@ mod tests {
 
         assert!(resp.status().is_success());
     }
+
+    #[actix_web::test]
+    async fn test_scoreboard_layouts() {
+        use crate::configurator::parser::{Configuration, Form, ScoreboardLayout, Year};
+        use crate::db::{events::Events, years::Years};
+
+        let pool = async_sqlite::PoolBuilder::new()
+            .path(get_test_db_path("scoreboard_layouts"))
+            .open()
+            .await
+            .unwrap();
+
+        crate::create_tables(&pool).await.unwrap();
+
+        Years::new("year7".to_string(), "Year 7".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        Events::new(
+            "year7-mixed-sprint".to_string(),
+            "Sprint".to_string(),
+            "year7".to_string(),
+            "mixed".to_string(),
+            "sprint".to_string(),
+            r#"{"form1":"10","form2":"8"}"#.to_string(),
+        )
+        .insert(&pool)
+        .await
+        .unwrap();
+
+        let mut bodies = vec![];
+        for layout in [
+            ScoreboardLayout::FormsAsColumns,
+            ScoreboardLayout::YearsAsColumns,
+        ] {
+            let config = Configuration {
+                version: "1.0.0".to_string(),
+                genders: vec!["mixed".to_string()],
+                years: vec![Year {
+                    id: "year7".to_string(),
+                    name: "Year 7".to_string(),
+                }],
+                forms: vec![
+                    Form {
+                        id: "form1".to_string(),
+                        name: "Form 1".to_string(),
+                        colour: "#ff0000".to_string(),
+                    },
+                    Form {
+                        id: "form2".to_string(),
+                        name: "Form 2".to_string(),
+                        colour: "#00ff00".to_string(),
+                    },
+                ],
+                scoreboard_layout: layout,
+                ..Default::default()
+            };
+
+            let app = test::init_service(
+                actix_web::App::new()
+                    .app_data(web::Data::new(crate::test_harness::app_state(
+                        pool.clone(),
+                        config,
+                    )))
+                    .service(get),
+            )
+            .await;
+
+            let req = test::TestRequest::get().uri("/scoreboard").to_request();
+            let body = test::call_and_read_body(&app, req).await;
+            bodies.push(String::from_utf8(body.to_vec()).unwrap());
+        }
+
+        // Forms as columns: a header row of forms, one row per year
+        assert!(bodies[0].contains("<th>Year</th>"));
+        assert!(bodies[0].contains("<th>Form 1</th>"));
+        assert!(!bodies[0].contains("<th>Year 7</th>"));
+
+        // Years as columns: a header row of years, one row per form
+        assert!(bodies[1].contains("<th>Form</th>"));
+        assert!(bodies[1].contains("<th>Year 7</th>"));
+        assert!(!bodies[1].contains("<th>Form 1</th>"));
+
+        // Both show the same cell data
+        for body in bodies.iter() {
+            assert!(body.contains(r#"id="year7-form1""#));
+            assert!(body.contains(r#"<strong id="total-total">18</strong>"#));
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_scoreboard_announcement() {
+        let pool = crate::test_harness::setup_db("scoreboard_announcement").await;
+
+        let mut bodies = vec![];
+        for announcement in [Some("Sponsored by the PTA"), Some("  "), None] {
+            let app = test::init_service(
+                actix_web::App::new()
+                    .app_data(web::Data::new(crate::test_harness::app_state(
+                        pool.clone(),
+                        crate::configurator::parser::Configuration {
+                            announcement: announcement.map(str::to_string),
+                            ..Default::default()
+                        },
+                    )))
+                    .service(get),
+            )
+            .await;
+
+            let req = test::TestRequest::get().uri("/scoreboard").to_request();
+            let body = test::call_and_read_body(&app, req).await;
+            bodies.push(String::from_utf8(body.to_vec()).unwrap());
+        }
+
+        assert!(bodies[0].contains(r#"id="announcement""#));
+        assert!(bodies[0].contains("<span>Sponsored by the PTA</span>"));
+        // Blank and missing announcements show no banner
+        assert!(!bodies[1].contains(r#"id="announcement""#));
+        assert!(!bodies[2].contains(r#"id="announcement""#));
+    }
+
+    #[actix_web::test]
+    async fn test_year_scoreboard_route() {
+        use crate::configurator::parser::{Configuration, Form};
+        use crate::db::{events::Events, years::Years};
+
+        let pool = crate::test_harness::setup_db("scoreboard_year_route").await;
+        for (year_id, year_name, scores) in [
+            ("year7", "Year 7", r#"{"form1":"10"}"#),
+            ("year8", "Year 8", r#"{"form1":"4"}"#),
+        ] {
+            Years::new(year_id.to_string(), year_name.to_string())
+                .insert(&pool)
+                .await
+                .unwrap();
+            Events::new(
+                format!("{}-mixed-sprint", year_id),
+                "Sprint".to_string(),
+                year_id.to_string(),
+                "mixed".to_string(),
+                "sprint".to_string(),
+                scores.to_string(),
+            )
+            .insert(&pool)
+            .await
+            .unwrap();
+        }
+
+        let app = test::init_service(
+            actix_web::App::new()
+                .app_data(web::Data::new(crate::test_harness::app_state(
+                    pool,
+                    Configuration {
+                        forms: vec![Form {
+                            id: "form1".to_string(),
+                            name: "Form 1".to_string(),
+                            colour: "#ff0000".to_string(),
+                        }],
+                        ..Default::default()
+                    },
+                )))
+                .service(year),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/scoreboard/year8")
+            .to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(r#"data-websocket-channel-value="year:year8""#));
+        assert!(body.contains(r#"id="year8-form1""#));
+        assert!(!body.contains("year7"));
+        assert!(body.contains(r#"<strong id="total-total">4</strong>"#));
+
+        let req = test::TestRequest::get()
+            .uri("/scoreboard/year13")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+    }
 }
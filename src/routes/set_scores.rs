@@ -1,32 +1,44 @@
 use actix_web::{get, post, web, HttpResponse};
 use askama::Template;
-use serde_json::Value;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
 
 use crate::{
     db::{self, events::Events},
     templates::SetScoresTemplate,
-    websocket::{ChannelsActor, Publish},
+    websocket::ChannelsActor,
     AppState,
 };
 
 #[get("")]
 pub async fn get(state: web::Data<AppState>, params: web::Query<Params>) -> HttpResponse {
-    let events = Events::r#where(
+    let config = state.config.get();
+    let mut events = Events::r#where(
         &state.pool,
         params.year.clone(),
         params.activity.clone(),
         params.group.clone(),
+        params.tag.clone(),
     )
     .await
     .unwrap();
+    crate::utils::sort_events(&config, &mut events);
+    // Only offer years that actually have events
+    let years_with_events = Events::distinct_years(&state.pool).await.unwrap();
+    let year_types = config
+        .years
+        .iter()
+        .filter(|year| years_with_events.contains(&year.id))
+        .cloned()
+        .collect();
     HttpResponse::Ok().body(
         SetScoresTemplate {
             events,
-            activity_types: state.config.events.clone(),
-            year_types: state.config.years.clone(),
-            group_types: state.config.genders.clone(),
-            forms: state.config.forms.clone(),
-            scores: state.config.scores.clone(),
+            activity_types: config.events.clone(),
+            year_types,
+            group_types: config.ordered_genders(),
+            forms: config.forms.clone(),
+            scores: config.scores.clone(),
         }
         .render()
         .expect("Template should be valid"),
@@ -41,19 +53,34 @@ pub async fn post(
 ) -> HttpResponse {
     let body: Value = serde_json::from_str(body.as_str()).unwrap();
 
-    for events in body.as_object().unwrap() {
-        let event_id = events.0;
-        let event_scores = events.1;
-        db::events::Events::set_scores(&state.pool, event_id.to_owned(), event_scores.to_owned())
-            .await
-            .unwrap();
+    // Check every event first so a typo doesn't leave the submission half saved
+    let config = state.config.get();
+    let out_of_range: BTreeMap<&String, BTreeMap<String, String>> = body
+        .as_object()
+        .unwrap()
+        .iter()
+        .map(|(event_id, scores)| (event_id, config.score_range_errors(scores)))
+        .filter(|(_, errors)| !errors.is_empty())
+        .collect();
+    if !out_of_range.is_empty() {
+        return HttpResponse::BadRequest()
+            .json(json!({"error": "Scores out of range", "events": out_of_range}));
     }
 
-    let scores = crate::utils::render_scoreboard(state).await;
-    channels.do_send(Publish {
-        channel: "scores".to_string(),
-        payload: scores,
-    });
+    let scores = body
+        .as_object()
+        .unwrap()
+        .iter()
+        .map(|(event_id, scores)| (event_id.to_owned(), scores.to_owned()))
+        .collect();
+    let written = db::events::Events::set_many_scores(&state.pool, scores)
+        .await
+        .unwrap();
+    if let db::events::ScoreWrite::Locked(id) = written {
+        return crate::routes::events::locked_conflict(&id);
+    }
+
+    crate::utils::publish_scoreboards(state, &channels).await;
 
     HttpResponse::NoContent().finish()
 }
@@ -63,4 +90,5 @@ struct Params {
     year: Option<String>,
     activity: Option<String>,
     group: Option<String>,
+    tag: Option<String>,
 }
@@ -0,0 +1,148 @@
+use actix::Actor;
+use actix_web::{get, http::header, web, HttpResponse};
+use futures::StreamExt;
+use serde::Deserialize;
+
+use crate::websocket::{ChannelsActor, SseSession, Subscribe};
+
+#[derive(Deserialize)]
+pub struct SseQuery {
+    /// Only stream one year's scoreboard, like the `year:{id}` WebSocket channel
+    pub year: Option<String>,
+}
+
+/// Format a payload as a single `scores` event, prefixing every line with `data:`
+pub fn sse_event(payload: &str) -> String {
+    let mut event = String::from("event: scores\n");
+    for line in payload.lines() {
+        event.push_str("data: ");
+        event.push_str(line);
+        event.push('\n');
+    }
+    event.push('\n');
+    event
+}
+
+/// Scoreboard updates as Server-Sent Events, for venues where WebSockets are blocked
+#[get("/sse/scoreboard")]
+async fn scoreboard(
+    query: web::Query<SseQuery>,
+    channels: web::Data<actix::Addr<ChannelsActor>>,
+) -> actix_web::Result<HttpResponse> {
+    let channel = match &query.year {
+        Some(year) => format!("year:{}", year),
+        None => "scores".to_string(),
+    };
+    let (tx, rx) = futures::channel::mpsc::unbounded::<String>();
+    let session = SseSession { tx }.start();
+    // Wait for the subscription so nothing published after we respond is missed
+    channels
+        .send(Subscribe {
+            channel,
+            addr: session.recipient(),
+        })
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(
+            rx.map(|payload| Ok::<_, actix_web::Error>(web::Bytes::from(sse_event(&payload)))),
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{body::MessageBody, test, App};
+    use std::time::Duration;
+
+    use crate::{
+        configurator::parser::{Configuration, Form, Year},
+        db::{events::Events, years::Years},
+        test_harness, utils, AppState,
+    };
+
+    #[actix_web::test]
+    async fn sse_event_test() {
+        assert_eq!(
+            sse_event("<table>\n<tr></tr>\n</table>"),
+            "event: scores\ndata: <table>\ndata: <tr></tr>\ndata: </table>\n\n"
+        );
+    }
+
+    #[actix_web::test]
+    async fn scoreboard_streams_published_scores_test() {
+        let pool = test_harness::setup_db("sse_scoreboard").await;
+        Years::new("y8".to_string(), "Y8".to_string())
+            .insert(&pool)
+            .await
+            .unwrap();
+        Events::new(
+            "y8-boys-100m".to_string(),
+            "100m".to_string(),
+            "y8".to_string(),
+            "boys".to_string(),
+            "100m".to_string(),
+            "{}".to_string(),
+        )
+        .insert(&pool)
+        .await
+        .unwrap();
+
+        let state = web::Data::new(test_harness::app_state(
+            pool.clone(),
+            Configuration {
+                years: vec![Year {
+                    id: "y8".to_string(),
+                    name: "Y8".to_string(),
+                }],
+                forms: vec![Form {
+                    id: "f1".to_string(),
+                    name: "Form 1".to_string(),
+                    colour: "red".to_string(),
+                }],
+                ..Default::default()
+            },
+        ));
+        let channels = ChannelsActor::with_coalesce_window(Duration::ZERO).start();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(channels.clone()))
+                .service(scoreboard),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/sse/scoreboard").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+
+        Events::set_scores(
+            &pool,
+            "y8-boys-100m".to_string(),
+            serde_json::json!({"f1": "5"}),
+        )
+        .await
+        .unwrap();
+        utils::publish_scoreboards(state, &channels).await;
+
+        let body = resp.into_body();
+        let mut body = std::pin::pin!(body);
+        let chunk = tokio::time::timeout(
+            Duration::from_secs(5),
+            std::future::poll_fn(|cx| body.as_mut().poll_next(cx)),
+        )
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+        let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+        assert!(chunk.starts_with("event: scores\ndata: "));
+        assert!(chunk.contains("Form 1"));
+    }
+}
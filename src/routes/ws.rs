@@ -1,29 +1,46 @@
 use actix_web::{get, web, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
 
-use crate::websocket::{ChannelsActor, WsSession};
+use crate::{
+    websocket::{requires_admin, ChannelsActor, WsSession},
+    AppState,
+};
 
+/// Subscribe to a channel's broadcasts. `admin:` channels need an admin session, anyone else is
+/// sent a close frame saying why.
 #[get("/ws/{channel}")]
 async fn get(
     req: HttpRequest,
     stream: web::Payload,
     path: web::Path<String>,
+    state: web::Data<AppState>,
     channels: web::Data<actix::Addr<ChannelsActor>>,
 ) -> actix_web::Result<HttpResponse> {
     let channel_name = path.into_inner();
-    ws::start(
-        WsSession {
-            channel_name,
-            channels: channels.get_ref().clone(),
-        },
-        &req,
-        stream,
-    )
+    let mut session = WsSession::new(channel_name.clone(), channels.get_ref().clone());
+
+    if requires_admin(&channel_name) {
+        let is_admin = match req.cookie("session_data") {
+            Some(cookie) => state
+                .sessions
+                .verify(cookie.value().to_string())
+                .await
+                .is_ok_and(|session| session.verified && session.has_admin),
+            None => false,
+        };
+        if !is_admin {
+            log::info!("Refused subscription to {} without admin", channel_name);
+            session = session.deny("Admin channels need an admin session");
+        }
+    }
+
+    ws::start(session, &req, stream)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::session_store::SessionStore;
     use actix::Actor;
     use actix_web::test;
     use std::sync::atomic::{AtomicU64, Ordering};
@@ -46,6 +63,7 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
         let client = reqwest::Client::builder()
@@ -68,13 +86,17 @@ mod tests {
             actix_web::App::new()
                 .app_data(web::Data::new(crate::AppState {
                     client: client.clone(),
-                    config: config.clone(),
+                    config: config.clone().into(),
                     pool: pool.clone(),
                     log_collector: log_collector.clone(),
                     oauth_creds: crate::OauthCreds {
                         client_id: "test".to_string(),
                         client_secret: "test".to_string(),
                     },
+                    sessions: Box::new(crate::session_store::MemorySessionStore::default()),
+                    settings: crate::settings::Settings::default(),
+                    config_path: crate::configurator::parser::DEFAULT_CONFIG_PATH.to_string(),
+                    maintenance: Default::default(),
                 }))
                 .app_data(web::Data::new(ws_channels.clone()))
                 .service(get),
@@ -88,4 +110,66 @@ mod tests {
         // WebSocket upgrade failure is expected in test
         assert!(resp.status().is_client_error() || resp.status().is_server_error());
     }
+
+    #[actix_web::test]
+    async fn test_admin_channel_needs_admin() {
+        let pool = crate::test_harness::setup_db("ws_admin_channel").await;
+        let sessions = crate::session_store::MemorySessionStore::default();
+        let admin = sessions
+            .insert(crate::db::user_sessions::UserSessions::new(1, true, false))
+            .await
+            .unwrap();
+        let ws_channels = ChannelsActor::new().start();
+
+        let app = test::init_service(
+            actix_web::App::new()
+                .app_data(web::Data::new(crate::AppState {
+                    sessions: Box::new(sessions),
+                    ..crate::test_harness::app_state(pool, Default::default())
+                }))
+                .app_data(web::Data::new(ws_channels.clone()))
+                .service(get),
+        )
+        .await;
+
+        let upgrade = || {
+            test::TestRequest::get()
+                .uri("/ws/admin:logs")
+                .insert_header(("connection", "upgrade"))
+                .insert_header(("upgrade", "websocket"))
+                .insert_header(("sec-websocket-version", "13"))
+                .insert_header(("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ=="))
+        };
+
+        // Without a cookie the connection opens only to be closed with a policy violation
+        let resp = test::call_service(&app, upgrade().to_request()).await;
+        assert_eq!(
+            resp.status(),
+            actix_web::http::StatusCode::SWITCHING_PROTOCOLS
+        );
+        let body = test::read_body(resp).await;
+        assert_eq!(body[0], 0x88);
+        assert_eq!(&body[2..4], &1008u16.to_be_bytes());
+        assert!(String::from_utf8_lossy(&body[4..]).contains("admin session"));
+        assert_eq!(
+            ws_channels
+                .send(crate::websocket::ChannelCount)
+                .await
+                .unwrap(),
+            0
+        );
+
+        let resp = test::call_service(
+            &app,
+            upgrade()
+                .cookie(actix_web::cookie::Cookie::new("session_data", admin.id))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(
+            resp.status(),
+            actix_web::http::StatusCode::SWITCHING_PROTOCOLS
+        );
+        assert!(!test::read_body(resp).await.starts_with(&[0x88]));
+    }
 }
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::thread;
+
+use async_sqlite::Pool;
+use chrono::{DateTime, Duration, Utc};
+use futures::future::{BoxFuture, FutureExt};
+
+use crate::{
+    db::user_sessions::{UserSessions, VerifiedSession},
+    prometheus::wait_for_next_sample,
+};
+
+/// Storage backend for login sessions
+pub trait SessionStore: Send + Sync {
+    /// Store a session, returning it as stored. Its id may differ if the given one was taken.
+    fn insert(
+        &self,
+        session: UserSessions,
+    ) -> BoxFuture<'_, Result<UserSessions, async_sqlite::Error>>;
+
+    fn verify(&self, id: String) -> BoxFuture<'_, Result<VerifiedSession, async_sqlite::Error>>;
+
+    fn delete(&self, id: String) -> BoxFuture<'_, Result<(), async_sqlite::Error>>;
+
+    /// Remove every session a user holds, returning how many were removed
+    fn delete_for_user(&self, user_id: i64) -> BoxFuture<'_, Result<usize, async_sqlite::Error>>;
+
+    /// When a session runs out, `None` if it doesn't exist, has already expired, has no known age
+    /// or sessions never expire
+    fn expires_at(
+        &self,
+        id: String,
+    ) -> BoxFuture<'_, Result<Option<DateTime<Utc>>, async_sqlite::Error>>;
+}
+
+/// Sessions stored in the `user_sessions` table
+pub struct SqliteSessionStore {
+    pool: Pool,
+    ttl: Option<Duration>,
+}
+
+impl SqliteSessionStore {
+    pub fn new(pool: Pool) -> Self {
+        Self { pool, ttl: None }
+    }
+
+    /// Reject sessions older than `ttl`, `None` keeps them forever
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+}
+
+impl SessionStore for SqliteSessionStore {
+    fn insert(
+        &self,
+        session: UserSessions,
+    ) -> BoxFuture<'_, Result<UserSessions, async_sqlite::Error>> {
+        session.insert(&self.pool).boxed()
+    }
+
+    fn verify(&self, id: String) -> BoxFuture<'_, Result<VerifiedSession, async_sqlite::Error>> {
+        match self.ttl {
+            Some(ttl) => UserSessions::verify_with_ttl(&self.pool, id, ttl).boxed(),
+            None => UserSessions::verify(&self.pool, id).boxed(),
+        }
+    }
+
+    fn delete(&self, id: String) -> BoxFuture<'_, Result<(), async_sqlite::Error>> {
+        UserSessions::delete(&self.pool, id).boxed()
+    }
+
+    fn delete_for_user(&self, user_id: i64) -> BoxFuture<'_, Result<usize, async_sqlite::Error>> {
+        UserSessions::delete_all_for_user(&self.pool, user_id).boxed()
+    }
+
+    fn expires_at(
+        &self,
+        id: String,
+    ) -> BoxFuture<'_, Result<Option<DateTime<Utc>>, async_sqlite::Error>> {
+        async move {
+            let Some(ttl) = self.ttl else {
+                return Ok(None);
+            };
+            // Every session that hasn't expired runs out within a whole `ttl`
+            Ok(UserSessions::expiring_within(&self.pool, Some(ttl), ttl)
+                .await?
+                .into_iter()
+                .find(|session| session.id == id)
+                .and_then(|session| session.expires_at(ttl)))
+        }
+        .boxed()
+    }
+}
+
+/// Sessions held in process memory, shared between clones. Sessions are lost on restart.
+#[derive(Clone, Default)]
+pub struct MemorySessionStore {
+    sessions: Arc<Mutex<HashMap<String, UserSessions>>>,
+    ttl: Option<Duration>,
+}
+
+impl MemorySessionStore {
+    /// Reject sessions older than `ttl`, `None` keeps them forever
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn insert(
+        &self,
+        session: UserSessions,
+    ) -> BoxFuture<'_, Result<UserSessions, async_sqlite::Error>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        // Never replace someone else's session
+        let mut session = session;
+        while sessions.contains_key(&session.id) {
+            session = session.with_new_id();
+        }
+        sessions.insert(session.id.clone(), session.clone());
+        futures::future::ready(Ok(session)).boxed()
+    }
+
+    fn verify(&self, id: String) -> BoxFuture<'_, Result<VerifiedSession, async_sqlite::Error>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let verified = match sessions.get(&id) {
+            Some(session)
+                if self
+                    .ttl
+                    .is_some_and(|ttl| session.is_expired(ttl, Utc::now())) =>
+            {
+                sessions.remove(&id);
+                VerifiedSession::unverified(id)
+            }
+            Some(session) => VerifiedSession {
+                _id: id,
+                verified: true,
+                user_id: Some(session.user_id),
+                has_admin: session.has_admin,
+                has_set_score: session.has_set_score,
+            },
+            None => VerifiedSession::unverified(id),
+        };
+        futures::future::ready(Ok(verified)).boxed()
+    }
+
+    fn delete(&self, id: String) -> BoxFuture<'_, Result<(), async_sqlite::Error>> {
+        self.sessions.lock().unwrap().remove(&id);
+        futures::future::ready(Ok(())).boxed()
+    }
+
+    fn delete_for_user(&self, user_id: i64) -> BoxFuture<'_, Result<usize, async_sqlite::Error>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let before = sessions.len();
+        sessions.retain(|_, session| session.user_id != user_id);
+        futures::future::ready(Ok(before - sessions.len())).boxed()
+    }
+
+    fn expires_at(
+        &self,
+        id: String,
+    ) -> BoxFuture<'_, Result<Option<DateTime<Utc>>, async_sqlite::Error>> {
+        let expires_at = self.ttl.and_then(|ttl| {
+            self.sessions
+                .lock()
+                .unwrap()
+                .get(&id)
+                .filter(|session| session.is_expiring_within(ttl, ttl, Utc::now()))
+                .and_then(|session| session.expires_at(ttl))
+        });
+        futures::future::ready(Ok(expires_at)).boxed()
+    }
+}
+
+/// Stops the expired session cleanup started by `spawn_purge`
+pub struct PurgeHandle {
+    stop: Arc<AtomicBool>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl PurgeHandle {
+    /// Wake the cleanup thread, let it finish any purge in progress and wait for it to exit
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.thread.thread().unpark();
+        let _ = self.thread.join();
+    }
+}
+
+/// Delete sessions older than `ttl` from the `user_sessions` table now and then every
+/// `interval`, so expired rows don't pile up
+pub fn spawn_purge(pool: Pool, ttl: Duration, interval: std::time::Duration) -> PurgeHandle {
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+    let thread = thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        loop {
+            match rt.block_on(UserSessions::purge_expired(&pool, ttl)) {
+                Ok(0) => {}
+                Ok(purged) => log::info!("Purged {} expired sessions", purged),
+                Err(e) => log::warn!("Failed to purge expired sessions: {}", e),
+            }
+            if !wait_for_next_sample(&thread_stop, interval) {
+                break;
+            }
+        }
+    });
+
+    PurgeHandle { stop, thread }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::users::Users, test_harness};
+
+    async fn exercise_store(store: &dyn SessionStore) {
+        let session = UserSessions::new(1, true, false);
+        assert!(store.insert(session.clone()).await.is_ok());
+
+        let verified = store.verify(session.id.clone()).await.unwrap();
+        assert!(verified.verified);
+        assert!(verified.has_admin);
+        assert!(!verified.has_set_score);
+        // Without a TTL nothing expires
+        assert_eq!(store.expires_at(session.id.clone()).await.unwrap(), None);
+
+        assert!(
+            !store
+                .verify("HelloWorld".to_string())
+                .await
+                .unwrap()
+                .verified
+        );
+
+        assert!(store.delete(session.id.clone()).await.is_ok());
+        assert!(!store.verify(session.id).await.unwrap().verified);
+
+        let first = store
+            .insert(UserSessions::new(1, true, false))
+            .await
+            .unwrap();
+        let second = store
+            .insert(UserSessions::new(1, true, false))
+            .await
+            .unwrap();
+        let other = store
+            .insert(UserSessions::new(2, true, false))
+            .await
+            .unwrap();
+        assert_eq!(store.delete_for_user(1).await.unwrap(), 2);
+        assert!(!store.verify(first.id).await.unwrap().verified);
+        assert!(!store.verify(second.id).await.unwrap().verified);
+        assert!(store.verify(other.id).await.unwrap().verified);
+    }
+
+    #[tokio::test]
+    async fn sqlite_store_test() {
+        let db = test_harness::setup_db("session_store_sqlite").await;
+        for email in ["example@example.com", "other@example.com"] {
+            assert!(Users::new(email.to_string(), true, false)
+                .insert(&db)
+                .await
+                .is_ok());
+        }
+
+        exercise_store(&SqliteSessionStore::new(db)).await;
+    }
+
+    #[tokio::test]
+    async fn memory_store_test() {
+        exercise_store(&MemorySessionStore::default()).await;
+    }
+
+    async fn exercise_ttl(store: &dyn SessionStore) {
+        let mut stale = UserSessions::new(1, true, true);
+        stale.created_at = Some(Utc::now() - Duration::hours(2));
+        assert!(store.insert(stale.clone()).await.is_ok());
+        let fresh = UserSessions::new(1, true, true);
+        assert!(store.insert(fresh.clone()).await.is_ok());
+
+        assert_eq!(store.expires_at(stale.id.clone()).await.unwrap(), None);
+        let expires_at = store.expires_at(fresh.id.clone()).await.unwrap().unwrap();
+        assert!(
+            (expires_at - (Utc::now() + Duration::hours(1)))
+                .num_seconds()
+                .abs()
+                < 5
+        );
+        assert_eq!(
+            store.expires_at("HelloWorld".to_string()).await.unwrap(),
+            None
+        );
+
+        assert!(!store.verify(stale.id).await.unwrap().verified);
+        assert!(store.verify(fresh.id).await.unwrap().verified);
+    }
+
+    #[tokio::test]
+    async fn sqlite_store_ttl_test() {
+        let db = test_harness::setup_db("session_store_sqlite_ttl").await;
+        assert!(Users::new("example@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+
+        exercise_ttl(&SqliteSessionStore::new(db).with_ttl(Some(Duration::hours(1)))).await;
+    }
+
+    #[tokio::test]
+    async fn memory_store_ttl_test() {
+        exercise_ttl(&MemorySessionStore::default().with_ttl(Some(Duration::hours(1)))).await;
+    }
+
+    #[tokio::test]
+    async fn spawn_purge_test() {
+        let db = test_harness::setup_db("session_store_spawn_purge").await;
+        assert!(Users::new("example@example.com".to_string(), true, true)
+            .insert(&db)
+            .await
+            .is_ok());
+        let mut stale = UserSessions::new(1, true, true);
+        stale.created_at = Some(Utc::now() - Duration::hours(2));
+        assert!(stale.insert(&db).await.is_ok());
+
+        // The first purge runs straight away, before the thread checks for a stop
+        spawn_purge(
+            db.clone(),
+            Duration::hours(1),
+            std::time::Duration::from_secs(3600),
+        )
+        .stop();
+        assert_eq!(UserSessions::count(&db).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn memory_store_shared_between_clones_test() {
+        let store = MemorySessionStore::default();
+        let session = UserSessions::new(1, false, true);
+        assert!(store.insert(session.clone()).await.is_ok());
+
+        let boxed: Box<dyn SessionStore> = Box::new(store.clone());
+        assert!(boxed.verify(session.id).await.unwrap().verified);
+    }
+}
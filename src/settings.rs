@@ -0,0 +1,258 @@
+use actix_web::{
+    cookie::{Cookie, CookieBuilder},
+    http::header,
+    HttpRequest,
+};
+
+/// Deployment settings read from the environment
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// Subpath the app is served under behind a proxy (e.g. "/sportsday"), empty at the root
+    pub base_path: String,
+    /// Path attribute for cookies, defaults to the base path
+    pub cookie_path: String,
+    /// Domain attribute for cookies, left unset by default
+    pub cookie_domain: Option<String>,
+    /// Tokens accepted by the read-only `/public/{token}/...` share links
+    pub share_tokens: Vec<String>,
+    /// Page size for list endpoints when the request doesn't give `per_page`
+    pub default_per_page: usize,
+    /// Largest `per_page` a list endpoint will return
+    pub max_per_page: usize,
+    /// How long a login session lasts, `None` if sessions never expire
+    pub session_ttl: Option<chrono::Duration>,
+    /// How often expired sessions are deleted from the database, when `session_ttl` is set
+    pub session_purge_interval: std::time::Duration,
+    /// Indent every API response, not just those asking with `?pretty=1`
+    pub dev_mode: bool,
+    /// Believe `X-Forwarded-Proto` and `X-Forwarded-Host` from a TLS-terminating proxy
+    pub trust_proxy: bool,
+    /// Records the config version the database was last built from
+    pub version_file: String,
+    /// Give a user a new session id when their own permissions are changed, rather than logging
+    /// them out
+    pub rotate_sessions: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            base_path: String::new(),
+            cookie_path: "/".to_string(),
+            cookie_domain: None,
+            share_tokens: vec![],
+            default_per_page: 50,
+            max_per_page: 500,
+            session_ttl: None,
+            session_purge_interval: std::time::Duration::from_secs(3600),
+            dev_mode: false,
+            trust_proxy: false,
+            version_file: "./version.txt".to_string(),
+            rotate_sessions: true,
+        }
+    }
+}
+
+impl Settings {
+    /// Read `BASE_PATH`, `COOKIE_PATH`, `COOKIE_DOMAIN`, the comma separated `SHARE_TOKENS`,
+    /// `DEFAULT_PER_PAGE`, `MAX_PER_PAGE`, `SESSION_TTL_SECS`, `SESSION_PURGE_INTERVAL_SECS`,
+    /// `DEV_MODE`, `TRUST_PROXY`, `VERSION_FILE` and `ROTATE_SESSIONS`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let base_path = std::env::var("BASE_PATH")
+            .map(|path| normalize_base_path(&path))
+            .unwrap_or_default();
+        let cookie_path = std::env::var("COOKIE_PATH").unwrap_or_else(|_| {
+            if base_path.is_empty() {
+                "/".to_string()
+            } else {
+                base_path.clone()
+            }
+        });
+
+        Self {
+            base_path,
+            cookie_path,
+            cookie_domain: std::env::var("COOKIE_DOMAIN").ok(),
+            share_tokens: std::env::var("SHARE_TOKENS")
+                .map(|tokens| parse_share_tokens(&tokens))
+                .unwrap_or_default(),
+            default_per_page: std::env::var("DEFAULT_PER_PAGE")
+                .ok()
+                .and_then(|size| size.parse().ok())
+                .unwrap_or(defaults.default_per_page),
+            max_per_page: std::env::var("MAX_PER_PAGE")
+                .ok()
+                .and_then(|size| size.parse().ok())
+                .unwrap_or(defaults.max_per_page),
+            session_ttl: std::env::var("SESSION_TTL_SECS")
+                .ok()
+                .and_then(|secs| secs.parse::<i64>().ok())
+                .map(chrono::Duration::seconds),
+            session_purge_interval: std::env::var("SESSION_PURGE_INTERVAL_SECS")
+                .ok()
+                .and_then(|secs| secs.parse::<u64>().ok())
+                .filter(|secs| *secs > 0)
+                .map(std::time::Duration::from_secs)
+                .unwrap_or(defaults.session_purge_interval),
+            dev_mode: matches!(std::env::var("DEV_MODE").as_deref(), Ok("1" | "true")),
+            trust_proxy: matches!(std::env::var("TRUST_PROXY").as_deref(), Ok("1" | "true")),
+            version_file: std::env::var("VERSION_FILE").unwrap_or(defaults.version_file),
+            rotate_sessions: !matches!(
+                std::env::var("ROTATE_SESSIONS").as_deref(),
+                Ok("0" | "false")
+            ),
+        }
+    }
+
+    /// Prefix an app path with the base path for use in `Location` headers
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_path, path)
+    }
+
+    pub fn is_share_token(&self, token: &str) -> bool {
+        self.share_tokens
+            .iter()
+            .any(|share_token| share_token == token)
+    }
+
+    /// Whether the client reached us over HTTPS, taking the proxy's word for it when trusted
+    pub fn is_https(&self, req: &HttpRequest) -> bool {
+        let forwarded_proto = req
+            .headers()
+            .get("x-forwarded-proto")
+            .and_then(|proto| proto.to_str().ok());
+        match forwarded_proto {
+            Some(proto) if self.trust_proxy => proto.eq_ignore_ascii_case("https"),
+            _ => req.app_config().secure(),
+        }
+    }
+
+    /// Full URL of an app path as the client sees it, e.g. for OAuth redirects
+    pub fn absolute_url(&self, req: &HttpRequest, path: &str) -> String {
+        let scheme = if self.is_https(req) { "https" } else { "http" };
+        let host = if self.trust_proxy {
+            req.connection_info().host().to_string()
+        } else {
+            req.headers()
+                .get(header::HOST)
+                .and_then(|host| host.to_str().ok())
+                .unwrap_or(req.app_config().host())
+                .to_string()
+        };
+        format!("{}://{}{}", scheme, host, self.url(path))
+    }
+
+    /// Start building a cookie with the configured path and domain, marked `Secure` when the
+    /// request came over HTTPS
+    pub fn cookie<'c>(&self, req: &HttpRequest, name: &'c str, value: String) -> CookieBuilder<'c> {
+        let cookie = Cookie::build(name, value)
+            .path(self.cookie_path.clone())
+            .secure(self.is_https(req));
+        match &self.cookie_domain {
+            Some(domain) => cookie.domain(domain.clone()),
+            None => cookie,
+        }
+    }
+}
+
+fn parse_share_tokens(tokens: &str) -> Vec<String> {
+    tokens
+        .split(',')
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+/// "sportsday/" and "/sportsday" both become "/sportsday", "/" becomes ""
+fn normalize_base_path(path: &str) -> String {
+    let path = path.trim().trim_matches('/');
+    if path.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_normalize_base_path() {
+        assert_eq!(normalize_base_path(""), "");
+        assert_eq!(normalize_base_path("/"), "");
+        assert_eq!(normalize_base_path("sportsday/"), "/sportsday");
+        assert_eq!(normalize_base_path("/sportsday"), "/sportsday");
+    }
+
+    #[test]
+    fn test_share_tokens() {
+        let settings = Settings {
+            share_tokens: parse_share_tokens(" abc, ,def "),
+            ..Default::default()
+        };
+        assert_eq!(settings.share_tokens, vec!["abc", "def"]);
+        assert!(settings.is_share_token("def"));
+        assert!(!settings.is_share_token(""));
+        assert!(!settings.is_share_token("ghi"));
+    }
+
+    #[test]
+    fn test_url_and_cookie() {
+        let settings = Settings {
+            base_path: "/sportsday".to_string(),
+            cookie_path: "/sportsday".to_string(),
+            cookie_domain: Some("example.com".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(settings.url("/admin/users"), "/sportsday/admin/users");
+
+        let req = TestRequest::default().to_http_request();
+        let cookie = settings
+            .cookie(&req, "session_data", "abc".to_string())
+            .finish();
+        assert_eq!(cookie.path(), Some("/sportsday"));
+        assert_eq!(cookie.domain(), Some("example.com"));
+
+        let cookie = Settings::default()
+            .cookie(&req, "session_data", "abc".to_string())
+            .finish();
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.domain(), None);
+    }
+
+    #[test]
+    fn test_secure_cookie_behind_trusted_proxy() {
+        let req = TestRequest::default()
+            .insert_header(("X-Forwarded-Proto", "https"))
+            .insert_header(("X-Forwarded-Host", "scores.example.com"))
+            .insert_header((header::HOST, "127.0.0.1:3000"))
+            .to_http_request();
+
+        // Untrusted, the header could have come from anyone
+        let settings = Settings::default();
+        let cookie = settings
+            .cookie(&req, "session_data", "abc".to_string())
+            .finish();
+        assert_eq!(cookie.secure(), Some(false));
+        assert_eq!(
+            settings.absolute_url(&req, "/oauth/callback"),
+            "http://127.0.0.1:3000/oauth/callback"
+        );
+
+        let settings = Settings {
+            trust_proxy: true,
+            ..Default::default()
+        };
+        let cookie = settings
+            .cookie(&req, "session_data", "abc".to_string())
+            .finish();
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(
+            settings.absolute_url(&req, "/oauth/callback"),
+            "https://scores.example.com/oauth/callback"
+        );
+    }
+}
@@ -4,7 +4,7 @@ use std::collections::HashMap;
 use crate::{
     configurator::{
         self,
-        parser::{Form, Score},
+        parser::{Form, NumberFormat, Score, ScoreboardLayout},
     },
     db::{events::Events, users::Users, years::Years},
     logger::LogEntry,
@@ -13,12 +13,19 @@ use crate::{
 
 #[derive(Template)]
 #[template(path = "index.html")]
-pub struct IndexTemplate {}
+pub struct IndexTemplate {
+    /// See `Configuration::banner`
+    pub announcement: Option<String>,
+}
 
 #[derive(Template)]
 #[template(path = "scoreboard.html")]
 pub struct ScoreboardTemplate {
     pub scores: String,
+    /// WebSocket channel the page listens on for updates
+    pub channel: String,
+    /// See `Configuration::banner`
+    pub announcement: Option<String>,
 }
 
 #[derive(Template)]
@@ -26,10 +33,18 @@ pub struct ScoreboardTemplate {
 pub struct ScoreboardPartialTemplate {
     pub forms: Vec<Form>,
     pub years: Vec<Years>,
-    pub scores: HashMap<String, HashMap<String, i64>>,
-    pub year_totals: HashMap<String, i64>,
-    pub form_totals: HashMap<String, i64>,
-    pub grand_total: i64,
+    pub scores: HashMap<String, HashMap<String, f64>>,
+    pub year_totals: HashMap<String, f64>,
+    pub form_totals: HashMap<String, f64>,
+    pub grand_total: f64,
+    /// (form id, total, position), see `utils::rank_totals`
+    pub form_rankings: Vec<(String, f64, usize)>,
+    /// (year id, total, position)
+    pub year_rankings: Vec<(String, f64, usize)>,
+    /// Events left out of the totals because their scores couldn't be read
+    pub skipped_events: usize,
+    pub layout: ScoreboardLayout,
+    pub number_format: NumberFormat,
 }
 
 #[derive(Template)]
@@ -54,6 +69,10 @@ pub struct ResultsTemplate {
 #[template(path = "admin/index.html")]
 pub struct AdminIndexTemplate {}
 
+#[derive(Template)]
+#[template(path = "maintenance.html")]
+pub struct MaintenanceTemplate {}
+
 #[derive(Template)]
 #[template(path = "admin/users/list.html")]
 pub struct AdminUsersListTemplate {
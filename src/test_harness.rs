@@ -2,7 +2,14 @@ use tokio::fs;
 
 use async_sqlite::{Pool, PoolBuilder};
 
-use crate::db;
+use crate::{
+    configurator::parser::{Configuration, DEFAULT_CONFIG_PATH},
+    db,
+    logger::LogCollector,
+    session_store::MemorySessionStore,
+    settings::Settings,
+    AppState, OauthCreds,
+};
 
 pub async fn setup_db(db_name: &str) -> Pool {
     fs::remove_file(format!("./test/{db_name}.db").as_str())
@@ -16,3 +23,22 @@ pub async fn setup_db(db_name: &str) -> Pool {
     db::create_tables(&pool).await.unwrap();
     pool
 }
+
+/// State for route tests, with sessions held in memory and default settings. Change other fields
+/// with struct update syntax, e.g. `AppState { sessions, ..app_state(pool, config) }`.
+pub fn app_state(pool: Pool, config: Configuration) -> AppState {
+    AppState {
+        client: reqwest::Client::new(),
+        config: config.into(),
+        config_path: DEFAULT_CONFIG_PATH.to_string(),
+        log_collector: LogCollector::new(1000),
+        maintenance: Default::default(),
+        oauth_creds: OauthCreds {
+            client_id: "test".to_string(),
+            client_secret: "test".to_string(),
+        },
+        pool,
+        sessions: Box::new(MemorySessionStore::default()),
+        settings: Settings::default(),
+    }
+}
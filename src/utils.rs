@@ -1,69 +1,420 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use actix_web::web;
+use actix::Addr;
+use actix_web::{
+    http::header::{self, HttpDate},
+    web, HttpRequest, HttpResponse,
+};
 use askama::Template;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 use crate::{
+    configurator::parser::Configuration,
     db::{events::Events, years::Years},
+    logger::LogCollector,
     templates::ScoreboardPartialTemplate,
+    websocket::{ChannelsActor, Publish},
     AppState,
 };
 
 pub async fn render_scoreboard(state: web::Data<AppState>) -> String {
-    let forms: Vec<crate::configurator::parser::Form> = state.config.forms.clone();
     let years = Years::all(&state.pool).await.unwrap();
-    let events = Events::all(&state.pool).await.unwrap();
+    render_years_scoreboard(&state, years).await
+}
+
+/// Scoreboard for a single year group, `None` if there is no such year
+pub async fn render_year_scoreboard(state: web::Data<AppState>, year_id: &str) -> Option<String> {
+    let year = Years::find_by_id(&state.pool, year_id.to_string())
+        .await
+        .unwrap()?;
+    Some(render_years_scoreboard(&state, vec![year]).await)
+}
 
-    let mut year_form_scores: HashMap<String, HashMap<String, i64>> = HashMap::new();
+/// Send the whole scoreboard to the "scores" channel and each year's to "year:{id}"
+pub async fn publish_scoreboards(state: web::Data<AppState>, channels: &Addr<ChannelsActor>) {
+    let years = Years::all(&state.pool).await.unwrap();
+    for year in years.iter() {
+        channels.do_send(Publish {
+            channel: format!("year:{}", year.id),
+            payload: render_years_scoreboard(&state, vec![year.clone()]).await,
+        });
+    }
+    channels.do_send(Publish {
+        channel: "scores".to_string(),
+        payload: render_years_scoreboard(&state, years).await,
+    });
+}
+
+/// Per-year and per-form totals shown on the scoreboard
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct ScoreboardData {
+    /// Year id to form id to that form's total for the year
+    pub year_form_scores: HashMap<String, HashMap<String, f64>>,
+    pub year_totals: HashMap<String, f64>,
+    pub form_totals: HashMap<String, f64>,
+    pub grand_total: f64,
+    /// (form id, total, position) highest first, every configured form included
+    pub form_rankings: Vec<(String, f64, usize)>,
+    /// (year id, total, position) highest first
+    pub year_rankings: Vec<(String, f64, usize)>,
+    /// Events left out because their scores couldn't be read
+    #[serde(default)]
+    pub skipped_events: usize,
+}
+
+/// Totals across every year
+pub async fn compute_scoreboard(state: &AppState) -> ScoreboardData {
+    let years = Years::all(&state.pool).await.unwrap();
+    compute_years_scoreboard(state, &years).await
+}
+
+async fn compute_years_scoreboard(state: &AppState, years: &[Years]) -> ScoreboardData {
+    let config = state.config.get();
+    let form_ids: Vec<String> = config.forms.iter().map(|form| form.id.clone()).collect();
+    tally_scoreboard(
+        &state.pool,
+        years,
+        &form_ids,
+        &config.gender_multipliers,
+        Some(&state.log_collector),
+    )
+    .await
+}
+
+/// Totals across every year as they stood at `at`, replayed from the score history
+pub async fn compute_scoreboard_at(state: &AppState, at: DateTime<Utc>) -> ScoreboardData {
+    let config = state.config.get();
+    let form_ids: Vec<String> = config.forms.iter().map(|form| form.id.clone()).collect();
+    let years = Years::all(&state.pool).await.unwrap();
+    let events = Events::as_of(&state.pool, at).await.unwrap();
+    tally_events(
+        events,
+        &years,
+        &form_ids,
+        &config.gender_multipliers,
+        Some(&state.log_collector),
+    )
+}
+
+/// The totals behind `compute_scoreboard` for some years and forms, for callers without an
+/// `AppState`. Each event's points are scaled by its gender's entry in `gender_multipliers`.
+/// Unreadable events are reported to `log_collector` when one is given.
+pub async fn tally_scoreboard(
+    pool: &async_sqlite::Pool,
+    years: &[Years],
+    form_ids: &[String],
+    gender_multipliers: &BTreeMap<String, f64>,
+    log_collector: Option<&LogCollector>,
+) -> ScoreboardData {
+    let events = Events::all(pool).await.unwrap();
+    tally_events(events, years, form_ids, gender_multipliers, log_collector)
+}
+
+fn tally_events(
+    events: Vec<Events>,
+    years: &[Years],
+    form_ids: &[String],
+    gender_multipliers: &BTreeMap<String, f64>,
+    log_collector: Option<&LogCollector>,
+) -> ScoreboardData {
+    let events: Vec<Events> = events
+        .into_iter()
+        .filter(|event| years.iter().any(|year| year.id == event.year_id))
+        .collect();
+
+    let mut year_form_scores: HashMap<String, HashMap<String, f64>> = HashMap::new();
+    let mut skipped_events = 0;
     for event in events.iter() {
-        let year_id = event.year_id.clone();
-        if let Ok(scores_map) =
-            serde_json::from_str::<HashMap<String, String>>(event.scores.as_str())
+        // Report corrupt rows in the console rather than quietly leaving them out of the totals
+        if serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&event.scores)
+            .is_err()
         {
-            let year_scores = year_form_scores.entry(year_id).or_insert_with(HashMap::new);
-            for (form_id, score_str) in scores_map {
-                if let Ok(score) = score_str.parse::<i64>() {
-                    *year_scores.entry(form_id).or_insert(0) += score;
-                }
+            if let Some(log_collector) = log_collector {
+                log_collector.add_entry(
+                    log::Level::Warn,
+                    &format!(
+                        "Skipping event {} on the scoreboard, its scores aren't valid JSON",
+                        event.id
+                    ),
+                    Some(module_path!()),
+                );
             }
+            skipped_events += 1;
+            continue;
+        }
+
+        let multiplier = gender_multipliers
+            .get(&event.gender_id)
+            .copied()
+            .unwrap_or(1.0);
+        let year_scores = year_form_scores
+            .entry(event.year_id.clone())
+            .or_insert_with(HashMap::new);
+        for (form_id, score) in event.parsed_decimal_scores() {
+            *year_scores.entry(form_id).or_insert(0.0) += score * multiplier;
         }
     }
 
     // Calculate year totals (sum of all forms for each year)
-    let mut year_totals: HashMap<String, i64> = HashMap::new();
+    let mut year_totals: HashMap<String, f64> = HashMap::new();
     for (year_id, form_scores) in &year_form_scores {
-        let total: i64 = form_scores.values().sum();
+        let total: f64 = form_scores.values().sum();
         year_totals.insert(year_id.clone(), total);
     }
 
     // Calculate form totals (sum of all years for each form)
-    let mut form_totals: HashMap<String, i64> = HashMap::new();
-    for form in &forms {
-        let mut total: i64 = 0;
+    let mut form_totals: HashMap<String, f64> = HashMap::new();
+    for form_id in form_ids {
+        let mut total: f64 = 0.0;
         for form_scores in year_form_scores.values() {
-            if let Some(score) = form_scores.get(&form.id) {
+            if let Some(score) = form_scores.get(form_id) {
                 total += score;
             }
         }
-        form_totals.insert(form.id.clone(), total);
+        form_totals.insert(form_id.clone(), total);
     }
 
     // Calculate grand total
-    let grand_total: i64 = form_totals.values().sum();
+    let grand_total: f64 = form_totals.values().sum();
 
-    let html = ScoreboardPartialTemplate {
-        forms,
-        years,
-        scores: year_form_scores,
+    ScoreboardData {
+        skipped_events,
+        form_rankings: rank_totals(&form_totals),
+        year_rankings: rank_totals(&year_totals),
+        year_form_scores,
         year_totals,
         form_totals,
         grand_total,
     }
+}
+
+async fn render_years_scoreboard(state: &AppState, years: Vec<Years>) -> String {
+    let config = state.config.get();
+    let data = compute_years_scoreboard(state, &years).await;
+
+    let html = ScoreboardPartialTemplate {
+        forms: config.forms.clone(),
+        years,
+        scores: data.year_form_scores,
+        year_totals: data.year_totals,
+        form_totals: data.form_totals,
+        grand_total: data.grand_total,
+        form_rankings: data.form_rankings,
+        year_rankings: data.year_rankings,
+        skipped_events: data.skipped_events,
+        layout: config.scoreboard_layout,
+        number_format: config.number_format,
+    }
     .render()
     .expect("template should bee valid");
     html
 }
 
+/// Order events for display: by year, then the configured event (running) order, then the
+/// configured gender order. Events missing from the config go last, alphabetically
+pub fn sort_events(config: &Configuration, events: &mut [Events]) {
+    events.sort_by_cached_key(|event| {
+        (
+            config
+                .years
+                .iter()
+                .position(|year| year.id == event.year_id)
+                .unwrap_or(config.years.len()),
+            config
+                .events
+                .iter()
+                .position(|config_event| config_event.id == event.filter_key)
+                .unwrap_or(config.events.len()),
+            event.filter_key.clone(),
+            config.gender_rank(&event.gender_id),
+        )
+    });
+}
+
+/// Points handed out in one event across every form, see `top_events`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EventTotal {
+    pub id: String,
+    /// From the config where the event is still listed, otherwise the stored name
+    pub name: String,
+    pub year_id: String,
+    pub gender_id: String,
+    /// Scaled by the gender's multiplier, as on the scoreboard
+    pub total: f64,
+}
+
+/// The `n` events with the most points across all forms, highest first, counting decimal scores
+/// and gender multipliers the way the scoreboard does. Events without any points are left out,
+/// and equal totals keep display order (see `sort_events`).
+pub fn top_events(config: &Configuration, events: &[Events], n: usize) -> Vec<EventTotal> {
+    let mut events = events.to_vec();
+    sort_events(config, &mut events);
+
+    let mut totals: Vec<EventTotal> = events
+        .into_iter()
+        .map(|event| EventTotal {
+            total: event.parsed_decimal_scores().values().sum::<f64>()
+                * config
+                    .gender_multipliers
+                    .get(&event.gender_id)
+                    .copied()
+                    .unwrap_or(1.0),
+            name: config
+                .events
+                .iter()
+                .find(|config_event| config_event.id == event.filter_key)
+                .map_or(event.name, |config_event| config_event.name.clone()),
+            id: event.id,
+            year_id: event.year_id,
+            gender_id: event.gender_id,
+        })
+        .filter(|event| event.total > 0.0)
+        .collect();
+    totals.sort_by(|a, b| b.total.total_cmp(&a.total));
+    totals.truncate(n);
+    totals
+}
+
+/// Sort totals highest first and give each a position, with ties sharing the same position
+/// (standard competition ranking, so 1, 2, 2, 4). Equal totals are ordered by id.
+pub fn rank_totals<T: Copy + PartialOrd>(totals: &HashMap<String, T>) -> Vec<(String, T, usize)> {
+    let mut sorted: Vec<(&String, &T)> = totals.iter().collect();
+    sorted.sort_by(|a, b| {
+        b.1.partial_cmp(a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.0.cmp(b.0))
+    });
+
+    let mut ranked: Vec<(String, T, usize)> = Vec::with_capacity(sorted.len());
+    for (index, (id, total)) in sorted.into_iter().enumerate() {
+        let position = match ranked.last() {
+            Some((_, previous, position)) if previous == total => *position,
+            _ => index + 1,
+        };
+        ranked.push((id.clone(), *total, position));
+    }
+    ranked
+}
+
+/// HTTP dates only have whole seconds, so drop anything smaller
+pub fn http_date(timestamp: DateTime<Utc>) -> HttpDate {
+    let seconds = DateTime::from_timestamp(timestamp.timestamp(), 0).unwrap_or(timestamp);
+    HttpDate::from(std::time::SystemTime::from(seconds))
+}
+
+/// `Last-Modified` for a change at `last_modified`. HTTP dates only have whole seconds, so this
+/// is the next whole second, which `is_not_modified` can tell apart from a later change in the
+/// same second. It is never later than now, so a change after this response can't be hidden
+/// behind it.
+pub fn last_modified_header(last_modified: DateTime<Utc>) -> HttpDate {
+    let next_second = DateTime::from_timestamp(last_modified.timestamp() + 1, 0)
+        .unwrap_or(last_modified)
+        .min(Utc::now());
+    http_date(next_second)
+}
+
+/// Whether the request's `If-Modified-Since` is strictly after `last_modified`, so the client
+/// has seen it
+pub fn is_not_modified(req: &HttpRequest, last_modified: DateTime<Utc>) -> bool {
+    req.headers()
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<HttpDate>().ok())
+        .is_some_and(|since| {
+            DateTime::<Utc>::from(std::time::SystemTime::from(since)) > last_modified
+        })
+}
+
+/// `page` and `per_page` query parameters for list endpoints
+#[derive(Deserialize, Default)]
+pub struct PageQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+/// A validated, 1-based page of a list
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pagination {
+    pub page: usize,
+    pub per_page: usize,
+}
+
+impl Pagination {
+    /// Read `page` and `per_page`, clamping `per_page` to `max_per_page`. Values below 1 are
+    /// answered with a 400.
+    pub fn from_query(
+        query: &PageQuery,
+        default_per_page: usize,
+        max_per_page: usize,
+    ) -> Result<Self, HttpResponse> {
+        let page = query.page.unwrap_or(1);
+        let per_page = query.per_page.unwrap_or(default_per_page as i64);
+        if page < 1 || per_page < 1 {
+            return Err(HttpResponse::BadRequest()
+                .json(json!({"error": "page and per_page must be at least 1"})));
+        }
+
+        Ok(Self {
+            page: page as usize,
+            per_page: (per_page as usize).min(max_per_page),
+        })
+    }
+
+    pub fn offset(&self) -> usize {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+
+    /// Just the items on this page
+    pub fn apply<T>(&self, items: Vec<T>) -> Vec<T> {
+        items
+            .into_iter()
+            .skip(self.offset())
+            .take(self.per_page)
+            .collect()
+    }
+}
+
+/// Points for display: whole numbers without a decimal point, anything else rounded to two
+/// decimal places with trailing zeros dropped. `grouped` adds thousands separators.
+pub fn format_points(value: f64, grouped: bool) -> String {
+    let rounded = (value * 100.0).round() / 100.0;
+    let whole = rounded.trunc() as i64;
+    let whole_str = if grouped {
+        group_thousands(whole.abs())
+    } else {
+        whole.abs().to_string()
+    };
+    let sign = if rounded < 0.0 { "-" } else { "" };
+    if rounded.fract() == 0.0 {
+        return format!("{}{}", sign, whole_str);
+    }
+    let fraction = format!("{:.2}", rounded.fract().abs());
+    format!(
+        "{}{}{}",
+        sign,
+        whole_str,
+        fraction.trim_start_matches('0').trim_end_matches('0')
+    )
+}
+
+/// Write an integer with commas between each group of three digits
+pub fn group_thousands(value: i64) -> String {
+    let digits = value.unsigned_abs().to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+    if value < 0 {
+        grouped.push('-');
+    }
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
 #[macro_export]
 macro_rules! ternary {
     ($condition: expr => $true_expr: expr , $false_expr: expr) => {
@@ -78,7 +429,7 @@ macro_rules! ternary {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::configurator::parser::{ApplicabilityRules, Configuration, Event, Form, Year};
+    use crate::configurator::parser::{ApplicabilityRules, Event, Form, Year};
     use crate::test_harness;
 
     #[test]
@@ -102,6 +453,228 @@ mod tests {
         assert_eq!(result, 0);
     }
 
+    #[test]
+    fn test_rank_totals_shares_ties() {
+        let totals = HashMap::from([
+            ("a".to_string(), 10),
+            ("b".to_string(), 7),
+            ("c".to_string(), 7),
+            ("d".to_string(), 0),
+        ]);
+        assert_eq!(
+            rank_totals(&totals),
+            vec![
+                ("a".to_string(), 10, 1),
+                ("b".to_string(), 7, 2),
+                ("c".to_string(), 7, 2),
+                ("d".to_string(), 0, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pagination_defaults() {
+        let pagination = Pagination::from_query(&PageQuery::default(), 50, 500).unwrap();
+        assert_eq!(
+            pagination,
+            Pagination {
+                page: 1,
+                per_page: 50
+            }
+        );
+        assert_eq!(pagination.offset(), 0);
+    }
+
+    #[test]
+    fn test_pagination_clamps_to_max() {
+        let query = PageQuery {
+            page: Some(3),
+            per_page: Some(10_000),
+        };
+        let pagination = Pagination::from_query(&query, 50, 500).unwrap();
+        assert_eq!(pagination.per_page, 500);
+        assert_eq!(pagination.offset(), 1000);
+
+        let items: Vec<i32> = (0..1200).collect();
+        assert_eq!(pagination.apply(items), (1000..1200).collect::<Vec<i32>>());
+    }
+
+    #[test]
+    fn test_pagination_rejects_negatives() {
+        for (page, per_page) in [(Some(-1), None), (None, Some(-5)), (Some(0), None)] {
+            let err = Pagination::from_query(&PageQuery { page, per_page }, 50, 500).unwrap_err();
+            assert_eq!(err.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[test]
+    fn test_format_points() {
+        assert_eq!(format_points(10.0, false), "10");
+        assert_eq!(format_points(10.5, false), "10.5");
+        assert_eq!(format_points(0.1 + 0.2, false), "0.3");
+        assert_eq!(format_points(-0.25, false), "-0.25");
+        assert_eq!(format_points(1234.5, true), "1,234.5");
+        assert_eq!(format_points(-1234.0, true), "-1,234");
+    }
+
+    #[test]
+    fn test_group_thousands() {
+        assert_eq!(group_thousands(0), "0");
+        assert_eq!(group_thousands(999), "999");
+        assert_eq!(group_thousands(1000), "1,000");
+        assert_eq!(group_thousands(12345), "12,345");
+        assert_eq!(group_thousands(-123456), "-123,456");
+    }
+
+    #[test]
+    fn test_sort_events_follows_config_order() {
+        let config = Configuration {
+            genders: vec!["boys".to_string(), "girls".to_string()],
+            years: vec![Year {
+                id: "y9".to_string(),
+                name: "Year 9".to_string(),
+            }],
+            events: ["sprint", "javelin", "relay"]
+                .iter()
+                .map(|id| Event {
+                    id: id.to_string(),
+                    name: id.to_string(),
+                    applicable_years: ApplicabilityRules::All,
+                    applicable_genders: ApplicabilityRules::All,
+                    tags: vec![],
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let event = |filter_key: &str, gender_id: &str| {
+            Events::new(
+                format!("y9-{}-{}", gender_id, filter_key),
+                filter_key.to_string(),
+                "y9".to_string(),
+                gender_id.to_string(),
+                filter_key.to_string(),
+                "{}".to_string(),
+            )
+        };
+        let mut events = vec![
+            event("zumba", "boys"),
+            event("relay", "girls"),
+            event("javelin", "boys"),
+            event("archery", "boys"),
+            event("relay", "boys"),
+            event("sprint", "boys"),
+        ];
+
+        sort_events(&config, &mut events);
+
+        let ids: Vec<&str> = events.iter().map(|event| event.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "y9-boys-sprint",
+                "y9-boys-javelin",
+                "y9-boys-relay",
+                "y9-girls-relay",
+                "y9-boys-archery",
+                "y9-boys-zumba",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_top_events() {
+        let config = Configuration {
+            genders: vec!["boys".to_string(), "girls".to_string()],
+            years: vec![Year {
+                id: "y9".to_string(),
+                name: "Year 9".to_string(),
+            }],
+            events: vec![Event {
+                id: "relay".to_string(),
+                name: "4x100m Relay".to_string(),
+                applicable_years: ApplicabilityRules::All,
+                applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
+            }],
+            ..Default::default()
+        };
+        let event = |filter_key: &str, gender_id: &str, scores: &str| {
+            Events::new(
+                format!("y9-{}-{}", gender_id, filter_key),
+                filter_key.to_string(),
+                "y9".to_string(),
+                gender_id.to_string(),
+                filter_key.to_string(),
+                scores.to_string(),
+            )
+        };
+        let events = vec![
+            event("shot", "boys", r#"{"a": "4", "b": "2"}"#),
+            event("relay", "girls", r#"{"a": "10", "b": "8"}"#),
+            event("sprint", "boys", r#"{"a": "0", "b": "0"}"#),
+            event("relay", "boys", r#"{"a": "3", "b": "3"}"#),
+            event("javelin", "girls", r#"{"a": "1"}"#),
+        ];
+
+        let top: Vec<(String, String, f64)> = top_events(&config, &events, 3)
+            .into_iter()
+            .map(|event| (event.id, event.name, event.total))
+            .collect();
+        assert_eq!(
+            top,
+            vec![
+                (
+                    "y9-girls-relay".to_string(),
+                    "4x100m Relay".to_string(),
+                    18.0
+                ),
+                // Tied with shot, but relay is earlier in the config
+                ("y9-boys-relay".to_string(), "4x100m Relay".to_string(), 6.0),
+                ("y9-boys-shot".to_string(), "shot".to_string(), 6.0),
+            ]
+        );
+
+        // Unscored events are never included
+        assert_eq!(top_events(&config, &events, 10).len(), 4);
+    }
+
+    #[test]
+    fn test_top_events_decimal_and_multipliers() {
+        let config = Configuration {
+            genders: vec!["boys".to_string(), "girls".to_string()],
+            gender_multipliers: BTreeMap::from([("girls".to_string(), 2.0)]),
+            ..Default::default()
+        };
+        let event = |filter_key: &str, gender_id: &str, scores: &str| {
+            Events::new(
+                format!("y9-{}-{}", gender_id, filter_key),
+                filter_key.to_string(),
+                "y9".to_string(),
+                gender_id.to_string(),
+                filter_key.to_string(),
+                scores.to_string(),
+            )
+        };
+        let events = vec![
+            event("shot", "boys", r#"{"a": "4.5", "b": "2"}"#),
+            event("relay", "girls", r#"{"a": "2.25", "b": "1"}"#),
+            event("sprint", "boys", r#"{"a": "0.5"}"#),
+        ];
+
+        let top: Vec<(String, f64)> = top_events(&config, &events, 10)
+            .into_iter()
+            .map(|event| (event.id, event.total))
+            .collect();
+        assert_eq!(
+            top,
+            vec![
+                ("y9-girls-relay".to_string(), 6.5),
+                ("y9-boys-shot".to_string(), 6.5),
+                ("y9-boys-sprint".to_string(), 0.5),
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn test_render_scoreboard_empty() {
         let db = test_harness::setup_db("utils_render_scoreboard_empty").await;
@@ -113,6 +686,7 @@ mod tests {
             years: vec![],
             forms: vec![],
             events: vec![],
+            ..Default::default()
         };
 
         let client = reqwest::Client::builder()
@@ -124,13 +698,17 @@ mod tests {
 
         let state = web::Data::new(crate::AppState {
             client,
-            config,
+            config: config.into(),
             pool: db,
             log_collector,
             oauth_creds: crate::OauthCreds {
                 client_id: "test".to_string(),
                 client_secret: "test".to_string(),
             },
+            sessions: Box::new(crate::session_store::MemorySessionStore::default()),
+            settings: crate::settings::Settings::default(),
+            config_path: crate::configurator::parser::DEFAULT_CONFIG_PATH.to_string(),
+            maintenance: Default::default(),
         });
 
         let html = render_scoreboard(state).await;
@@ -183,7 +761,9 @@ mod tests {
                 name: "Event 1".to_string(),
                 applicable_years: ApplicabilityRules::All,
                 applicable_genders: ApplicabilityRules::All,
+                tags: vec![],
             }],
+            ..Default::default()
         };
 
         let client = reqwest::Client::builder()
@@ -195,13 +775,17 @@ mod tests {
 
         let state = web::Data::new(crate::AppState {
             client,
-            config,
+            config: config.into(),
             pool: db,
             log_collector,
             oauth_creds: crate::OauthCreds {
                 client_id: "test".to_string(),
                 client_secret: "test".to_string(),
             },
+            sessions: Box::new(crate::session_store::MemorySessionStore::default()),
+            settings: crate::settings::Settings::default(),
+            config_path: crate::configurator::parser::DEFAULT_CONFIG_PATH.to_string(),
+            maintenance: Default::default(),
         });
 
         let html = render_scoreboard(state).await;
@@ -210,6 +794,305 @@ mod tests {
         assert!(html.len() > 100);
     }
 
+    #[tokio::test]
+    async fn test_compute_scoreboard_rankings() {
+        let db = test_harness::setup_db("utils_compute_scoreboard_rankings").await;
+
+        use crate::db::events::Events;
+        use crate::db::years::Years;
+
+        for year in ["y7", "y8"] {
+            Years::new(year.to_string(), year.to_string())
+                .insert(&db)
+                .await
+                .unwrap();
+        }
+        for (id, year, scores) in [
+            ("y7-mixed-100m", "y7", r#"{"a":"10","b":"4","c":"3"}"#),
+            ("y8-mixed-100m", "y8", r#"{"b":"3","c":"4","d":"1"}"#),
+        ] {
+            Events::new(
+                id.to_string(),
+                "100m".to_string(),
+                year.to_string(),
+                "mixed".to_string(),
+                "100m".to_string(),
+                scores.to_string(),
+            )
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let form = |id: &str| Form {
+            id: id.to_string(),
+            name: id.to_string(),
+            colour: "red".to_string(),
+        };
+        let state = test_harness::app_state(
+            db,
+            Configuration {
+                forms: vec![form("a"), form("b"), form("c"), form("d"), form("e")],
+                ..Default::default()
+            },
+        );
+
+        let data = compute_scoreboard(&state).await;
+        assert_eq!(
+            data.form_rankings,
+            vec![
+                ("a".to_string(), 10.0, 1),
+                ("b".to_string(), 7.0, 2),
+                ("c".to_string(), 7.0, 2),
+                ("d".to_string(), 1.0, 4),
+                // Unscored forms are still ranked, last
+                ("e".to_string(), 0.0, 5),
+            ]
+        );
+        assert_eq!(
+            data.year_rankings,
+            vec![("y7".to_string(), 17.0, 1), ("y8".to_string(), 8.0, 2)]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_compute_scoreboard_at() {
+        use chrono::TimeZone;
+
+        let db = test_harness::setup_db("utils_compute_scoreboard_at").await;
+        Years::new("y7".to_string(), "Year 7".to_string())
+            .insert(&db)
+            .await
+            .unwrap();
+        for id in ["y7-mixed-100m", "y7-mixed-relay"] {
+            Events::new(
+                id.to_string(),
+                id.to_string(),
+                "y7".to_string(),
+                "mixed".to_string(),
+                id.to_string(),
+                r#"{"a":0,"b":0}"#.to_string(),
+            )
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+        for (id, scores) in [
+            ("y7-mixed-100m", json!({"a": "5", "b": "0"})),
+            ("y7-mixed-relay", json!({"a": "0", "b": "3"})),
+            ("y7-mixed-100m", json!({"a": "5", "b": "8"})),
+        ] {
+            Events::set_scores(&db, id.to_string(), scores)
+                .await
+                .unwrap();
+        }
+        // Spread the changes over the day: 09:00, 11:00 and 14:00
+        crate::db::timed_conn(&db, |conn| {
+            conn.execute(
+                "UPDATE score_history SET changed_at = CASE id WHEN 1 THEN '2025-07-01T09:00:00.000Z' WHEN 2 THEN '2025-07-01T11:00:00.000Z' ELSE '2025-07-01T14:00:00.000Z' END",
+                [],
+            )
+        })
+        .await
+        .unwrap();
+
+        let form = |id: &str| Form {
+            id: id.to_string(),
+            name: id.to_string(),
+            colour: "red".to_string(),
+        };
+        let state = test_harness::app_state(
+            db,
+            Configuration {
+                forms: vec![form("a"), form("b")],
+                ..Default::default()
+            },
+        );
+        let at = |hour| Utc.with_ymd_and_hms(2025, 7, 1, hour, 0, 0).unwrap();
+
+        let lunchtime = compute_scoreboard_at(&state, at(12)).await;
+        assert_eq!(
+            lunchtime.form_rankings,
+            vec![("a".to_string(), 5.0, 1), ("b".to_string(), 3.0, 2)]
+        );
+        assert_eq!(lunchtime.grand_total, 8.0);
+
+        let morning = compute_scoreboard_at(&state, at(8)).await;
+        assert_eq!(morning.grand_total, 0.0);
+
+        // After the last change it matches the live scoreboard
+        assert_eq!(
+            compute_scoreboard_at(&state, at(15)).await,
+            compute_scoreboard(&state).await
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_compute_scoreboard_decimal_scores() {
+        let db = test_harness::setup_db("utils_compute_scoreboard_decimal").await;
+
+        use crate::db::events::Events;
+        use crate::db::years::Years;
+
+        Years::new("y7".to_string(), "Year 7".to_string())
+            .insert(&db)
+            .await
+            .unwrap();
+        for (id, scores) in [
+            ("y7-mixed-100m", r#"{"a":"10","b":"2.5"}"#),
+            ("y7-mixed-javelin", r#"{"a":0.75,"b":"3","c":"DNF"}"#),
+        ] {
+            Events::new(
+                id.to_string(),
+                id.to_string(),
+                "y7".to_string(),
+                "mixed".to_string(),
+                id.to_string(),
+                scores.to_string(),
+            )
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let form = |id: &str| Form {
+            id: id.to_string(),
+            name: id.to_string(),
+            colour: "red".to_string(),
+        };
+        let state = web::Data::new(test_harness::app_state(
+            db,
+            Configuration {
+                forms: vec![form("a"), form("b"), form("c")],
+                ..Default::default()
+            },
+        ));
+
+        let data = compute_scoreboard(&state).await;
+        assert_eq!(data.form_totals["a"], 10.75);
+        assert_eq!(data.form_totals["b"], 5.5);
+        // Non-numeric scores are still skipped
+        assert_eq!(data.form_totals["c"], 0.0);
+        assert_eq!(data.grand_total, 16.25);
+
+        let html: String = render_scoreboard(state).await.split_whitespace().collect();
+        assert!(html.contains(r#"id="total-a">10.75<"#));
+        assert!(html.contains(r#"id="total-b">5.5<"#));
+        assert!(html.contains(r#"id="total-c">0<"#));
+        assert!(html.contains(r#"id="total-total">16.25<"#));
+    }
+
+    #[tokio::test]
+    async fn test_compute_scoreboard_gender_multipliers() {
+        use crate::db::events::Events;
+        use crate::db::years::Years;
+
+        let db = test_harness::setup_db("utils_compute_scoreboard_multipliers").await;
+        Years::new("y7".to_string(), "Year 7".to_string())
+            .insert(&db)
+            .await
+            .unwrap();
+        for (id, gender_id, scores) in [
+            ("y7-boys-100m", "boys", r#"{"a":"10","b":"6"}"#),
+            ("y7-mixed-relay", "mixed", r#"{"a":"8","b":"3"}"#),
+        ] {
+            Events::new(
+                id.to_string(),
+                id.to_string(),
+                "y7".to_string(),
+                gender_id.to_string(),
+                id.to_string(),
+                scores.to_string(),
+            )
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let form = |id: &str| Form {
+            id: id.to_string(),
+            name: id.to_string(),
+            colour: "red".to_string(),
+        };
+        let state = test_harness::app_state(
+            db.clone(),
+            Configuration {
+                forms: vec![form("a"), form("b")],
+                gender_multipliers: BTreeMap::from([("mixed".to_string(), 0.5)]),
+                ..Default::default()
+            },
+        );
+
+        let data = compute_scoreboard(&state).await;
+        assert_eq!(data.form_totals["a"], 14.0);
+        assert_eq!(data.form_totals["b"], 7.5);
+        assert_eq!(data.year_totals["y7"], 21.5);
+        assert_eq!(data.grand_total, 21.5);
+
+        // The stored scores are untouched
+        let relay = Events::find_by_id(&db, "y7-mixed-relay".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(relay.parsed_decimal_scores()["a"], 8.0);
+    }
+
+    #[actix_web::test]
+    async fn test_render_scoreboard_reports_malformed_scores() {
+        let db = test_harness::setup_db("utils_render_scoreboard_malformed").await;
+
+        use crate::db::events::Events;
+        use crate::db::years::Years;
+
+        Years::new("y7".to_string(), "Year 7".to_string())
+            .insert(&db)
+            .await
+            .unwrap();
+        for (id, scores) in [
+            ("y7-mixed-100m", r#"{"a":"5","b":3}"#),
+            ("y7-mixed-relay", "not json"),
+        ] {
+            Events::new(
+                id.to_string(),
+                id.to_string(),
+                "y7".to_string(),
+                "mixed".to_string(),
+                id.to_string(),
+                scores.to_string(),
+            )
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let form = |id: &str| Form {
+            id: id.to_string(),
+            name: id.to_string(),
+            colour: "red".to_string(),
+        };
+        let state = web::Data::new(test_harness::app_state(
+            db,
+            Configuration {
+                forms: vec![form("a"), form("b")],
+                ..Default::default()
+            },
+        ));
+
+        let html: String = render_scoreboard(state.clone())
+            .await
+            .split_whitespace()
+            .collect();
+        // The good event still counts
+        assert!(html.contains(r#"id="total-a">5<"#));
+        assert!(html.contains(r#"id="total-b">3<"#));
+        assert!(html.contains(r#"id="skipped-events""#));
+
+        let entries = state.log_collector.get_entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].level, "WARN");
+        assert!(entries[0].message.contains("y7-mixed-relay"));
+    }
+
     // E2E test
     #[actix_web::test]
     async fn test_e2e_complete_scoreboard_calculation() {
@@ -270,6 +1153,7 @@ mod tests {
                     name: "100m Sprint".to_string(),
                     applicable_years: ApplicabilityRules::All,
                     applicable_genders: ApplicabilityRules::All,
+                    tags: vec![],
                 },
                 Event {
                     id: "relay".to_string(),
@@ -278,11 +1162,13 @@ mod tests {
                         ids: vec!["year8".to_string()],
                     },
                     applicable_genders: ApplicabilityRules::All,
+                    tags: vec![],
                 },
             ],
+            ..Default::default()
         };
 
-        let plan = crate::configurator::build::build_plan(config.clone());
+        let plan = crate::configurator::build::build_plan(config.clone()).unwrap();
         crate::configurator::run::run(plan, &pool).await.unwrap();
 
         // Set scores for multiple events
@@ -323,13 +1209,17 @@ mod tests {
 
         let state = web::Data::new(crate::AppState {
             client,
-            config,
+            config: config.into(),
             pool: pool.clone(),
             log_collector,
             oauth_creds: crate::OauthCreds {
                 client_id: "test".to_string(),
                 client_secret: "test".to_string(),
             },
+            sessions: Box::new(crate::session_store::MemorySessionStore::default()),
+            settings: crate::settings::Settings::default(),
+            config_path: crate::configurator::parser::DEFAULT_CONFIG_PATH.to_string(),
+            maintenance: Default::default(),
         });
 
         let html = render_scoreboard(state).await;
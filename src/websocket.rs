@@ -5,17 +5,95 @@ use actix_web_actors::ws; // Import the trait for stop()
 pub struct WsSession {
     pub channel_name: String,
     pub channels: Addr<ChannelsActor>,
+    heartbeat_interval: Duration,
+    client_timeout: Duration,
+    /// When the client last answered a ping or sent one of its own
+    last_heartbeat: Instant,
+    /// Close reason sent instead of subscribing, for clients not allowed on the channel
+    denied: Option<String>,
+}
+
+/// Channels with this prefix are only open to admins
+pub const ADMIN_CHANNEL_PREFIX: &str = "admin:";
+
+/// Whether subscribing to `channel` needs an admin session
+pub fn requires_admin(channel: &str) -> bool {
+    channel.starts_with(ADMIN_CHANNEL_PREFIX)
+}
+
+impl WsSession {
+    pub fn new(channel_name: String, channels: Addr<ChannelsActor>) -> Self {
+        WsSession {
+            channel_name,
+            channels,
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            client_timeout: DEFAULT_CLIENT_TIMEOUT,
+            last_heartbeat: Instant::now(),
+            denied: None,
+        }
+    }
+
+    /// Close the connection with `reason` as soon as it opens, without subscribing
+    pub fn deny(mut self, reason: impl Into<String>) -> Self {
+        self.denied = Some(reason.into());
+        self
+    }
+
+    /// How often the client is pinged, and how long it has to answer before it is dropped
+    pub fn with_heartbeat(
+        mut self,
+        heartbeat_interval: Duration,
+        client_timeout: Duration,
+    ) -> Self {
+        self.heartbeat_interval = heartbeat_interval;
+        self.client_timeout = client_timeout;
+        self
+    }
 }
 
 impl Actor for WsSession {
     type Context = ws::WebsocketContext<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
+        if let Some(reason) = self.denied.clone() {
+            log::debug!("WsSession denied {}: {}", self.channel_name, reason);
+            ctx.close(Some(ws::CloseReason {
+                code: ws::CloseCode::Policy,
+                description: Some(reason),
+            }));
+            ctx.stop();
+            return;
+        }
+
         log::debug!("WsSession started, subscribing to {}", self.channel_name);
         self.channels.do_send(Subscribe {
             channel: self.channel_name.clone(),
             addr: ctx.address().recipient(),
         });
+
+        // Clients that vanish without a close frame would otherwise hold their subscription forever
+        ctx.run_interval(self.heartbeat_interval, |session, ctx| {
+            if session.last_heartbeat.elapsed() > session.client_timeout {
+                log::debug!("WebSocket client missed its heartbeat, disconnecting");
+                ctx.stop();
+                return;
+            }
+            ctx.ping(b"");
+        });
+    }
+
+    fn stopped(&mut self, ctx: &mut Self::Context) {
+        if self.denied.is_some() {
+            return;
+        }
+        log::debug!(
+            "WsSession stopped, unsubscribing from {}",
+            self.channel_name
+        );
+        self.channels.do_send(Unsubscribe {
+            channel: self.channel_name.clone(),
+            addr: ctx.address().recipient(),
+        });
     }
 }
 
@@ -27,7 +105,11 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
                 log::debug!("Received from client: {}", text);
                 ctx.text(format!("echo: {}", text));
             }
-            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Ping(msg)) => {
+                self.last_heartbeat = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Pong(_)) => self.last_heartbeat = Instant::now(),
             Ok(ws::Message::Close(reason)) => {
                 log::debug!("Client disconnected");
                 ctx.close(reason);
@@ -48,8 +130,54 @@ impl Handler<BroadcastMessage> for WsSession {
     }
 }
 
-use actix::{Message, Recipient};
+/// Forwards broadcasts to a Server-Sent Events response, for networks that block WebSockets
+pub struct SseSession {
+    pub tx: futures::channel::mpsc::UnboundedSender<String>,
+}
+
+impl Actor for SseSession {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        // The receiver is dropped with the response stream when the client goes away
+        ctx.run_interval(DEFAULT_SSE_CHECK_INTERVAL, |session, ctx| {
+            if session.tx.is_closed() {
+                log::debug!("SSE client disconnected");
+                ctx.stop();
+            }
+        });
+    }
+}
+
+impl Handler<BroadcastMessage> for SseSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: BroadcastMessage, ctx: &mut Self::Context) {
+        if self.tx.unbounded_send(msg.0).is_err() {
+            log::debug!("SSE client disconnected");
+            ctx.stop();
+        }
+    }
+}
+
+use actix::{dev::SendError, Message, Recipient};
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Default interval between pings sent to each WebSocket client
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Default time a WebSocket client may go without answering a ping before it is disconnected
+pub const DEFAULT_CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often an idle SSE session checks whether its client has gone
+pub const DEFAULT_SSE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Default window in which consecutive publishes to a channel are collapsed into one broadcast
+pub const DEFAULT_COALESCE_WINDOW: Duration = Duration::from_millis(250);
+
+/// Default interval between sweeps for channels whose clients have all gone
+pub const DEFAULT_REAP_INTERVAL: Duration = Duration::from_secs(60);
 
 #[derive(Message, Clone)]
 #[rtype(result = "()")]
@@ -78,29 +206,86 @@ impl Channels {
             .push(client);
     }
 
-    pub fn broadcast(&self, channel: &str, msg: String) {
-        if let Some(ch) = self.inner.get(channel) {
-            for client in &ch.clients {
-                let _ = client.do_send(BroadcastMessage(msg.clone()));
+    /// Remove a client, and its channel if that leaves it empty
+    pub fn unsubscribe(&mut self, channel: &str, client: &Recipient<BroadcastMessage>) {
+        if let Some(ch) = self.inner.get_mut(channel) {
+            ch.clients.retain(|c| c != client);
+            if ch.clients.is_empty() {
+                self.inner.remove(channel);
             }
         }
     }
+
+    /// Drop disconnected clients and remove channels left with none, returning how many were removed
+    pub fn reap(&mut self) -> usize {
+        let before = self.inner.len();
+        self.inner.retain(|_, ch| {
+            ch.clients.retain(|client| client.connected());
+            !ch.clients.is_empty()
+        });
+        before - self.inner.len()
+    }
+
+    /// Send to every client of a channel, dropping any that have gone
+    pub fn broadcast(&mut self, channel: &str, msg: String) {
+        if let Some(ch) = self.inner.get_mut(channel) {
+            ch.clients.retain(|client| {
+                !matches!(
+                    client.try_send(BroadcastMessage(msg.clone())),
+                    Err(SendError::Closed(_))
+                )
+            });
+        }
+    }
 }
 
 pub struct ChannelsActor {
     state: Channels,
+    coalesce_window: Duration,
+    reap_interval: Duration,
+    pending: HashMap<String, String>,
 }
 
 impl ChannelsActor {
     pub fn new() -> Self {
+        Self::with_coalesce_window(DEFAULT_COALESCE_WINDOW)
+    }
+
+    /// A zero window disables coalescing and broadcasts every publish immediately
+    pub fn with_coalesce_window(coalesce_window: Duration) -> Self {
         ChannelsActor {
             state: Channels::new(),
+            coalesce_window,
+            reap_interval: DEFAULT_REAP_INTERVAL,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// How often empty channels are removed from the map
+    pub fn with_reap_interval(mut self, reap_interval: Duration) -> Self {
+        self.reap_interval = reap_interval;
+        self
+    }
+
+    fn flush(&mut self, channel: &str) {
+        if let Some(payload) = self.pending.remove(channel) {
+            log::debug!("Flushing coalesced publish to channel: {}", channel);
+            self.state.broadcast(channel, payload);
         }
     }
 }
 
 impl Actor for ChannelsActor {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(self.reap_interval, |actor, _| {
+            let reaped = actor.state.reap();
+            if reaped > 0 {
+                log::debug!("Reaped {} empty channels", reaped);
+            }
+        });
+    }
 }
 
 #[derive(Message)]
@@ -110,6 +295,13 @@ pub struct Subscribe {
     pub addr: Recipient<BroadcastMessage>,
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Unsubscribe {
+    pub channel: String,
+    pub addr: Recipient<BroadcastMessage>,
+}
+
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct Publish {
@@ -117,6 +309,19 @@ pub struct Publish {
     pub payload: String,
 }
 
+/// Number of channels currently held
+#[derive(Message)]
+#[rtype(result = "usize")]
+pub struct ChannelCount;
+
+impl Handler<ChannelCount> for ChannelsActor {
+    type Result = usize;
+
+    fn handle(&mut self, _: ChannelCount, _: &mut Self::Context) -> usize {
+        self.state.inner.len()
+    }
+}
+
 impl Handler<Subscribe> for ChannelsActor {
     type Result = ();
 
@@ -126,12 +331,35 @@ impl Handler<Subscribe> for ChannelsActor {
     }
 }
 
+impl Handler<Unsubscribe> for ChannelsActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: Unsubscribe, _: &mut Self::Context) {
+        log::debug!("Unsubscribing from channel: {}", msg.channel);
+        self.state.unsubscribe(&msg.channel, &msg.addr);
+    }
+}
+
 impl Handler<Publish> for ChannelsActor {
     type Result = ();
 
-    fn handle(&mut self, msg: Publish, _: &mut Self::Context) {
+    fn handle(&mut self, msg: Publish, ctx: &mut Self::Context) {
         log::debug!("Publishing to channel: {}", msg.channel);
-        self.state.broadcast(&msg.channel, msg.payload);
+        if self.coalesce_window.is_zero() {
+            self.state.broadcast(&msg.channel, msg.payload);
+            return;
+        }
+
+        // Only the latest payload is kept while a broadcast is pending
+        if self
+            .pending
+            .insert(msg.channel.clone(), msg.payload)
+            .is_none()
+        {
+            ctx.run_later(self.coalesce_window, move |actor, _| {
+                actor.flush(&msg.channel);
+            });
+        }
     }
 }
 
@@ -163,7 +391,7 @@ mod tests {
 
     #[test]
     fn test_channels_broadcast_nonexistent_channel() {
-        let channels = Channels::new();
+        let mut channels = Channels::new();
         // Broadcasting to a non-existent channel should not panic
         channels.broadcast("nonexistent", "test message".to_string());
     }
@@ -206,6 +434,222 @@ mod tests {
         assert!(!msg.0.is_empty());
     }
 
+    struct Collector {
+        received: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl Actor for Collector {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<BroadcastMessage> for Collector {
+        type Result = ();
+
+        fn handle(&mut self, msg: BroadcastMessage, _: &mut Self::Context) {
+            self.received.lock().unwrap().push(msg.0);
+        }
+    }
+
+    async fn publish_three(channels: ChannelsActor) -> Vec<String> {
+        let received = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let collector = Collector {
+            received: received.clone(),
+        }
+        .start();
+        let addr = channels.start();
+
+        addr.send(Subscribe {
+            channel: "scores".to_string(),
+            addr: collector.recipient(),
+        })
+        .await
+        .unwrap();
+        for i in 1..=3 {
+            addr.send(Publish {
+                channel: "scores".to_string(),
+                payload: i.to_string(),
+            })
+            .await
+            .unwrap();
+        }
+
+        actix_rt::time::sleep(Duration::from_millis(200)).await;
+        let received = received.lock().unwrap().clone();
+        received
+    }
+
+    #[actix_rt::test]
+    async fn test_publish_coalesces_bursts() {
+        let received = publish_three(ChannelsActor::with_coalesce_window(Duration::from_millis(
+            50,
+        )))
+        .await;
+
+        assert!(received.len() < 3);
+        assert_eq!(received.last(), Some(&"3".to_string()));
+    }
+
+    #[actix_rt::test]
+    async fn test_publish_zero_window_is_immediate() {
+        let received = publish_three(ChannelsActor::with_coalesce_window(Duration::ZERO)).await;
+
+        assert_eq!(received, vec!["1", "2", "3"]);
+    }
+
+    // Stops as soon as it starts, like a client that has disconnected
+    struct Leaver;
+
+    impl Actor for Leaver {
+        type Context = Context<Self>;
+
+        fn started(&mut self, ctx: &mut Self::Context) {
+            ctx.stop();
+        }
+    }
+
+    impl Handler<BroadcastMessage> for Leaver {
+        type Result = ();
+
+        fn handle(&mut self, _: BroadcastMessage, _: &mut Self::Context) {}
+    }
+
+    #[actix_rt::test]
+    async fn test_emptied_channel_is_reaped() {
+        let addr = ChannelsActor::new()
+            .with_reap_interval(Duration::from_millis(20))
+            .start();
+
+        addr.send(Subscribe {
+            channel: "scores".to_string(),
+            addr: Leaver.start().recipient(),
+        })
+        .await
+        .unwrap();
+
+        actix_rt::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(addr.send(ChannelCount).await.unwrap(), 0);
+    }
+
+    // A ping frame as the server sends it, and a masked pong as a browser would answer
+    const PING_FRAME: [u8; 2] = [0x89, 0x00];
+    const PONG_FRAME: [u8; 6] = [0x8a, 0x80, 0, 0, 0, 0];
+
+    fn heartbeat_session(
+        input: futures::channel::mpsc::UnboundedReceiver<
+            Result<actix_web::web::Bytes, actix_web::error::PayloadError>,
+        >,
+    ) -> impl futures::Stream<Item = Result<actix_web::web::Bytes, actix_web::Error>> {
+        let session = WsSession::new("scores".to_string(), ChannelsActor::new().start())
+            .with_heartbeat(Duration::from_millis(20), Duration::from_millis(60));
+        ws::WebsocketContext::create(session, input)
+    }
+
+    #[actix_rt::test]
+    async fn test_heartbeat_drops_silent_client() {
+        use futures::StreamExt;
+
+        let (_tx, rx) = futures::channel::mpsc::unbounded();
+        let mut output = heartbeat_session(rx);
+
+        let first = output.next().await.unwrap().unwrap();
+        assert_eq!(first.as_ref(), PING_FRAME);
+
+        // Without a pong the session stops and the output ends
+        let rest = actix_rt::time::timeout(Duration::from_secs(2), output.collect::<Vec<_>>())
+            .await
+            .unwrap();
+        assert!(!rest.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_heartbeat_survives_with_pongs() {
+        use futures::StreamExt;
+
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let mut output = heartbeat_session(rx);
+
+        // Answer every ping for well past the timeout
+        let answered = actix_rt::time::timeout(Duration::from_millis(200), async {
+            loop {
+                let frame = output.next().await.unwrap().unwrap();
+                assert_eq!(frame.as_ref(), PING_FRAME);
+                tx.unbounded_send(Ok(actix_web::web::Bytes::from_static(&PONG_FRAME)))
+                    .unwrap();
+            }
+        })
+        .await;
+        assert!(
+            answered.is_err(),
+            "session closed while the client was answering"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_unsubscribe_removes_client() {
+        let addr = ChannelsActor::new().start();
+        let collector = Collector {
+            received: Default::default(),
+        }
+        .start();
+
+        addr.send(Subscribe {
+            channel: "scores".to_string(),
+            addr: collector.clone().recipient(),
+        })
+        .await
+        .unwrap();
+        assert_eq!(addr.send(ChannelCount).await.unwrap(), 1);
+
+        addr.send(Unsubscribe {
+            channel: "scores".to_string(),
+            addr: collector.recipient(),
+        })
+        .await
+        .unwrap();
+        assert_eq!(addr.send(ChannelCount).await.unwrap(), 0);
+    }
+
+    #[actix_rt::test]
+    async fn test_broadcast_drops_stopped_clients() {
+        let received = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let mut channels = Channels::new();
+        channels.subscribe("scores", Leaver.start().recipient());
+        channels.subscribe(
+            "scores",
+            Collector {
+                received: received.clone(),
+            }
+            .start()
+            .recipient(),
+        );
+
+        // Give the leaver a moment to stop
+        actix_rt::time::sleep(Duration::from_millis(50)).await;
+        channels.broadcast("scores", "1".to_string());
+        assert_eq!(channels.inner["scores"].clients.len(), 1);
+
+        actix_rt::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(*received.lock().unwrap(), vec!["1"]);
+    }
+
+    #[actix_rt::test]
+    async fn test_ws_session_unsubscribes_when_stopped() {
+        use futures::StreamExt;
+
+        let channels = ChannelsActor::new().start();
+        let (_tx, rx) = futures::channel::mpsc::unbounded();
+        let session = WsSession::new("scores".to_string(), channels.clone())
+            .with_heartbeat(Duration::from_millis(20), Duration::from_millis(60));
+        let output = ws::WebsocketContext::create(session, rx);
+
+        // The silent client times out, which stops the session
+        actix_rt::time::timeout(Duration::from_secs(2), output.collect::<Vec<_>>())
+            .await
+            .unwrap();
+        actix_rt::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(channels.send(ChannelCount).await.unwrap(), 0);
+    }
+
     #[test]
     fn test_subscribe_struct() {
         // Test that Subscribe message fields can be accessed
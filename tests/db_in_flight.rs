@@ -0,0 +1,33 @@
+// DB_IN_FLIGHT is process-wide, so this lives in its own test binary where no other test's
+// queries can move it
+
+use sportsday_scoreboard_v2::db::{open_pool, timed_conn, DB_IN_FLIGHT, DB_POOL_SIZE};
+
+#[tokio::test]
+async fn in_flight_gauge_rises_during_concurrent_queries() {
+    let db = open_pool(":memory:", Some(2)).await.unwrap();
+    assert_eq!(DB_IN_FLIGHT.get(), 0);
+
+    let queries: Vec<_> = (0..8)
+        .map(|_| {
+            let db = db.clone();
+            tokio::spawn(async move {
+                timed_conn(&db, |_conn| {
+                    std::thread::sleep(std::time::Duration::from_millis(200));
+                    Ok(())
+                })
+                .await
+            })
+        })
+        .collect();
+
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    // Two are running and the rest are queued behind them
+    assert_eq!(DB_IN_FLIGHT.get(), 8);
+
+    for query in queries {
+        query.await.unwrap().unwrap();
+    }
+    assert_eq!(DB_IN_FLIGHT.get(), 0);
+    assert_eq!(DB_POOL_SIZE.get(), 2);
+}
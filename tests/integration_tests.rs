@@ -84,10 +84,12 @@ async fn test_configuration_loading_and_plan_building() {
             name: "Event 1".to_string(),
             applicable_years: ApplicabilityRules::All,
             applicable_genders: ApplicabilityRules::All,
+            tags: vec![],
         }],
+        ..Default::default()
     };
 
-    let plan = build_plan(config);
+    let plan = build_plan(config).unwrap();
     assert_eq!(plan.year_plans.len(), 1);
     assert_eq!(plan.year_plans[0].events.len(), 2); // boys + girls
 